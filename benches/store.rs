@@ -0,0 +1,52 @@
+//! Measure how many small configuration files can be stored per second.
+//!
+//! Run with `cargo bench --bench store`.
+
+use std::{env::temp_dir, time::Instant};
+
+use config_file::{StoreOptions, ToConfigFile};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Config {
+    host: String,
+    port: u16,
+    tags: Vec<String>,
+}
+
+fn bench(name: &str, iterations: u32, mut store: impl FnMut(u32)) {
+    let start = Instant::now();
+    for i in 0..iterations {
+        store(i);
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name}: {:.0} stores/s ({:?} per store)",
+        f64::from(iterations) / elapsed.as_secs_f64(),
+        elapsed / iterations
+    );
+}
+
+fn main() {
+    let dir = temp_dir().join("config-file-bench");
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = || Config {
+        host: "example.com".to_string(),
+        port: 443,
+        tags: vec!["example".to_string(), "test".to_string()],
+    };
+    let path = |i| dir.join(format!("config-{}.toml", i % 64));
+    bench("to_config_file", 2000, |i| {
+        config().to_config_file(path(i)).unwrap();
+    });
+    let options = StoreOptions::default().fsync(true);
+    bench("to_config_file_with_options (fsync)", 200, |i| {
+        config()
+            .to_config_file_with_options(path(i), &options)
+            .unwrap();
+    });
+    bench("to_config_file_if_changed", 2000, |i| {
+        config().to_config_file_if_changed(path(i)).unwrap();
+    });
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,58 @@
+use crate::{value::ConfigValue, Warning};
+
+/// Move the value found under @alias in @value to @key, unless there already
+/// is one. Both are dotted paths such as `server.max_conn`.
+pub(crate) fn rename(value: &mut ConfigValue, alias: &str, key: &str, warnings: &mut Vec<Warning>) {
+    let Some(aliased) = value.remove_path(alias) else {
+        return;
+    };
+    warnings.push(Warning::DeprecatedKey {
+        alias: alias.to_string(),
+        key: key.to_string(),
+    });
+    if value.get_path(key).is_none() {
+        value.set_path(key, aliased);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::value::to_value;
+
+    #[test]
+    fn test_rename() {
+        let mut value = to_value(&BTreeMap::from([(
+            "server",
+            BTreeMap::from([("max_conn", 4), ("port", 80)]),
+        )]))
+        .unwrap();
+        let mut warnings = Vec::new();
+        rename(
+            &mut value,
+            "server.max_conn",
+            "server.max_connections",
+            &mut warnings,
+        );
+        rename(
+            &mut value,
+            "server.port",
+            "server.max_connections",
+            &mut warnings,
+        );
+        rename(&mut value, "missing", "key", &mut warnings);
+        let expected = to_value(&BTreeMap::from([(
+            "server",
+            BTreeMap::from([("max_connections", 4)]),
+        )]))
+        .unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0].to_string(),
+            "`server.max_conn` is deprecated, use `server.max_connections` instead"
+        );
+    }
+}
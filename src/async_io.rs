@@ -0,0 +1,340 @@
+use std::{
+    future::Future,
+    io::ErrorKind,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{format::ConfigFormat, store_file, ConfigFileError, StoreOptions};
+
+/// Size in bytes above which [`AsyncFromConfigFile::from_config_file_async`]
+/// parses files on the blocking thread pool
+pub const DEFAULT_BLOCKING_THRESHOLD: usize = 256 * 1024;
+
+/// Trait for asynchronously loading a struct from a configuration file.
+/// This trait is automatically implemented when [`serde::Deserialize`] is.
+pub trait AsyncFromConfigFile: Sized {
+    /// Load ourselves from the configuration file located at @path
+    ///
+    /// Files larger than [`DEFAULT_BLOCKING_THRESHOLD`] are parsed with
+    /// [`tokio::task::spawn_blocking`] to avoid stalling the runtime.
+    fn from_config_file_async(
+        path: impl AsRef<Path>,
+    ) -> impl Future<Output = Result<Self, ConfigFileError>> + Send {
+        Self::from_config_file_async_with_threshold(path, DEFAULT_BLOCKING_THRESHOLD)
+    }
+
+    /// Load ourselves from the configuration file located at @path, parsing
+    /// it on the blocking thread pool if it is at least @threshold bytes long
+    fn from_config_file_async_with_threshold(
+        path: impl AsRef<Path>,
+        threshold: usize,
+    ) -> impl Future<Output = Result<Self, ConfigFileError>> + Send;
+
+    /// Load ourselves from the configuration file located at @path, or use
+    /// the default value if the file doesn't exist or can't be read within
+    /// @timeout, e.g. because it lives on a hung network mount.
+    ///
+    /// Note that a read stuck in the kernel keeps its blocking thread busy
+    /// after the timeout fires.
+    fn from_config_file_or_default_timeout(
+        path: impl AsRef<Path>,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Self, ConfigFileError>> + Send
+    where
+        Self: Default,
+    {
+        let load = Self::from_config_file_async(path);
+        async move {
+            match tokio::time::timeout(timeout, load).await {
+                Ok(Err(ConfigFileError::FileAccess(err))) if err.kind() == ErrorKind::NotFound => {
+                    Ok(Self::default())
+                }
+                Ok(result) => result,
+                Err(_) => Ok(Self::default()),
+            }
+        }
+    }
+}
+
+impl<C: DeserializeOwned + Send + 'static> AsyncFromConfigFile for C {
+    fn from_config_file_async_with_threshold(
+        path: impl AsRef<Path>,
+        threshold: usize,
+    ) -> impl Future<Output = Result<Self, ConfigFileError>> + Send {
+        let path = path.as_ref().to_path_buf();
+        async move {
+            let format =
+                ConfigFormat::from_path(&path).ok_or(ConfigFileError::UnsupportedFormat)?;
+            #[cfg(feature = "chaos")]
+            {
+                crate::chaos::read_error(&path)?;
+                if let Some(delay) = crate::chaos::read_delay(&path) {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+            let data = tokio::fs::read(&path)
+                .await
+                .map_err(ConfigFileError::FileAccess)?;
+            #[cfg(feature = "chaos")]
+            let data = crate::chaos::corrupt(&path, data);
+            if data.len() < threshold {
+                return format.deserialize(&data);
+            }
+            blocking(move || format.deserialize(&data)).await
+        }
+    }
+}
+
+/// Trait for asynchronously storing a struct into a configuration file.
+/// This trait is automatically implemented when [`serde::Serialize`] is.
+///
+/// The file is written on the blocking thread pool as the synchronous store
+/// does, keeping the permissions of the file it replaces and the symbolic
+/// links pointing to it. The data is first written to a temporary file next
+/// to @path which is then renamed over it, so cancelling the returned future
+/// never leaves a truncated configuration file behind.
+///
+/// Cancelling the returned future, such as when it times out, stops the
+/// store before the temporary file is renamed over @path and removes it, so
+/// that a store made afterwards is never overwritten by the cancelled one. A
+/// rename already under way when the future is dropped still completes.
+/// Files written in place, as [`StoreOptions::atomic`] allows, are only left
+/// untouched if the store is cancelled before writing starts.
+pub trait AsyncToConfigFile {
+    /// Store ourselves into the configuration file located at @path
+    fn to_config_file_async(
+        self,
+        path: impl AsRef<Path>,
+    ) -> impl Future<Output = Result<(), ConfigFileError>> + Send;
+
+    /// Store ourselves into the configuration file located at @path
+    /// according to @options
+    fn to_config_file_async_with_options(
+        self,
+        path: impl AsRef<Path>,
+        options: &StoreOptions,
+    ) -> impl Future<Output = Result<(), ConfigFileError>> + Send;
+}
+
+impl<C: Serialize> AsyncToConfigFile for C {
+    fn to_config_file_async(
+        self,
+        path: impl AsRef<Path>,
+    ) -> impl Future<Output = Result<(), ConfigFileError>> + Send {
+        store(self, path.as_ref(), StoreOptions::default())
+    }
+
+    fn to_config_file_async_with_options(
+        self,
+        path: impl AsRef<Path>,
+        options: &StoreOptions,
+    ) -> impl Future<Output = Result<(), ConfigFileError>> + Send {
+        store(self, path.as_ref(), options.clone())
+    }
+}
+
+/// Store @config into the configuration file located at @path according to
+/// @options, serializing it right away
+fn store<C: Serialize>(
+    config: C,
+    path: &Path,
+    options: StoreOptions,
+) -> impl Future<Output = Result<(), ConfigFileError>> + Send {
+    let data = options.path(path).and_then(|path| {
+        let format = ConfigFormat::from_path(&path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        let data = options.serialize(format, &config)?;
+        Ok((path.into_owned(), format, data))
+    });
+    async move {
+        let (path, format, data) = data?;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _cancel = Cancel(cancelled.clone());
+        let options = StoreOptions {
+            cancelled: Some(cancelled),
+            ..options
+        };
+        blocking(move || store_file(&path, format, data, &options)).await
+    }
+}
+
+/// Flag cancelling a store on the blocking thread pool when dropped along
+/// with the future waiting for it
+struct Cancel(Arc<AtomicBool>);
+
+impl Drop for Cancel {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Run @operation on the blocking thread pool
+async fn blocking<T: Send + 'static>(
+    operation: impl FnOnce() -> Result<T, ConfigFileError> + Send + 'static,
+) -> Result<T, ConfigFileError> {
+    match tokio::task::spawn_blocking(operation).await {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(err) => Err(ConfigFileError::FileAccess(std::io::Error::other(err))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::TestConfig;
+
+    #[tokio::test]
+    #[cfg(feature = "toml")]
+    async fn test_round_trip() {
+        use std::env::temp_dir;
+
+        let dir = temp_dir().join("config-file-async");
+        let path = dir.join("config.toml");
+        TestConfig::example()
+            .to_config_file_async(&path)
+            .await
+            .unwrap();
+        assert_eq!(
+            TestConfig::from_config_file_async(&path).await.unwrap(),
+            TestConfig::example()
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "toml"))]
+    async fn test_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join("config-file-async-secret.toml");
+        TestConfig::example()
+            .to_config_file_async(&path)
+            .await
+            .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        TestConfig::example()
+            .to_config_file_async(&path)
+            .await
+            .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "toml"))]
+    async fn test_symlink() {
+        let dir = std::env::temp_dir().join("config-file-async-symlink");
+        let _ = std::fs::remove_dir_all(&dir);
+        let target = dir.join("dotfiles/config.toml");
+        let link = dir.join("config.toml");
+        TestConfig::example()
+            .to_config_file_async(&target)
+            .await
+            .unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file_async(&link).await.unwrap();
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        let config = TestConfig::from_config_file_async(&target).await.unwrap();
+        assert_eq!(config.port, 8080);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "toml")]
+    async fn test_options() {
+        let path = std::env::temp_dir().join("config-file-async-options.toml");
+        let _ = std::fs::remove_file(&path);
+        let options = StoreOptions::new().overwrite(crate::OverwritePolicy::ErrorIfExists);
+        let store = |port| {
+            let mut config = TestConfig::example();
+            config.port = port;
+            config.to_config_file_async_with_options(&path, &options)
+        };
+        store(1).await.unwrap();
+        assert!(store(2).await.is_err());
+        let config = TestConfig::from_config_file_async(&path).await.unwrap();
+        assert_eq!(config.port, 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_cancelled() {
+        use crate::{FromConfigFile, ToConfigFile};
+
+        let dir = std::env::temp_dir().join("config-file-async-cancelled");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        drop(Cancel(cancelled.clone()));
+        let options = StoreOptions {
+            cancelled: Some(cancelled),
+            ..StoreOptions::new()
+        };
+        let data = b"port = 8080".to_vec();
+        assert!(store_file(&path, ConfigFormat::Toml, data, &options).is_err());
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "toml")]
+    async fn test_blocking_threshold() {
+        let config =
+            TestConfig::from_config_file_async_with_threshold("testdata/config.toml", 0).await;
+        assert_eq!(config.unwrap(), TestConfig::example());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "toml")]
+    async fn test_default_timeout() {
+        #[derive(Debug, Default, PartialEq, serde::Deserialize)]
+        struct Defaulted {
+            port: Option<u64>,
+        }
+
+        let timeout = Duration::from_secs(5);
+        let config =
+            Defaulted::from_config_file_or_default_timeout("testdata/config.toml", timeout).await;
+        assert_eq!(config.unwrap().port, Some(443));
+        let config = Defaulted::from_config_file_or_default_timeout("/tmp/foobar.toml", timeout);
+        assert_eq!(config.await.unwrap(), Defaulted::default());
+
+        #[cfg(feature = "chaos")]
+        {
+            let path = std::env::temp_dir().join("config-file-async-slow.toml");
+            TestConfig::example()
+                .to_config_file_async(&path)
+                .await
+                .unwrap();
+            crate::chaos::inject(&path, crate::chaos::Fault::SlowRead(timeout));
+            let config =
+                Defaulted::from_config_file_or_default_timeout(&path, Duration::from_millis(10));
+            assert_eq!(config.await.unwrap(), Defaulted::default());
+            crate::chaos::remove(&path);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown() {
+        let config = TestConfig::from_config_file_async("/tmp/foobar").await;
+        assert!(matches!(config, Err(ConfigFileError::UnsupportedFormat)));
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::ConfigFileError;
+
+/// Minimum number of digits of the timestamps of backups, in milliseconds
+/// since the Unix epoch
+const TIMESTAMP_DIGITS: usize = 13;
+
+/// How the previous content of a configuration file is kept when storing it
+#[derive(Debug, Clone)]
+pub(crate) enum Backup {
+    /// A single backup, named after the file followed by the given suffix
+    Suffix(OsString),
+    /// Up to the given number of backups, named after the file followed by
+    /// `.1` for the most recent one, `.2` for the previous one and so on
+    Numbered(usize),
+    /// Up to the given number of backups, named after the file followed by
+    /// the time they were taken at, in milliseconds since the Unix epoch
+    Timestamped(usize),
+}
+
+/// Keep the current content of @path, if any, as specified by @backup.
+/// The file will be written @in_place rather than replaced.
+pub(crate) fn backup(path: &Path, backup: &Backup, in_place: bool) -> Result<(), ConfigFileError> {
+    if !path.exists() {
+        return Ok(());
+    }
+    match backup {
+        Backup::Suffix(suffix) => keep(path, &sibling(path, suffix), in_place),
+        Backup::Numbered(0) | Backup::Timestamped(0) => Ok(()),
+        Backup::Numbered(count) => {
+            remove(&numbered(path, *count))?;
+            for number in (1..*count).rev() {
+                match std::fs::rename(numbered(path, number), numbered(path, number + 1)) {
+                    Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                    _ => {}
+                }
+            }
+            keep(path, &numbered(path, 1), in_place)
+        }
+        Backup::Timestamped(count) => {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis())
+                .unwrap_or_default();
+            keep(path, &numbered(path, timestamp), in_place)?;
+            prune(path, *count)
+        }
+    }
+}
+
+/// Remove the oldest timestamped backups of @path, keeping the @count most
+/// recent ones
+fn prune(path: &Path, count: usize) -> Result<(), ConfigFileError> {
+    let Some(name) = path.file_name().and_then(OsStr::to_str) else {
+        return Ok(());
+    };
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{name}.");
+    let mut timestamps = std::fs::read_dir(parent)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let timestamp = name.to_str()?.strip_prefix(&prefix)?;
+            // Shorter numbers are numbered backups or files of the user
+            if timestamp.len() < TIMESTAMP_DIGITS
+                || !timestamp.bytes().all(|byte| byte.is_ascii_digit())
+            {
+                return None;
+            }
+            timestamp.parse::<u128>().ok()
+        })
+        .collect::<Vec<_>>();
+    timestamps.sort_unstable_by(|a, b| b.cmp(a));
+    for timestamp in timestamps.into_iter().skip(count) {
+        remove(&numbered(path, timestamp))?;
+    }
+    Ok(())
+}
+
+/// Copy the current content of @path to @backup, before @path is written
+/// @in_place or replaced
+fn keep(path: &Path, backup: &Path, in_place: bool) -> Result<(), ConfigFileError> {
+    remove(backup)?;
+    // Writing in place would change the content of a link too
+    if in_place {
+        return match std::fs::copy(path, backup) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        };
+    }
+    // Linking leaves @path in place until the new content replaces it
+    match std::fs::hard_link(path, backup) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(_) => {
+            std::fs::copy(path, backup)?;
+            Ok(())
+        }
+    }
+}
+
+fn remove(path: &Path) -> Result<(), ConfigFileError> {
+    #[cfg(windows)]
+    crate::windows::make_writable(path)?;
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+fn sibling(path: &Path, suffix: impl AsRef<OsStr>) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn numbered(path: &Path, number: impl std::fmt::Display) -> PathBuf {
+    sibling(path, format!(".{number}"))
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, StoreOptions, ToConfigFile};
+
+    fn store_ports(path: &Path, options: &StoreOptions, ports: impl Iterator<Item = u64>) {
+        for port in ports {
+            let mut config = TestConfig::example();
+            config.port = port;
+            config.to_config_file_with_options(path, options).unwrap();
+        }
+    }
+
+    fn port(path: PathBuf) -> u64 {
+        let data = std::fs::read(path).unwrap();
+        crate::ConfigFormat::Toml
+            .deserialize::<TestConfig>(&data)
+            .unwrap()
+            .port
+    }
+
+    #[test]
+    fn test_numbered() {
+        let dir = temp_dir().join("config-backup-numbered");
+        let path = dir.join("config.toml");
+        let _ = std::fs::remove_dir_all(&dir);
+        store_ports(&path, &StoreOptions::new().numbered_backups(2), 1..=4);
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 4);
+        assert_eq!(port(numbered(&path, 1)), 3);
+        assert_eq!(port(numbered(&path, 2)), 2);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_timestamped() {
+        let dir = temp_dir().join("config-backup-timestamped");
+        let path = dir.join("config.toml");
+        let _ = std::fs::remove_dir_all(&dir);
+        store_ports(&path, &StoreOptions::new().numbered_backups(1), 0..=1);
+        let other = sibling(&path, ".2024");
+        std::fs::write(&other, "").unwrap();
+        let options = StoreOptions::new().timestamped_backups(2);
+        for port in 1..=4 {
+            store_ports(&path, &options, std::iter::once(port));
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        let mut backups = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|backup| ![&path, &other, &numbered(&path, 1)].contains(&backup))
+            .collect::<Vec<_>>();
+        backups.sort();
+        assert_eq!(backups.into_iter().map(port).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(port(numbered(&path, 1)), 0);
+        assert!(other.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "chaos")]
+    #[test]
+    fn test_retried() {
+        let dir = temp_dir().join("config-backup-retried");
+        let path = dir.join("config.toml");
+        let _ = std::fs::remove_dir_all(&dir);
+        let options = StoreOptions::new()
+            .numbered_backups(3)
+            .retry(crate::RetryPolicy::new(3, std::time::Duration::ZERO));
+        store_ports(&path, &options, 1..=2);
+        crate::chaos::inject(
+            &path,
+            crate::chaos::Fault::WriteError(std::io::ErrorKind::Interrupted),
+        );
+        let result = TestConfig::example().to_config_file_with_options(&path, &options);
+        crate::chaos::remove(&path);
+        assert!(result.is_err());
+        assert_eq!(port(path.clone()), 2);
+        assert_eq!(port(numbered(&path, 1)), 2);
+        assert_eq!(port(numbered(&path, 2)), 1);
+        assert!(!numbered(&path, 3).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_followed_link() {
+        let dir = temp_dir().join("config-backup-link");
+        let _ = std::fs::remove_dir_all(&dir);
+        let real = dir.join("real.toml");
+        let link = dir.join("config.toml");
+        store_ports(&real, &StoreOptions::new(), std::iter::once(1));
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        let options = StoreOptions::new()
+            .symlinks(crate::SymlinkPolicy::Follow)
+            .numbered_backups(2);
+        store_ports(&link, &options, 2..=3);
+        assert_eq!(port(real.clone()), 3);
+        assert_eq!(port(numbered(&real, 1)), 2);
+        assert_eq!(port(numbered(&real, 2)), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use std::{io::Read, path::Path};
+
+use serde::Deserialize;
+
+use crate::{format::ConfigFormat, open_file, ConfigFileError};
+
+/// Load the configuration file located at @path into @buffer, and load a `T`
+/// borrowing its strings from it rather than allocating each of them, such
+/// as a struct of `&str` or `Cow<str>` fields.
+///
+/// JSON files holding a [`Metadata`](crate::metadata::Metadata) header can't be
+/// borrowed from once it is stripped: their `Cow<str>` fields are then owned,
+/// and `&str` ones fail to load.
+///
+/// ```rust,no_run
+/// use config_file::load_borrowed;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config<'a> { host: &'a str }
+///
+/// let mut buffer = Vec::new();
+/// let config: Config<'_> = load_borrowed("/etc/myconfig.json", &mut buffer).unwrap();
+/// ```
+pub fn load_borrowed<'de, T: Deserialize<'de>>(
+    path: impl AsRef<Path>,
+    buffer: &'de mut Vec<u8>,
+) -> Result<T, ConfigFileError> {
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+    buffer.clear();
+    open_file(path)?
+        .read_to_end(buffer)
+        .map_err(ConfigFileError::FileAccess)?;
+    format.deserialize_borrowed(buffer)
+}
+
+#[cfg(all(test, any(feature = "json", feature = "toml")))]
+mod test {
+    use std::{borrow::Cow, env::temp_dir};
+
+    use super::*;
+    use crate::{test::TestConfig, ToConfigFile};
+
+    #[cfg(feature = "toml")]
+    #[derive(Deserialize)]
+    struct Borrowed<'a> {
+        host: &'a str,
+        #[serde(borrow)]
+        tags: Vec<Cow<'a, str>>,
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_load_borrowed() {
+        let path = temp_dir().join("config-borrowed.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let mut buffer = Vec::new();
+        let config: Borrowed<'_> = load_borrowed(&path, &mut buffer).unwrap();
+        assert_eq!(config.host, "example.com");
+        assert_eq!(config.tags, ["example", "test"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_load_borrowed_metadata() {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Strict<'a> {
+            #[serde(borrow)]
+            host: Cow<'a, str>,
+            port: u64,
+            #[serde(borrow)]
+            tags: Vec<Cow<'a, str>>,
+            inner: crate::test::TestConfigInner,
+        }
+
+        let path = temp_dir().join("config-borrowed-metadata.json");
+        TestConfig::example()
+            .to_config_file_with_metadata(&path, &crate::metadata::Metadata::new("myapp", 3))
+            .unwrap();
+        let mut buffer = Vec::new();
+        let config: Strict<'_> = load_borrowed(&path, &mut buffer).unwrap();
+        assert_eq!(config.host, "example.com");
+        assert_eq!(config.port, 443);
+        assert_eq!(config.tags, ["example", "test"]);
+        assert_eq!(config.inner.answer, 42);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{ConfigFileError, LoadOptions};
+
+/// Loader of a configuration file which only parses it again once its
+/// modification time or its size changed, for code paths reloading it
+/// defensively.
+///
+/// A change keeping both the size and the modification time, which some
+/// filesystems only track by the second, goes unnoticed.
+///
+/// ```rust,no_run
+/// use config_file::CachedLoader;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let loader = CachedLoader::<Config>::new("/etc/myconfig.toml");
+/// let port = loader.load().unwrap().port;
+/// ```
+pub struct CachedLoader<T> {
+    path: PathBuf,
+    options: LoadOptions,
+    cached: Mutex<Option<(Stamp, Arc<T>)>>,
+}
+
+/// What tells apart two versions of a file
+#[derive(PartialEq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl<T: DeserializeOwned> CachedLoader<T> {
+    /// Create a loader of the configuration file located at @path
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self::with_options(path, LoadOptions::default())
+    }
+
+    /// Create a loader of the configuration file located at @path according
+    /// to @options
+    pub fn with_options(path: impl AsRef<Path>, options: LoadOptions) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            options,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Get the configuration, loading the file again only if it changed
+    /// since it was last loaded
+    pub fn load(&self) -> Result<Arc<T>, ConfigFileError> {
+        let metadata = std::fs::metadata(&self.path)?;
+        let stamp = Stamp {
+            modified: metadata.modified()?,
+            len: metadata.len(),
+        };
+        let mut cached = self.cached.lock().unwrap_or_else(PoisonError::into_inner);
+        match &*cached {
+            Some((cached_stamp, config)) if *cached_stamp == stamp => Ok(config.clone()),
+            _ => {
+                let config: Arc<T> = Arc::new(self.options.load(&self.path)?.0);
+                *cached = Some((stamp, config.clone()));
+                Ok(config)
+            }
+        }
+    }
+
+    /// Forget the cached configuration, so that the next
+    /// [`load`](Self::load) parses the file again
+    pub fn invalidate(&self) {
+        *self.cached.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, ToConfigFile};
+
+    #[test]
+    fn test_cached_loader() {
+        let path = temp_dir().join("config-cached-loader.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let loader = CachedLoader::<TestConfig>::new(&path);
+        let config = loader.load().unwrap();
+        assert!(Arc::ptr_eq(&config, &loader.load().unwrap()));
+
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        let config = loader.load().unwrap();
+        assert_eq!(config.port, 8080);
+        loader.invalidate();
+        assert!(!Arc::ptr_eq(&config, &loader.load().unwrap()));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(loader.load().is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Failure injection to test how applications handle configuration errors.
+//!
+//! Faults are registered per path and affect every load and store going
+//! through this crate for that path until they are removed, so error handling
+//! paths can be exercised deterministically in CI.
+//!
+//! ```rust
+//! # #[cfg(feature = "toml")] {
+//! use config_file::{
+//!     chaos::{self, Fault},
+//!     ConfigFileError, FromConfigFile,
+//! };
+//! # #[derive(serde::Deserialize)]
+//! # struct Config {}
+//!
+//! chaos::inject("/etc/myconfig.toml", Fault::ReadError(std::io::ErrorKind::PermissionDenied));
+//! let config = Config::from_config_file("/etc/myconfig.toml");
+//! assert!(matches!(config, Err(ConfigFileError::FileAccess(_))));
+//! chaos::remove("/etc/myconfig.toml");
+//! # }
+//! ```
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// A failure to inject
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Reading the file fails with an error of the given kind
+    ReadError(io::ErrorKind),
+    /// Reading the file takes at least the given duration
+    SlowRead(Duration),
+    /// The file content read is corrupted so that parsing it fails
+    ParseError,
+    /// Writing the file fails with an error of the given kind
+    WriteError(io::ErrorKind),
+    /// Only the given number of bytes are written before writing fails
+    PartialWrite(usize),
+}
+
+static FAULTS: Mutex<Vec<(PathBuf, Fault)>> = Mutex::new(Vec::new());
+
+/// Inject @fault for the file located at @path
+pub fn inject(path: impl AsRef<Path>, fault: Fault) {
+    let path = normalize(path.as_ref());
+    faults().push((path, fault));
+}
+
+/// Remove all the faults injected for the file located at @path
+pub fn remove(path: impl AsRef<Path>) {
+    let path = normalize(path.as_ref());
+    faults().retain(|(faulty, _)| *faulty != path);
+}
+
+/// Remove all the injected faults
+pub fn clear() {
+    faults().clear();
+}
+
+fn faults() -> std::sync::MutexGuard<'static, Vec<(PathBuf, Fault)>> {
+    FAULTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn find<T>(path: &Path, select: impl Fn(&Fault) -> Option<T>) -> Option<T> {
+    let faults = faults();
+    if faults.is_empty() {
+        return None;
+    }
+    let path = normalize(path);
+    faults
+        .iter()
+        .filter(|(faulty, _)| *faulty == path)
+        .find_map(|(_, fault)| select(fault))
+}
+
+/// Fail if a read error was injected for @path
+pub(crate) fn read_error(path: &Path) -> io::Result<()> {
+    match find(path, |fault| match fault {
+        Fault::ReadError(kind) => Some(*kind),
+        _ => None,
+    }) {
+        Some(kind) => Err(io::Error::new(kind, "injected read failure")),
+        None => Ok(()),
+    }
+}
+
+/// How long reading @path should be delayed
+pub(crate) fn read_delay(path: &Path) -> Option<Duration> {
+    find(path, |fault| match fault {
+        Fault::SlowRead(delay) => Some(*delay),
+        _ => None,
+    })
+}
+
+/// Corrupt @data, read from @path, if a parse error was injected for it
+pub(crate) fn corrupt(path: &Path, data: Vec<u8>) -> Vec<u8> {
+    match find(path, |fault| (*fault == Fault::ParseError).then_some(())) {
+        // Invalid in every supported format
+        Some(()) => b"{[<".to_vec(),
+        None => data,
+    }
+}
+
+/// Fail if a write error was injected for @path
+pub(crate) fn write_error(path: &Path) -> io::Result<()> {
+    match find(path, |fault| match fault {
+        Fault::WriteError(kind) => Some(*kind),
+        _ => None,
+    }) {
+        Some(kind) => Err(io::Error::new(kind, "injected write failure")),
+        None => Ok(()),
+    }
+}
+
+/// How many bytes can be written to @path before failing
+pub(crate) fn partial_write(path: &Path) -> Option<usize> {
+    find(path, |fault| match fault {
+        Fault::PartialWrite(written) => Some(*written),
+        _ => None,
+    })
+}
+
+/// The error returned after a partial write
+pub(crate) fn partial_write_error() -> io::Error {
+    io::Error::new(io::ErrorKind::WriteZero, "injected partial write")
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::{env::temp_dir, time::Instant};
+
+    use super::*;
+    use crate::{test::TestConfig, ConfigFileError, FromConfigFile, ToConfigFile};
+
+    #[test]
+    fn test_read_faults() {
+        let path = temp_dir().join("config-chaos-read.toml");
+        inject(&path, Fault::ReadError(io::ErrorKind::PermissionDenied));
+        let config = TestConfig::from_config_file(&path);
+        assert!(
+            matches!(config, Err(ConfigFileError::FileAccess(err)) if err.kind() == io::ErrorKind::PermissionDenied)
+        );
+        remove(&path);
+        assert!(read_error(&path).is_ok());
+    }
+
+    #[test]
+    fn test_parse_and_slow_read() {
+        let path = temp_dir().join("config-chaos-parse.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        inject(&path, Fault::SlowRead(Duration::from_millis(50)));
+        inject(&path, Fault::ParseError);
+        let start = Instant::now();
+        let config = TestConfig::from_config_file(&path);
+        assert!(matches!(config, Err(ConfigFileError::Toml(_))));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        remove(&path);
+        assert!(TestConfig::from_config_file(&path).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_partial_write() {
+        let dir = temp_dir().join("config-chaos-write");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        inject(&path, Fault::PartialWrite(4));
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        let result = changed.to_config_file(&path);
+        assert!(matches!(result, Err(ConfigFileError::FileAccess(_))));
+        // The write is atomic, so the previous content is left untouched
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        remove(&path);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::{read_file, write_file_with_options, ConfigFileError, ConfigValue, StoreOptions};
+
+/// Path of the file holding the checksum of the file located at @path
+pub(crate) fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    path.with_file_name(name)
+}
+
+/// Write the checksum of @data, stored at @path, next to it in the format
+/// used by `sha256sum`
+pub(crate) fn write(path: &Path, data: &[u8], fsync: bool) -> Result<(), ConfigFileError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let sidecar = format!("{}  {name}\n", digest(data));
+    let options = StoreOptions::new().fsync(fsync);
+    write_file_with_options(&sidecar_path(path), sidecar.as_bytes(), &options)
+}
+
+/// Check that @data, read from @path, matches the checksum stored next to it
+pub(crate) fn verify(path: &Path, data: &[u8]) -> Result<(), ConfigFileError> {
+    let sidecar = read_file(&sidecar_path(path))?;
+    let expected = String::from_utf8_lossy(&sidecar);
+    match expected.split_whitespace().next() {
+        Some(expected) if expected.eq_ignore_ascii_case(&digest(data)) => Ok(()),
+        _ => Err(ConfigFileError::ChecksumMismatch(path.to_path_buf())),
+    }
+}
+
+fn digest(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// The checksum of @value, computed over an encoding which only depends on
+/// its content
+pub(crate) fn value_digest(value: &ConfigValue) -> String {
+    fn feed(hasher: &mut Sha256, value: &ConfigValue) {
+        let len = |hasher: &mut Sha256, len: usize| hasher.update((len as u64).to_le_bytes());
+        match value {
+            ConfigValue::Null => hasher.update(b"n"),
+            ConfigValue::Bool(value) => hasher.update([b'b', u8::from(*value)]),
+            ConfigValue::Integer(value) => {
+                hasher.update(b"i");
+                hasher.update(value.to_le_bytes());
+            }
+            ConfigValue::Float(value) => {
+                hasher.update(b"f");
+                hasher.update(value.to_bits().to_le_bytes());
+            }
+            ConfigValue::String(value) => {
+                hasher.update(b"s");
+                len(hasher, value.len());
+                hasher.update(value);
+            }
+            ConfigValue::Array(values) => {
+                hasher.update(b"a");
+                len(hasher, values.len());
+                values.iter().for_each(|value| feed(hasher, value));
+            }
+            ConfigValue::Map(map) => {
+                hasher.update(b"m");
+                len(hasher, map.len());
+                // Keys may keep the order of the document, which doesn't
+                // change the configuration
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                for (key, value) in entries {
+                    len(hasher, key.len());
+                    hasher.update(key);
+                    feed(hasher, value);
+                }
+            }
+        }
+    }
+    let mut hasher = Sha256::new();
+    feed(&mut hasher, value);
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, LoadOptions, ToConfigFile};
+
+    #[test]
+    fn test_checksum() {
+        let dir = temp_dir().join("config-checksum");
+        let path = dir.join("config.toml");
+        let store = StoreOptions::new().checksum(true);
+        let load = LoadOptions::new().verify_checksum(true);
+        TestConfig::example()
+            .to_config_file_with_options(&path, &store)
+            .unwrap();
+        assert!(std::fs::read_to_string(sidecar_path(&path))
+            .unwrap()
+            .ends_with("  config.toml\n"));
+        assert_eq!(
+            TestConfig::from_config_file_with_options(&path, &load).unwrap(),
+            TestConfig::example()
+        );
+
+        let mut data = std::fs::read_to_string(&path).unwrap();
+        data = data.replace("443", "444");
+        std::fs::write(&path, data).unwrap();
+        assert!(TestConfig::from_config_file(&path).is_ok());
+        let config = TestConfig::from_config_file_with_options(&path, &load);
+        assert!(matches!(config, Err(ConfigFileError::ChecksumMismatch(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use clap::{parser::ValueSource, ArgMatches};
+
+use crate::{
+    layers::{Layer, Layers},
+    ConfigValue,
+};
+
+impl Layer {
+    /// Create a layer named `command line` holding the arguments given in
+    /// @matches, so that they override the values loaded from files.
+    ///
+    /// Only arguments which were actually passed, on the command line or
+    /// through environment variables, are kept: default values never
+    /// override anything. Arguments are stored under their id with dashes
+    /// replaced by underscores, so that `--log-level` sets `log_level`, and
+    /// dots in ids, such as `server.port`, set nested values. Arguments
+    /// taking several values set arrays. Subcommands are ignored.
+    pub fn args(matches: &ArgMatches) -> Self {
+        let mut value = ConfigValue::Map(Default::default());
+        for id in matches.ids() {
+            let id = id.as_str();
+            match matches.value_source(id) {
+                Some(ValueSource::DefaultValue) | None => continue,
+                Some(_) => {}
+            }
+            let Ok(Some(raw)) = matches.try_get_raw(id) else {
+                continue;
+            };
+            let mut values = raw
+                .map(|raw| ConfigValue::String(raw.to_string_lossy().into_owned()))
+                .collect::<Vec<_>>();
+            let arg = match values.len() {
+                0 => continue,
+                1 => values.remove(0),
+                _ => ConfigValue::Array(values),
+            };
+            value.set_path(&id.replace('-', "_"), arg);
+        }
+        Self::new("command line", value)
+    }
+}
+
+impl Layers {
+    /// Add the arguments given in @matches on top of the existing layers, as
+    /// described in [`Layer::args`].
+    ///
+    /// ```rust,no_run
+    /// use clap::{Arg, Command};
+    /// use config_file::layers::Layers;
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config { port: u16 }
+    ///
+    /// let matches = Command::new("myapp")
+    ///     .arg(Arg::new("port").long("port"))
+    ///     .get_matches();
+    /// let config: Config = Layers::new()
+    ///     .file("/etc/myapp/config.toml")
+    ///     .unwrap()
+    ///     .args(&matches)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn args(self, matches: &ArgMatches) -> Self {
+        self.layer(Layer::args(matches))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{Arg, ArgAction, Command};
+
+    use super::*;
+    use crate::test::TestConfig;
+
+    #[test]
+    fn test_args() {
+        let command = Command::new("test")
+            .arg(Arg::new("host").long("host").default_value("localhost"))
+            .arg(Arg::new("port").long("port"))
+            .arg(Arg::new("tags").long("tag").action(ArgAction::Append))
+            .arg(Arg::new("inner.answer").long("answer"));
+        let matches = command
+            .try_get_matches_from(["test", "--port", "9090", "--tag", "a", "--tag", "b"])
+            .unwrap();
+        let config: TestConfig = Layers::new()
+            .layer(Layer::serialized("file", &TestConfig::example()).unwrap())
+            .args(&matches)
+            .build()
+            .unwrap();
+        assert_eq!(config.host, "example.com");
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.tags, ["a", "b"]);
+        assert_eq!(config.inner.answer, 42);
+    }
+}
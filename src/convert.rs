@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use crate::{format::ConfigFormat, read_file, write_file, ConfigFileError, ConfigValue};
+
+/// Convert the configuration file located at @src into the one located at
+/// @dst, guessing both formats from their extensions.
+///
+/// The configuration is loaded as a [`ConfigValue`], so any document can be
+/// converted without knowing its type. TOML datetimes become strings in
+/// formats which have no datetimes.
+///
+/// ```rust,no_run
+/// use config_file::convert;
+///
+/// convert("/etc/myconfig.json", "/etc/myconfig.toml").unwrap();
+/// ```
+pub fn convert(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(), ConfigFileError> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    let src_format = ConfigFormat::from_path(src).ok_or(ConfigFileError::UnsupportedFormat)?;
+    let dst_format = ConfigFormat::from_path(dst).ok_or(ConfigFileError::UnsupportedFormat)?;
+    convert_with_formats(src, src_format, dst, dst_format)
+}
+
+/// Convert the configuration file located at @src, in @src_format, into the
+/// one located at @dst, in @dst_format, whatever their extensions
+pub fn convert_with_formats(
+    src: impl AsRef<Path>,
+    src_format: ConfigFormat,
+    dst: impl AsRef<Path>,
+    dst_format: ConfigFormat,
+) -> Result<(), ConfigFileError> {
+    let mut value: ConfigValue = src_format.deserialize(&read_file(src.as_ref())?)?;
+    #[cfg(feature = "toml")]
+    let keeps_datetimes = dst_format == ConfigFormat::Toml;
+    #[cfg(not(feature = "toml"))]
+    let keeps_datetimes = false;
+    if !keeps_datetimes {
+        value.flatten_datetimes();
+    }
+    write_file(dst.as_ref(), &dst_format.serialize(&value)?)
+}
+
+/// Rewrite the configuration file located at @path the way this crate
+/// stores it, returning whether it changed.
+///
+/// Keys are sorted, unless the preserve_order feature is enabled, and the
+/// pretty printer of the format is used, so files
+/// edited by hand can be normalized before being committed, and
+/// normalization can be checked by a `false` result.
+pub fn reformat(path: impl AsRef<Path>) -> Result<bool, ConfigFileError> {
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+    let current = read_file(path)?;
+    let value: ConfigValue = format.deserialize(&current)?;
+    let data = format.serialize(&value)?;
+    if data == current {
+        return Ok(false);
+    }
+    write_file(path, &data)?;
+    Ok(true)
+}
+
+#[cfg(all(test, feature = "json", feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, ToConfigFile};
+
+    #[test]
+    fn test_convert() {
+        let src = temp_dir().join("config-convert.json");
+        let dst = temp_dir().join("config-convert.toml");
+        TestConfig::example().to_config_file(&src).unwrap();
+        convert(&src, &dst).unwrap();
+        assert_eq!(
+            TestConfig::from_config_file(&dst).unwrap(),
+            TestConfig::example()
+        );
+
+        let copy = temp_dir().join("config-convert.conf");
+        convert_with_formats(&dst, ConfigFormat::Toml, &copy, ConfigFormat::Json).unwrap();
+        assert_eq!(std::fs::read(&copy).unwrap(), std::fs::read(&src).unwrap());
+        assert!(convert(&src, &copy).is_err());
+        for path in [src, dst, copy] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reformat() {
+        let path = temp_dir().join("config-reformat.json");
+        std::fs::write(&path, r#"{"port": 443, "host":"example.com"}"#).unwrap();
+        assert!(reformat(&path).unwrap());
+        #[cfg(not(feature = "preserve_order"))]
+        let expected = "{\n  \"host\": \"example.com\",\n  \"port\": 443\n}";
+        #[cfg(feature = "preserve_order")]
+        let expected = "{\n  \"port\": 443,\n  \"host\": \"example.com\"\n}";
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+        assert!(!reformat(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_convert_datetimes() {
+        let src = temp_dir().join("config-convert-datetimes.toml");
+        let dst = temp_dir().join("config-convert-datetimes.json");
+        let datetimes = "ratio = 1.0\nwhen = 1979-05-27T07:32:00Z\n\n[day]\nstart = 1979-05-27\n";
+        std::fs::write(&src, datetimes).unwrap();
+        assert!(!reformat(&src).unwrap());
+        convert(&src, &dst).unwrap();
+        let value = ConfigValue::from_config_file(&dst).unwrap();
+        assert_eq!(value.get("when"), Some(&"1979-05-27T07:32:00Z".into()));
+        assert_eq!(value.get("ratio"), Some(&ConfigValue::Float(1.0)));
+        std::fs::remove_file(&src).unwrap();
+        convert(&dst, &src).unwrap();
+        let value = ConfigValue::from_config_file(&src).unwrap();
+        assert_eq!(value.get("day.start"), Some(&"1979-05-27".into()));
+        for path in [src, dst] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
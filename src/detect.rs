@@ -0,0 +1,166 @@
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use crate::{format::ConfigFormat, read_file, ConfigFileError};
+
+/// Load the configuration file located at @path, guessing its format from
+/// its content when its extension is missing or wrong, as described in
+/// [`ConfigFormat::from_content`].
+///
+/// The format given by the extension is tried first, so that content
+/// sniffing can only rescue files which would otherwise fail to load.
+///
+/// ```rust,no_run
+/// use config_file::load_detect;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { host: String }
+///
+/// let config: Config = load_detect("/etc/myapp/config").unwrap();
+/// ```
+pub fn load_detect<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, ConfigFileError> {
+    let path = path.as_ref();
+    let data = read_file(path)?;
+    match (
+        ConfigFormat::from_path(path),
+        ConfigFormat::from_content(&data),
+    ) {
+        (Some(extension), Some(content)) if extension != content => extension
+            .deserialize(&data)
+            .or_else(|err| content.deserialize(&data).map_err(|_| err)),
+        (Some(format), _) | (None, Some(format)) => format.deserialize(&data),
+        (None, None) => Err(ConfigFileError::UnsupportedFormat),
+    }
+}
+
+/// Load the configuration file located at @path with the first of @formats
+/// able to parse it, whatever its extension, returning the format which
+/// worked along with the configuration.
+///
+/// When none of them works, the error of the first format is returned.
+///
+/// ```rust,no_run
+/// # #[cfg(all(feature = "toml", feature = "yaml"))] {
+/// use config_file::{load_any, ConfigFormat};
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { host: String }
+///
+/// let (config, format): (Config, _) =
+///     load_any("/etc/myapp.toml", &[ConfigFormat::Toml, ConfigFormat::Yaml]).unwrap();
+/// # }
+/// ```
+pub fn load_any<T: DeserializeOwned>(
+    path: impl AsRef<Path>,
+    formats: &[ConfigFormat],
+) -> Result<(T, ConfigFormat), ConfigFileError> {
+    let data = read_file(path.as_ref())?;
+    let mut error = None;
+    for &format in formats {
+        match format.deserialize(&data) {
+            Ok(config) => return Ok((config, format)),
+            Err(err) => {
+                error.get_or_insert(err);
+            }
+        }
+    }
+    Err(error.unwrap_or(ConfigFileError::UnsupportedFormat))
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, ToConfigFile};
+
+    #[test]
+    fn test_from_content() {
+        let format = |data: &str| ConfigFormat::from_content(data.as_bytes());
+        assert_eq!(
+            format("\u{feff}# comment\n\nport = 1"),
+            Some(ConfigFormat::Toml)
+        );
+        assert_eq!(format("[inner]\nanswer = 42"), Some(ConfigFormat::Toml));
+        assert_eq!(format("[[servers]]\nport = 1"), Some(ConfigFormat::Toml));
+        assert_eq!(
+            format("url = \"http://example.com\""),
+            Some(ConfigFormat::Toml)
+        );
+        assert_eq!(format(""), None);
+        assert_eq!(format("# only comments"), None);
+        assert_eq!(format("port"), None);
+        #[cfg(feature = "json")]
+        {
+            assert_eq!(format("  {\"port\": 1}"), Some(ConfigFormat::Json));
+            assert_eq!(format("[1, 2]"), Some(ConfigFormat::Json));
+            assert_eq!(format("[\n  {}\n]"), Some(ConfigFormat::Json));
+        }
+        #[cfg(feature = "xml")]
+        assert_eq!(format("<?xml version=\"1.0\"?>"), Some(ConfigFormat::Xml));
+        #[cfg(feature = "yaml")]
+        {
+            assert_eq!(format("---\nport: 1"), Some(ConfigFormat::Yaml));
+            assert_eq!(format("port: 1"), Some(ConfigFormat::Yaml));
+            assert_eq!(format("- a\n- b"), Some(ConfigFormat::Yaml));
+            assert_eq!(format("url: http://example.com"), Some(ConfigFormat::Yaml));
+        }
+    }
+
+    #[test]
+    fn test_load_detect() {
+        let dir = temp_dir().join("config-detect");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let bare = dir.join("config");
+        std::fs::rename(&path, &bare).unwrap();
+        assert_eq!(
+            load_detect::<TestConfig>(&bare).unwrap(),
+            TestConfig::example()
+        );
+        #[cfg(feature = "json")]
+        {
+            let path = dir.join("config.json");
+            TestConfig::example().to_config_file(&path).unwrap();
+            let wrong = dir.join("config.yml.toml");
+            std::fs::rename(&path, &wrong).unwrap();
+            assert_eq!(
+                load_detect::<TestConfig>(&wrong).unwrap(),
+                TestConfig::example()
+            );
+        }
+        std::fs::write(&bare, "not a configuration").unwrap();
+        assert!(matches!(
+            load_detect::<TestConfig>(&bare),
+            Err(ConfigFileError::UnsupportedFormat)
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_any() {
+        let path = temp_dir().join("config-any.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let (config, format) = load_any::<TestConfig>(&path, &[ConfigFormat::Toml]).unwrap();
+        assert_eq!(config, TestConfig::example());
+        assert_eq!(format, ConfigFormat::Toml);
+        assert!(matches!(
+            load_any::<TestConfig>(&path, &[]),
+            Err(ConfigFileError::UnsupportedFormat)
+        ));
+        #[cfg(feature = "yaml")]
+        {
+            std::fs::write(&path, "host: example.com\nport: 443\n").unwrap();
+            let formats = [ConfigFormat::Toml, ConfigFormat::Yaml];
+            let (value, format) = load_any::<crate::ConfigValue>(&path, &formats).unwrap();
+            assert_eq!(value.get("port"), Some(&crate::ConfigValue::Integer(443)));
+            assert_eq!(format, ConfigFormat::Yaml);
+            assert!(matches!(
+                load_any::<TestConfig>(&path, &[ConfigFormat::Toml]),
+                Err(ConfigFileError::Toml(_))
+            ));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
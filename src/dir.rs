@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    format::ConfigFormat, value::from_value, ConfigFileError, ConfigValue, FromConfigFile,
+};
+
+/// Load a `T` from the configuration files located in the directory @dir,
+/// following the drop-in directory convention of `conf.d` directories.
+///
+/// Every file in a supported format is loaded, in lexical order, and merged
+/// into the previous ones: maps are merged recursively, while any other
+/// value replaces the one from the previous files. Other files are ignored,
+/// and a missing directory is handled as an empty one.
+///
+/// ```rust,no_run
+/// use config_file::load_dir;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config {}
+///
+/// // Loads 10-defaults.toml, then 50-site.toml, then 99-local.yaml
+/// let config: Config = load_dir("/etc/myapp/conf.d").unwrap();
+/// ```
+pub fn load_dir<T: DeserializeOwned>(dir: impl AsRef<Path>) -> Result<T, ConfigFileError> {
+    let (value, _) = merge_dir(dir.as_ref())?;
+    Ok(from_value(value)?)
+}
+
+/// The configuration files in a supported format located in @dir for which
+/// @matches holds, in lexical order. A missing directory holds no files.
+pub(crate) fn config_files(
+    dir: &Path,
+    matches: impl Fn(&Path) -> bool,
+) -> Result<Vec<PathBuf>, ConfigFileError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut files = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|file| file.is_file() && ConfigFormat::from_path(file).is_some() && matches(file));
+    files.sort();
+    Ok(files)
+}
+
+/// Merge the configuration files in a supported format located in @dir, in
+/// lexical order, returning the merged value along with the files. A missing
+/// directory holds no files.
+pub(crate) fn merge_dir(dir: &Path) -> Result<(ConfigValue, Vec<PathBuf>), ConfigFileError> {
+    let mut value = ConfigValue::Map(Default::default());
+    let files = config_files(dir, |_| true)?;
+    for file in &files {
+        value.merge(ConfigValue::from_config_file(file)?);
+    }
+    Ok((value, files))
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, ToConfigFile};
+
+    #[test]
+    fn test_load_dir() {
+        let dir = temp_dir().join("config-load-dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("50-port.toml"), "port = 8080").unwrap();
+        std::fs::write(dir.join("60-inner.toml"), "[inner]\nanswer = 0").unwrap();
+        std::fs::write(dir.join("README"), "ignored").unwrap();
+        TestConfig::example()
+            .to_config_file(dir.join("10-base.toml"))
+            .unwrap();
+
+        let config: TestConfig = load_dir(&dir).unwrap();
+        assert_eq!(config.host, "example.com");
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.inner.answer, 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    format::ConfigFormat, preserve_layout, read_file, value::to_value, write_file, ConfigFileError,
+    ConfigValue,
+};
+
+/// Load the configuration file located at @path, change it with @edit, and
+/// store it back, returning the edited configuration.
+///
+/// Nothing is stored if @edit fails, if the edited configuration can't be
+/// loaded back once serialized, or if it didn't change, so the file always
+/// holds either its previous content or the edited one.
+///
+/// ```rust,no_run
+/// use config_file::{edit, ConfigFileError};
+/// # #[derive(serde::Serialize, serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// edit("/etc/myconfig.toml", |config: &mut Config| {
+///     config.port = 8080;
+///     Ok::<_, ConfigFileError>(())
+/// })
+/// .unwrap();
+/// ```
+pub fn edit<T, E>(
+    path: impl AsRef<Path>,
+    edit: impl FnOnce(&mut T) -> Result<(), E>,
+) -> Result<T, E>
+where
+    T: Serialize + DeserializeOwned,
+    E: From<ConfigFileError>,
+{
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+    let snapshot = read_file(path)?;
+    let mut config = format.deserialize(&snapshot)?;
+    edit(&mut config)?;
+    let data = preserve_layout(path, format, format.serialize(&config)?);
+    format.deserialize::<T>(&data)?;
+    if data != snapshot {
+        write_file(path, &data)?;
+    }
+    Ok(config)
+}
+
+/// Set the value located at @key, made of dotted keys and array indices such
+/// as `server.port` or a JSON Pointer such as `/server/port`, to @value in
+/// the configuration file located at @path.
+///
+/// The file is loaded as a [`ConfigValue`], so the rest of the file is kept
+/// even when it doesn't match the types of the application, such as when it
+/// was written by a newer version of it. Nothing is stored if the value
+/// didn't change.
+///
+/// ```rust,no_run
+/// use config_file::update_key;
+///
+/// update_key("/etc/myconfig.toml", "server.port", &8080).unwrap();
+/// ```
+pub fn update_key<V: Serialize + ?Sized>(
+    path: impl AsRef<Path>,
+    key: &str,
+    value: &V,
+) -> Result<(), ConfigFileError> {
+    let value = to_value(value)?;
+    edit(path, |config: &mut ConfigValue| {
+        config.set(key, value).map_err(ConfigFileError::from)
+    })?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, ToConfigFile};
+
+    #[test]
+    fn test_edit() {
+        let path = temp_dir().join("config-edit.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let result = edit(&path, |config: &mut TestConfig| {
+            config.port = 8080;
+            Err(ConfigFileError::UnsupportedFormat)
+        });
+        assert!(result.is_err());
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 443);
+
+        let config = edit(&path, |config: &mut TestConfig| {
+            config.port = 8080;
+            Ok::<_, ConfigFileError>(())
+        })
+        .unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(TestConfig::from_config_file(&path).unwrap(), config);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_update_key() {
+        let path = temp_dir().join("config-update-key.toml");
+        std::fs::write(
+            &path,
+            "host = \"example.com\"\nport = 443\ntags = [\"example\", \"test\"]\n\
+             newer = true\n[inner]\nanswer = 42\n",
+        )
+        .unwrap();
+        update_key(&path, "inner.answer", &0).unwrap();
+        update_key(&path, "tags[2]", "new").unwrap();
+        let config = TestConfig::from_config_file(&path).unwrap();
+        assert_eq!(config.inner.answer, 0);
+        assert_eq!(config.tags, ["example", "test", "new"]);
+        let value = ConfigValue::from_config_file(&path).unwrap();
+        assert_eq!(value.get("newer"), Some(&ConfigValue::Bool(true)));
+        assert!(update_key(&path, "host[0]", &0).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{ConfigFileError, FromConfigFile, OverwritePolicy, StoreOptions, ToConfigFile};
+
+/// Load the configuration file located at @path, or store the default
+/// configuration there and return it if the file doesn't exist yet, so that
+/// users get a file they can edit on first run.
+///
+/// ```rust,no_run
+/// use config_file::load_or_store_default;
+/// # #[derive(Default, serde::Deserialize, serde::Serialize)]
+/// # struct Config { port: u16 }
+///
+/// let config: Config = load_or_store_default("/etc/myapp.toml").unwrap();
+/// ```
+pub fn load_or_store_default<T: DeserializeOwned + Serialize + Default>(
+    path: impl AsRef<Path>,
+) -> Result<T, ConfigFileError> {
+    let path = path.as_ref();
+    match T::from_config_file(path) {
+        Err(err) if is_missing(&err) => {
+            let config = T::default();
+            // Another instance or the user may create the file meanwhile
+            let options = StoreOptions::new().overwrite(OverwritePolicy::ErrorIfExists);
+            match (&config).to_config_file_with_options(path, &options) {
+                Err(ConfigFileError::FileAccess(err))
+                    if err.kind() == std::io::ErrorKind::AlreadyExists =>
+                {
+                    T::from_config_file(path)
+                }
+                result => result.map(|()| config),
+            }
+        }
+        config => config,
+    }
+}
+
+/// Why a configuration file couldn't be loaded, as given to the fallback of
+/// [`load_or_else_with`]
+#[derive(Debug)]
+pub enum LoadFailure {
+    /// The file doesn't exist
+    Missing,
+    /// The file exists, but couldn't be read or parsed
+    Invalid(ConfigFileError),
+}
+
+/// Load the configuration file located at @path, or compute a configuration
+/// with @fallback if the file doesn't exist, which unlike
+/// [`load_or_store_default`] doesn't require `T` to implement `Default`.
+///
+/// A file which exists but can't be loaded is still an error.
+///
+/// ```rust,no_run
+/// use config_file::load_or_else;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let config: Config = load_or_else("/etc/myapp.toml", || Config { port: 8080 }).unwrap();
+/// ```
+pub fn load_or_else<T: DeserializeOwned>(
+    path: impl AsRef<Path>,
+    fallback: impl FnOnce() -> T,
+) -> Result<T, ConfigFileError> {
+    load_or_else_with(path, |failure| match failure {
+        LoadFailure::Missing => Ok(fallback()),
+        LoadFailure::Invalid(err) => Err(err),
+    })
+}
+
+/// Load the configuration file located at @path, or let @fallback decide
+/// what to do when it can't, depending on whether the file is missing or
+/// invalid.
+///
+/// ```rust,no_run
+/// use config_file::{load_or_else_with, LoadFailure};
+/// # #[derive(Default, serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let config: Config = load_or_else_with("/etc/myapp.toml", |failure| match failure {
+///     LoadFailure::Missing => Ok(Config::default()),
+///     LoadFailure::Invalid(err) => {
+///         eprintln!("ignoring invalid configuration: {err}");
+///         Ok(Config::default())
+///     }
+/// })
+/// .unwrap();
+/// ```
+pub fn load_or_else_with<T: DeserializeOwned>(
+    path: impl AsRef<Path>,
+    fallback: impl FnOnce(LoadFailure) -> Result<T, ConfigFileError>,
+) -> Result<T, ConfigFileError> {
+    match T::from_config_file(path) {
+        Ok(config) => Ok(config),
+        Err(err) if is_missing(&err) => fallback(LoadFailure::Missing),
+        Err(err) => fallback(LoadFailure::Invalid(err)),
+    }
+}
+
+/// Whether @err was raised because the file to load doesn't exist
+fn is_missing(err: &ConfigFileError) -> bool {
+    matches!(err, ConfigFileError::FileAccess(err) if err.kind() == std::io::ErrorKind::NotFound)
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::test::TestConfig;
+
+    impl Default for TestConfig {
+        fn default() -> Self {
+            Self::example()
+        }
+    }
+
+    #[test]
+    fn test_load_or_store_default() {
+        let dir = temp_dir().join("config-fallback");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        let config: TestConfig = load_or_store_default(&path).unwrap();
+        assert_eq!(config, TestConfig::example());
+        assert_eq!(TestConfig::from_config_file(&path).unwrap(), config);
+
+        std::fs::write(&path, "port = 1").unwrap();
+        assert!(load_or_store_default::<TestConfig>(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "port = 1");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_or_else() {
+        let path = temp_dir().join("config-fallback-else.toml");
+        let _ = std::fs::remove_file(&path);
+        let fallback = || TestConfig {
+            port: 1,
+            ..TestConfig::example()
+        };
+        assert_eq!(load_or_else(&path, fallback).unwrap().port, 1);
+        assert!(!path.exists());
+        let config = load_or_else_with(&path, |failure| match failure {
+            LoadFailure::Missing => Ok(fallback()),
+            LoadFailure::Invalid(err) => Err(err),
+        });
+        assert_eq!(config.unwrap().port, 1);
+
+        std::fs::write(&path, "port = 2").unwrap();
+        assert!(load_or_else(&path, fallback).is_err());
+        let config = load_or_else_with(&path, |failure| match failure {
+            LoadFailure::Missing => panic!("{} exists", path.display()),
+            LoadFailure::Invalid(_) => Ok(fallback()),
+        });
+        assert_eq!(config.unwrap().port, 1);
+
+        TestConfig::example().to_config_file(&path).unwrap();
+        assert_eq!(
+            load_or_else(&path, fallback).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
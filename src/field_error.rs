@@ -0,0 +1,213 @@
+use std::fmt;
+
+use crate::ConfigFileError;
+
+/// A field of a configuration which couldn't be deserialized, described the
+/// same way whatever the format of the file, so that settings UIs can
+/// highlight it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The dotted path of the field, such as `inner.answer`, when the format
+    /// reports it
+    pub path: Option<String>,
+    /// What the field should hold, such as `u8` or ``one of `a`, `b` ``
+    pub expected: Option<String>,
+    /// What the field holds instead, such as `string "x"`
+    pub found: Option<String>,
+    /// A short description of the problem, meant for users
+    pub hint: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "`{path}`: {}", self.hint),
+            None => f.write_str(&self.hint),
+        }
+    }
+}
+
+impl ConfigFileError {
+    /// Describe the field this error was raised for, when it was raised
+    /// because a field didn't match the configuration type, rather than
+    /// because the file couldn't be read or parsed.
+    ///
+    /// This is best effort: formats which don't report where the error
+    /// happened leave [`FieldError::path`] empty.
+    pub fn field_error(&self) -> Option<FieldError> {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json(err) if err.is_data() => parse(&strip_location(&err.to_string()), None),
+            #[cfg(feature = "toml")]
+            Self::Toml(crate::TomlError::DeserializationError(err)) => {
+                let message = strip_location(&err.to_string());
+                match message.rsplit_once(" for key `") {
+                    Some((message, key)) => parse(message, key.strip_suffix('`')),
+                    None => parse(&message, None),
+                }
+            }
+            #[cfg(feature = "xml")]
+            Self::Xml(quick_xml::DeError::Custom(message)) => parse(message, None),
+            #[cfg(feature = "yaml")]
+            Self::Yaml(err) => {
+                let message = strip_location(&err.to_string());
+                match message.split_once(": ") {
+                    Some((path, rest)) if !is_field_message(&message) => parse(rest, Some(path)),
+                    _ => parse(&message, None),
+                }
+            }
+            Self::Value(err) => parse(&err.to_string(), None),
+            _ => None,
+        }
+    }
+}
+
+/// The messages serde raises for fields not matching the type, followed by
+/// what they are about
+const KINDS: [&str; 7] = [
+    "invalid type: ",
+    "invalid value: ",
+    "invalid length ",
+    "unknown variant ",
+    "unknown field ",
+    "missing field ",
+    "duplicate field ",
+];
+
+fn is_field_message(message: &str) -> bool {
+    KINDS.iter().any(|kind| message.starts_with(kind))
+}
+
+/// Remove the position appended by some formats to @message
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn strip_location(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// Describe the field error reported by @message for the value located at
+/// @parent, which is the root of the document when empty
+fn parse(message: &str, parent: Option<&str>) -> Option<FieldError> {
+    let parent = parent.filter(|parent| !parent.is_empty() && *parent != ".");
+    if !is_field_message(message) {
+        return None;
+    }
+    let child = |field: &str| {
+        let field = field.trim_matches('`');
+        Some(match parent {
+            Some(parent) => format!("{parent}.{field}"),
+            None => field.to_string(),
+        })
+    };
+    let (message, expected) = match message.rsplit_once(", expected ") {
+        Some((message, expected)) => (message, Some(expected.to_string())),
+        None => (message, None),
+    };
+    let mut error = FieldError {
+        path: parent.map(str::to_string),
+        expected,
+        found: None,
+        hint: String::new(),
+    };
+    if let Some(found) = message
+        .strip_prefix("invalid type: ")
+        .or_else(|| message.strip_prefix("invalid value: "))
+    {
+        error.found = Some(found.to_string());
+    } else if let Some(length) = message.strip_prefix("invalid length ") {
+        error.found = Some(length.to_string());
+    } else if let Some(variant) = message.strip_prefix("unknown variant ") {
+        error.found = Some(variant.to_string());
+    } else if let Some(field) = message.strip_prefix("unknown field ") {
+        error.path = child(field);
+        error.hint = "unknown field".into();
+    } else if let Some(field) = message.strip_prefix("missing field ") {
+        error.path = child(field);
+        error.hint = "missing field".into();
+    } else if let Some(field) = message.strip_prefix("duplicate field ") {
+        error.path = child(field);
+        error.hint = "field set more than once".into();
+    }
+    error.hint = match (&error.expected, &error.found) {
+        (Some(expected), Some(found)) => format!("expected {expected}, found {found}"),
+        (Some(expected), None) => format!("{}, expected {expected}", error.hint),
+        _ => error.hint,
+    };
+    Some(error)
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use super::*;
+    use crate::{test::TestConfig, ConfigFormat};
+
+    fn invalid_answer() -> FieldError {
+        FieldError {
+            path: Some("inner.answer".into()),
+            expected: Some("u8".into()),
+            found: Some("string \"x\"".into()),
+            hint: "expected u8, found string \"x\"".into(),
+        }
+    }
+
+    fn missing_answer() -> FieldError {
+        FieldError {
+            path: Some("inner.answer".into()),
+            expected: None,
+            found: None,
+            hint: "missing field".into(),
+        }
+    }
+
+    fn field_error(format: ConfigFormat, data: &str) -> FieldError {
+        format
+            .deserialize::<TestConfig>(data.as_bytes())
+            .unwrap_err()
+            .field_error()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_toml() {
+        let data = "host = \"a\"\nport = 1\ntags = []\n[inner]\n";
+        assert_eq!(field_error(ConfigFormat::Toml, data), missing_answer());
+        let data = format!("{data}answer = \"x\"\n");
+        assert_eq!(field_error(ConfigFormat::Toml, &data), invalid_answer());
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_yaml() {
+        let data = "host: a\nport: 1\ntags: []\ninner: {}\n";
+        assert_eq!(field_error(ConfigFormat::Yaml, data), missing_answer());
+        let data = "host: a\nport: 1\ntags: []\ninner:\n  answer: x\n";
+        assert_eq!(field_error(ConfigFormat::Yaml, data), invalid_answer());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json() {
+        let data = r#"{"host": "a", "port": 1, "tags": [], "inner": {"answer": "x"}}"#;
+        let error = FieldError {
+            path: None,
+            ..invalid_answer()
+        };
+        assert_eq!(field_error(ConfigFormat::Json, data), error);
+        let syntax = ConfigFormat::Json.deserialize::<TestConfig>(b"{");
+        assert_eq!(syntax.unwrap_err().field_error(), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let error = parse("unknown field `x`, expected `host` or `inner`", None).unwrap();
+        assert_eq!(error.path.as_deref(), Some("x"));
+        assert_eq!(error.expected.as_deref(), Some("`host` or `inner`"));
+        assert_eq!(error.hint, "unknown field, expected `host` or `inner`");
+        let error = parse("invalid length 1, expected 2 elements", Some("inner")).unwrap();
+        assert_eq!(error.path.as_deref(), Some("inner"));
+        assert_eq!(error.hint, "expected 2 elements, found 1");
+        assert_eq!(parse("EOF while parsing", None), None);
+    }
+}
@@ -0,0 +1,90 @@
+use figment::{
+    providers::Serialized,
+    value::{Dict, Map},
+    Figment, Metadata, Profile, Provider, Source,
+};
+
+use crate::{
+    layers::{Layer, Layers},
+    ConfigFileError, ConfigValue,
+};
+
+impl Provider for Layer {
+    fn metadata(&self) -> Metadata {
+        let metadata = Metadata::named(self.name().to_string());
+        match self
+            .source()
+            .and_then(|source| source.watch().into_iter().next())
+        {
+            Some(path) => metadata.source(Source::File(path)),
+            None => metadata,
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        Serialized::defaults(self.value()).data()
+    }
+}
+
+impl Provider for Layers {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("config-file layers")
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        Serialized::defaults(self.merge().0).data()
+    }
+}
+
+impl Layer {
+    /// Create a layer holding what @provider provides for its selected
+    /// profile, named after it, so that Figment providers can be layered
+    /// with configuration files
+    pub fn from_provider(provider: impl Provider) -> Result<Self, ConfigFileError> {
+        let name = provider.metadata().name.to_string();
+        let value = Figment::from(provider)
+            .extract::<ConfigValue>()
+            .map_err(|err| ConfigFileError::Figment(Box::new(err)))?;
+        Ok(Self::new(name, value))
+    }
+}
+
+impl Layers {
+    /// Add what @provider provides on top of the existing layers, as
+    /// described in [`Layer::from_provider`]
+    pub fn provider(self, provider: impl Provider) -> Result<Self, ConfigFileError> {
+        Ok(self.layer(Layer::from_provider(provider)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use figment::providers::Serialized;
+
+    use super::*;
+    use crate::test::TestConfig;
+
+    #[test]
+    fn test_provider() {
+        let layer = Layer::serialized("file", &TestConfig::example()).unwrap();
+        let config: TestConfig = Figment::new()
+            .merge(layer)
+            .merge(Serialized::default("port", 8080))
+            .extract()
+            .unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.inner.answer, 42);
+
+        let user = BTreeMap::from([("inner", BTreeMap::from([("answer", 0)]))]);
+        let config: TestConfig = Layers::new()
+            .provider(Serialized::defaults(TestConfig::example()))
+            .unwrap()
+            .layer(Layer::serialized("user", &user).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(config.host, "example.com");
+        assert_eq!(config.inner.answer, 0);
+    }
+}
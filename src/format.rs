@@ -0,0 +1,337 @@
+use std::{ffi::OsStr, fmt, path::Path, str::FromStr};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+#[cfg(feature = "toml")]
+use toml_crate as toml;
+
+#[cfg(feature = "toml")]
+use crate::TomlError;
+use crate::{limits::Budget, ConfigFileError};
+
+/// Root element used for XML documents which don't provide their own
+#[cfg(feature = "xml")]
+const XML_ROOT: &str = "config";
+
+/// The configuration formats we know how to handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConfigFormat {
+    /// JSON, using the `.json` extension
+    #[cfg(feature = "json")]
+    Json,
+    /// TOML, using the `.toml` extension
+    #[cfg(feature = "toml")]
+    Toml,
+    /// XML, using the `.xml` extension
+    #[cfg(feature = "xml")]
+    Xml,
+    /// YAML, using the `.yaml` or `.yml` extension
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+/// Error raised when parsing the name of a format which isn't known or
+/// enabled
+#[derive(Debug, Error)]
+#[error("unknown configuration format {0}")]
+pub struct UnknownFormat(String);
+
+impl ConfigFormat {
+    /// The formats enabled at compile time
+    const ENABLED: &'static [Self] = &[
+        #[cfg(feature = "json")]
+        Self::Json,
+        #[cfg(feature = "toml")]
+        Self::Toml,
+        #[cfg(feature = "xml")]
+        Self::Xml,
+        #[cfg(feature = "yaml")]
+        Self::Yaml,
+    ];
+
+    /// The formats enabled at compile time, to offer them in a `--format`
+    /// flag or its help text
+    pub fn enabled() -> impl Iterator<Item = Self> {
+        Self::ENABLED.iter().copied()
+    }
+
+    /// The extensions of files using this format, the preferred one first
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json => &["json"],
+            #[cfg(feature = "toml")]
+            Self::Toml => &["toml"],
+            #[cfg(feature = "xml")]
+            Self::Xml => &["xml"],
+            #[cfg(feature = "yaml")]
+            Self::Yaml => &["yaml", "yml"],
+        }
+    }
+
+    /// Guess the format of the file located at @path from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension().and_then(OsStr::to_str)?.to_lowercase();
+        Self::from_extension(&extension)
+    }
+
+    /// Guess the format of @data from its content, for files without an
+    /// extension or with a wrong one.
+    ///
+    /// Blank lines and `#` comments are skipped, then the first line tells
+    /// the format apart: `{` or `[` for JSON, `<` for XML, `---`, `- item`
+    /// or `key: value` for YAML, and `[section]` or `key = value` for TOML.
+    pub fn from_content(data: &[u8]) -> Option<Self> {
+        let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+        let line = data
+            .split(|byte| *byte == b'\n')
+            .filter_map(|line| std::str::from_utf8(line).ok())
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+        let format = if line.starts_with("---") || line == "-" || line.starts_with("- ") {
+            "yaml"
+        } else if line.starts_with('<') {
+            "xml"
+        } else if line.starts_with('{') {
+            "json"
+        } else if let Some(header) = line.strip_prefix('[') {
+            // TOML headers hold keys, JSON arrays hold values
+            let header = header.strip_prefix('[').unwrap_or(header);
+            let is_key = |c: char| c.is_alphanumeric() || "_-.\" '".contains(c);
+            match header.split_once(']') {
+                Some((key, _)) if !key.is_empty() && key.chars().all(is_key) => "toml",
+                _ => "json",
+            }
+        } else {
+            match line.find(['=', ':']).map(|index| line.as_bytes()[index]) {
+                Some(b'=') => "toml",
+                Some(_) => "yaml",
+                None => return None,
+            }
+        };
+        Self::from_extension(format)
+    }
+
+    /// The format of the media type @mime, as found in `Content-Type` headers,
+    /// such as `application/x-yaml` or `application/json; charset=utf-8`
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let essence = mime.split(';').next()?.trim().to_lowercase();
+        let (kind, subtype) = essence.split_once('/')?;
+        if kind != "application" && kind != "text" {
+            return None;
+        }
+        // Structured syntax suffixes, as in `application/ld+json`
+        let subtype = subtype.rsplit('+').next()?;
+        Self::from_extension(subtype.strip_prefix("x-").unwrap_or(subtype))
+    }
+
+    /// The media type of this format, to use in `Content-Type` headers
+    pub fn mime(self) -> &'static str {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json => "application/json",
+            #[cfg(feature = "toml")]
+            Self::Toml => "application/toml",
+            #[cfg(feature = "xml")]
+            Self::Xml => "application/xml",
+            #[cfg(feature = "yaml")]
+            Self::Yaml => "application/yaml",
+        }
+    }
+
+    /// The format using @extension, in lowercase
+    fn from_extension(extension: &str) -> Option<Self> {
+        Self::enabled().find(|format| format.extensions().contains(&extension))
+    }
+
+    /// Parse @data as this format
+    #[allow(unused)]
+    pub fn deserialize<C: DeserializeOwned>(self, data: &[u8]) -> Result<C, ConfigFileError> {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json => serde_json::from_slice(&crate::metadata::strip(data)?)
+                .map_err(ConfigFileError::Json),
+            #[cfg(feature = "toml")]
+            Self::Toml => Ok(toml::from_slice(data).map_err(TomlError::DeserializationError)?),
+            #[cfg(feature = "xml")]
+            Self::Xml => Ok(quick_xml::de::from_reader(data)?),
+            #[cfg(feature = "yaml")]
+            Self::Yaml => serde_yaml::from_slice(data).map_err(ConfigFileError::Yaml),
+        }
+    }
+
+    /// Parse @data as this format within @budget
+    #[allow(unused)]
+    pub(crate) fn deserialize_limited<C: DeserializeOwned>(
+        self,
+        data: &[u8],
+        budget: &Budget,
+    ) -> Result<C, ConfigFileError> {
+        let config = match self {
+            #[cfg(feature = "json")]
+            Self::Json => {
+                let data = crate::metadata::strip(data)?;
+                let mut deserializer = serde_json::Deserializer::from_slice(&data);
+                budget
+                    .deserialize_json(&mut deserializer)
+                    .and_then(|config| deserializer.end().map(|()| config))
+                    .map_err(ConfigFileError::Json)
+            }
+            #[cfg(feature = "toml")]
+            Self::Toml => {
+                let data = std::str::from_utf8(data)
+                    .map_err(<toml::de::Error as serde::de::Error>::custom)
+                    .map_err(TomlError::DeserializationError)?;
+                budget
+                    .deserialize(&mut toml::Deserializer::new(data))
+                    .map_err(|err| TomlError::DeserializationError(err).into())
+            }
+            #[cfg(feature = "xml")]
+            Self::Xml => {
+                Ok(budget.deserialize(&mut quick_xml::de::Deserializer::from_reader(data))?)
+            }
+            #[cfg(feature = "yaml")]
+            Self::Yaml => budget
+                .deserialize(serde_yaml::Deserializer::from_slice(data))
+                .map_err(ConfigFileError::Yaml),
+        };
+        match budget.exceeded() {
+            Some(limit) => Err(ConfigFileError::LimitExceeded(limit)),
+            None => config,
+        }
+    }
+
+    /// Parse @data as this format, borrowing from it where the format and
+    /// `C` allow it
+    #[allow(unused)]
+    pub fn deserialize_borrowed<'de, C: Deserialize<'de>>(
+        self,
+        data: &'de [u8],
+    ) -> Result<C, ConfigFileError> {
+        match self {
+            // Once the metadata header is stripped, the document no longer
+            // lives in @data and can't be borrowed from
+            #[cfg(feature = "json")]
+            Self::Json => match crate::metadata::strip(data)? {
+                std::borrow::Cow::Borrowed(data) => serde_json::from_slice(data),
+                std::borrow::Cow::Owned(data) => {
+                    serde_json::from_slice::<serde_json::Value>(&data).and_then(C::deserialize)
+                }
+            }
+            .map_err(ConfigFileError::Json),
+            #[cfg(feature = "toml")]
+            Self::Toml => Ok(toml::from_slice(data).map_err(TomlError::DeserializationError)?),
+            #[cfg(feature = "xml")]
+            Self::Xml => {
+                let data = std::str::from_utf8(data).map_err(quick_xml::DeError::from)?;
+                Ok(quick_xml::de::from_str(data)?)
+            }
+            // serde_yaml never borrows, but can still fill types which may
+            // borrow such as `Cow<'de, str>`
+            #[cfg(feature = "yaml")]
+            Self::Yaml => C::deserialize(serde_yaml::Deserializer::from_slice(data))
+                .map_err(ConfigFileError::Yaml),
+        }
+    }
+
+    /// Render @config in this format
+    #[allow(unused)]
+    pub fn serialize<C: Serialize + ?Sized>(self, config: &C) -> Result<Vec<u8>, ConfigFileError> {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json => serde_json::to_vec_pretty(config).map_err(ConfigFileError::Json),
+            #[cfg(feature = "toml")]
+            Self::Toml => Ok(toml::to_string_pretty(config)
+                .map_err(TomlError::SerializationError)?
+                .into_bytes()),
+            #[cfg(feature = "xml")]
+            Self::Xml => match quick_xml::se::to_string(config) {
+                // Maps, unlike structs, have no name to use as root element
+                Err(quick_xml::DeError::Unsupported(_)) => {
+                    Ok(quick_xml::se::to_string_with_root(XML_ROOT, config)?.into_bytes())
+                }
+                xml => Ok(xml?.into_bytes()),
+            },
+            #[cfg(feature = "yaml")]
+            Self::Yaml => serde_yaml::to_vec(config).map_err(ConfigFileError::Yaml),
+        }
+    }
+}
+
+/// Formats are displayed as their preferred extension, such as `yaml`
+impl fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extensions()[0])
+    }
+}
+
+/// Formats are parsed from any of their extensions, ignoring case
+impl FromStr for ConfigFormat {
+    type Err = UnknownFormat;
+
+    fn from_str(name: &str) -> Result<Self, UnknownFormat> {
+        Self::from_extension(&name.to_lowercase()).ok_or_else(|| UnknownFormat(name.to_string()))
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mime() {
+        assert_eq!(
+            ConfigFormat::from_mime("application/toml"),
+            Some(ConfigFormat::Toml)
+        );
+        assert_eq!(
+            ConfigFormat::from_mime(" Text/X-TOML; charset=utf-8"),
+            Some(ConfigFormat::Toml)
+        );
+        assert_eq!(ConfigFormat::from_mime("image/toml"), None);
+        assert_eq!(ConfigFormat::from_mime("text/plain"), None);
+        assert_eq!(ConfigFormat::from_mime("toml"), None);
+        #[cfg(feature = "json")]
+        assert_eq!(
+            ConfigFormat::from_mime("application/ld+json"),
+            Some(ConfigFormat::Json)
+        );
+        #[cfg(feature = "yaml")]
+        assert_eq!(
+            ConfigFormat::from_mime("application/x-yaml"),
+            Some(ConfigFormat::Yaml)
+        );
+        let formats = [
+            #[cfg(feature = "json")]
+            ConfigFormat::Json,
+            ConfigFormat::Toml,
+            #[cfg(feature = "xml")]
+            ConfigFormat::Xml,
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml,
+        ];
+        for format in formats {
+            assert_eq!(ConfigFormat::from_mime(format.mime()), Some(format));
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        assert!(ConfigFormat::enabled().any(|format| format == ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::Toml.to_string(), "toml");
+        assert_eq!("TOML".parse::<ConfigFormat>().unwrap(), ConfigFormat::Toml);
+        let err = "ini".parse::<ConfigFormat>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown configuration format ini");
+        #[cfg(feature = "yaml")]
+        {
+            assert_eq!(ConfigFormat::Yaml.extensions(), ["yaml", "yml"]);
+            assert_eq!("yml".parse::<ConfigFormat>().unwrap(), ConfigFormat::Yaml);
+            assert_eq!(ConfigFormat::Yaml.to_string(), "yaml");
+        }
+        for format in ConfigFormat::enabled() {
+            assert_eq!(format.to_string().parse::<ConfigFormat>().unwrap(), format);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{format::ConfigFormat, read_file, store_file, ConfigFileError, StoreOptions};
+
+/// A configuration along with the file it was loaded from, its format and
+/// its modification time, so that it can be saved or reloaded without
+/// carrying the path around.
+///
+/// Changes made through [`get_mut`](Self::get_mut) or
+/// [`update`](Self::update) are tracked until the configuration is saved,
+/// for settings screens which edit it before persisting it.
+///
+/// ```rust,no_run
+/// use config_file::Config;
+/// # #[derive(serde::Deserialize, serde::Serialize)]
+/// # struct Settings { port: u16 }
+///
+/// let mut config = Config::<Settings>::load("/etc/myapp.toml").unwrap();
+/// println!("listening on {} as set in {}", config.port, config.path().display());
+/// config.update(|settings| settings.port = 8080);
+/// assert!(config.is_dirty());
+/// config.save().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config<T> {
+    config: T,
+    path: PathBuf,
+    format: ConfigFormat,
+    modified: Option<SystemTime>,
+    dirty: bool,
+}
+
+impl<T: DeserializeOwned> Config<T> {
+    /// Load the configuration file located at @path, in the format of its
+    /// extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        Self::load_with_format(path, format)
+    }
+
+    /// Load the configuration file located at @path as @format, whatever
+    /// its extension
+    pub fn load_with_format(
+        path: impl AsRef<Path>,
+        format: ConfigFormat,
+    ) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+        let modified = modified(path);
+        Ok(Self {
+            config: format.deserialize(&read_file(path)?)?,
+            path: path.to_path_buf(),
+            format,
+            modified,
+            dirty: false,
+        })
+    }
+
+    /// Load the file again, replacing the configuration along with its
+    /// unsaved changes
+    pub fn reload(&mut self) -> Result<(), ConfigFileError> {
+        *self = Self::load_with_format(&self.path, self.format)?;
+        Ok(())
+    }
+}
+
+impl<T: Serialize> Config<T> {
+    /// Store the configuration back into its file
+    pub fn save(&mut self) -> Result<(), ConfigFileError> {
+        self.save_with_options(&StoreOptions::default())
+    }
+
+    /// Store the configuration back into its file according to @options
+    pub fn save_with_options(&mut self, options: &StoreOptions) -> Result<(), ConfigFileError> {
+        let data = options.serialize(self.format, &self.config)?;
+        store_file(&self.path, self.format, data, options)?;
+        self.modified = modified(&self.path);
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl<T> Config<T> {
+    /// The path of the configuration file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The format of the configuration file
+    pub fn format(&self) -> ConfigFormat {
+        self.format
+    }
+
+    /// The modification time of the configuration file when it was last
+    /// loaded or saved, if the platform provides it
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Get the configuration mutably, marking it as changed
+    pub fn get_mut(&mut self) -> &mut T {
+        self.dirty = true;
+        &mut self.config
+    }
+
+    /// Change the configuration with @update, marking it as changed
+    pub fn update<R>(&mut self, update: impl FnOnce(&mut T) -> R) -> R {
+        update(self.get_mut())
+    }
+
+    /// Whether the configuration was changed since it was last loaded or
+    /// saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Take the configuration, forgetting about its file
+    pub fn into_inner(self) -> T {
+        self.config
+    }
+}
+
+impl<T> Deref for Config<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.config
+    }
+}
+
+/// The modification time of the file located at @path, if available
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, ToConfigFile};
+
+    #[test]
+    fn test_config() {
+        let path = temp_dir().join("config-handle.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let mut config = Config::<TestConfig>::load(&path).unwrap();
+        assert_eq!(*config, TestConfig::example());
+        assert_eq!(config.path(), path);
+        assert_eq!(config.format(), ConfigFormat::Toml);
+        assert!(config.modified().is_some());
+
+        std::fs::write(&path, "port = 1").unwrap();
+        assert!(config.reload().is_err());
+        assert_eq!(config.port, TestConfig::example().port);
+        config.save().unwrap();
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+
+        let bare = temp_dir().join("config-handle");
+        std::fs::rename(&path, &bare).unwrap();
+        assert!(matches!(
+            Config::<TestConfig>::load(&bare),
+            Err(ConfigFileError::UnsupportedFormat)
+        ));
+        let mut config = Config::<TestConfig>::load_with_format(&bare, ConfigFormat::Toml).unwrap();
+        assert_eq!(*config, TestConfig::example());
+        assert!(!config.is_dirty());
+        let port = config.update(|config| {
+            config.port += 1;
+            config.port
+        });
+        config.get_mut().host = "localhost".into();
+        assert!(config.is_dirty());
+        config.save().unwrap();
+        assert!(!config.is_dirty());
+        config.get_mut().port = 0;
+        config.reload().unwrap();
+        assert!(!config.is_dirty());
+        assert_eq!(config.port, port);
+
+        let options = StoreOptions::new().overwrite(crate::OverwritePolicy::ErrorIfExists);
+        assert!(config.save_with_options(&options).is_err());
+        config.get_mut().port = 0;
+        let options = StoreOptions::new().overwrite(crate::OverwritePolicy::MergeInto);
+        config.save_with_options(&options).unwrap();
+        assert!(!config.is_dirty());
+        config.reload().unwrap();
+        assert_eq!(config.port, port);
+        assert_eq!(config.into_inner().host, "localhost");
+        std::fs::remove_file(bare).unwrap();
+    }
+}
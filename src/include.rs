@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    format::ConfigFormat,
+    limits::Budget,
+    value::{self, from_value, ConfigValue},
+    ConfigFileError, LoadOptions,
+};
+
+/// Maximum number of nested includes
+const MAX_DEPTH: usize = 16;
+
+/// Key listing the files to include
+const INCLUDE: &str = "include";
+
+/// Replace the `include` key of @value, loaded from the configuration file
+/// located at @path, with the content of the files it lists.
+///
+/// Included files are merged in order, then @value is merged over them, so
+/// the including file has the last word. Relative paths are relative to the
+/// directory holding the including file. They are all read within @budget.
+pub(crate) fn splice(
+    value: &mut ConfigValue,
+    path: &Path,
+    options: &LoadOptions,
+    budget: Option<&Budget>,
+) -> Result<(), ConfigFileError> {
+    splice_nested(value, path, options, budget, &mut vec![identity(path)])
+}
+
+/// Splice the includes of @value, loaded from @path, which was itself
+/// included by the files of @stack
+fn splice_nested(
+    value: &mut ConfigValue,
+    path: &Path,
+    options: &LoadOptions,
+    budget: Option<&Budget>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), ConfigFileError> {
+    let Some(includes) = value
+        .as_map_mut()
+        .and_then(|map| value::remove(map, INCLUDE))
+    else {
+        return Ok(());
+    };
+    if stack.len() > MAX_DEPTH {
+        return Err(ConfigFileError::IncludeDepth(path.to_path_buf()));
+    }
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let mut merged = ConfigValue::Map(Default::default());
+    for include in from_value::<Vec<PathBuf>>(includes)? {
+        let include = parent.join(include);
+        let id = identity(&include);
+        if stack.contains(&id) {
+            return Err(ConfigFileError::IncludeCycle(include));
+        }
+        let format = ConfigFormat::from_path(&include).ok_or(ConfigFileError::UnsupportedFormat)?;
+        let data = options.read(&include, budget)?;
+        let mut included = options.deserialize(format, &data, budget)?;
+        stack.push(id);
+        splice_nested(&mut included, &include, options, budget, stack)?;
+        stack.pop();
+        merged.merge(included);
+    }
+    merged.merge(std::mem::take(value));
+    *value = merged;
+    Ok(())
+}
+
+/// What tells @path apart from other paths to the same file, as far as we
+/// can tell
+fn identity(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use crate::{test::TestConfig, ConfigFileError, FromConfigFile, LoadOptions};
+
+    #[test]
+    fn test_include() {
+        let dir = temp_dir().join("config-include");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("conf")).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            "include = [\"conf/base.toml\", \"conf/inner.toml\"]\nport = 8080\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("conf/base.toml"),
+            "include = \"tags.toml\"\nhost = \"example.com\"\nport = 443\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("conf/tags.toml"), "tags = [\"example\", \"test\"]").unwrap();
+        std::fs::write(dir.join("conf/inner.toml"), "[inner]\nanswer = 42").unwrap();
+
+        assert!(TestConfig::from_config_file(&path).is_err());
+        let options = LoadOptions::new().includes(true);
+        let config = TestConfig::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.tags, ["example", "test"]);
+        assert_eq!(config.inner.answer, 42);
+
+        std::fs::write(dir.join("conf/inner.toml"), "include = \"../config.toml\"").unwrap();
+        assert!(matches!(
+            TestConfig::from_config_file_with_options(&path, &options),
+            Err(ConfigFileError::IncludeCycle(_))
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
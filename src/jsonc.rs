@@ -0,0 +1,101 @@
+//! Support for JSON with comments and trailing commas, as accepted by most
+//! editors.
+
+/// Blank out the comments and trailing commas of @data.
+///
+/// They are replaced by spaces rather than removed, and the line breaks of
+/// block comments are kept, so that parse errors still point to the right
+/// place.
+pub(crate) fn strip(data: &[u8]) -> Vec<u8> {
+    let mut data = data.to_vec();
+    strip_comments(&mut data);
+    strip_trailing_commas(&mut data);
+    data
+}
+
+fn strip_comments(data: &mut [u8]) {
+    let mut i = 0;
+    while i < data.len() {
+        match (data[i], data.get(i + 1)) {
+            (b'"', _) => i = skip_string(data, i),
+            (b'/', Some(b'/')) => {
+                while i < data.len() && data[i] != b'\n' {
+                    data[i] = b' ';
+                    i += 1;
+                }
+            }
+            (b'/', Some(b'*')) => {
+                let end = data[i + 2..]
+                    .windows(2)
+                    .position(|end| end == b"*/")
+                    .map_or(data.len(), |end| i + 2 + end + 2);
+                for byte in &mut data[i..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+fn strip_trailing_commas(data: &mut [u8]) {
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'"' => i = skip_string(data, i),
+            b',' => {
+                let next = data[i + 1..]
+                    .iter()
+                    .find(|byte| !byte.is_ascii_whitespace());
+                if matches!(next, Some(b']' | b'}')) {
+                    data[i] = b' ';
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+/// Index right after the end of the string starting at @start
+fn skip_string(data: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < data.len() {
+        match data[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    data.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        let data = br#"{
+            // comment
+            "url": "http://example.com", /* inline */
+            "escaped": "\" // not a comment",
+            "list": [1, 2,],
+            /* multi
+               line */
+        }"#;
+        let value: serde_json::Value = serde_json::from_slice(&strip(data)).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "url": "http://example.com",
+                "escaped": "\" // not a comment",
+                "list": [1, 2],
+            })
+        );
+        assert_eq!(strip(data).len(), data.len());
+    }
+}
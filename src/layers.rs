@@ -0,0 +1,746 @@
+//! Configurations made of several layers merged on top of each other, such
+//! as system wide defaults, then user settings, then project settings.
+//!
+//! Layers are merged in the order they are added: by default, maps are
+//! merged recursively, while any other value replaces the one from the
+//! previous layers, which [`MergeStrategy`] allows changing. A layer can
+//! lock some of its keys, as system policies do, so that the following
+//! layers can't override them.
+//!
+//! ```rust,no_run
+//! use config_file::layers::{Layer, Layers};
+//! # #[derive(serde::Deserialize)]
+//! # struct Config {}
+//!
+//! let (config, warnings) = Layers::new()
+//!     .layer(Layer::file("/etc/myapp/config.toml").unwrap().lock("telemetry.enabled"))
+//!     .layer(Layer::file("/home/user/.config/myapp/config.toml").unwrap())
+//!     .build_with_warnings::<Config>()
+//!     .unwrap();
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    dir::config_files,
+    policy::Policy,
+    source::{ConfigSource, FileSource},
+    value::{from_value, to_value},
+    ConfigFileError, ConfigValue, Warning,
+};
+
+/// A layer of configuration
+#[derive(Debug, Clone)]
+pub struct Layer {
+    name: String,
+    value: ConfigValue,
+    source: Option<Arc<dyn ConfigSource>>,
+    locks: Vec<String>,
+    lock_all: bool,
+}
+
+impl Layer {
+    /// Create a layer named @name holding @value
+    pub fn new(name: impl Into<String>, value: ConfigValue) -> Self {
+        Self {
+            name: name.into(),
+            value,
+            source: None,
+            locks: Vec::new(),
+            lock_all: false,
+        }
+    }
+
+    /// Create a layer named @name holding the serialization of @value
+    pub fn serialized<T: Serialize + ?Sized>(
+        name: impl Into<String>,
+        value: &T,
+    ) -> Result<Self, ConfigFileError> {
+        Ok(Self::new(name, to_value(value)?))
+    }
+
+    /// Create a layer holding the content of the configuration file located
+    /// at @path, named after it
+    pub fn file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        Self::from_source(Box::new(FileSource::new(path.as_ref())))
+    }
+
+    /// Create a layer holding the content of @source, named after it
+    pub fn from_source(source: Box<dyn ConfigSource>) -> Result<Self, ConfigFileError> {
+        let mut layer = Self::new(source.describe(), source.read()?.parse()?);
+        layer.source = Some(source.into());
+        Ok(layer)
+    }
+
+    /// Prevent the following layers from overriding the value of the dotted
+    /// @key, such as `server.port`
+    pub fn lock(mut self, key: impl Into<String>) -> Self {
+        self.locks.push(key.into());
+        self
+    }
+
+    /// Prevent the following layers from overriding any of our values
+    pub fn lock_all(mut self) -> Self {
+        self.lock_all = true;
+        self
+    }
+
+    /// Our name, as used in warnings
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Our value
+    pub fn value(&self) -> &ConfigValue {
+        &self.value
+    }
+
+    /// The source we were read from, if any
+    pub fn source(&self) -> Option<&dyn ConfigSource> {
+        self.source.as_deref()
+    }
+
+    /// Read our value from our source again, if any
+    pub fn reload(&mut self) -> Result<(), ConfigFileError> {
+        if let Some(source) = &self.source {
+            self.value = source.read()?.parse()?;
+        }
+        Ok(())
+    }
+}
+
+/// How a value from a layer is merged with the one from the previous layers
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Replace the previous value, even a map
+    Replace,
+    /// Merge maps key by key, and replace any other value
+    #[default]
+    Deep,
+    /// Append arrays to the previous ones, and merge maps as [`Deep`] does
+    ///
+    /// [`Deep`]: MergeStrategy::Deep
+    Append,
+    /// Merge the maps held by arrays with the previous ones holding the same
+    /// value for the given key, appending the others, and merge maps as
+    /// [`Deep`] does
+    ///
+    /// [`Deep`]: MergeStrategy::Deep
+    MergeByKey(String),
+    /// Keep the value from the previous layers, only using the value of a
+    /// layer when none of the previous ones holds it
+    Keep,
+}
+
+/// A stack of [`Layer`]s
+#[derive(Debug, Clone, Default)]
+pub struct Layers {
+    layers: Vec<Layer>,
+    policies: Vec<Layer>,
+    strategy: MergeStrategy,
+    strategies: Vec<(String, MergeStrategy)>,
+}
+
+impl Layers {
+    /// Create an empty stack
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add @layer on top of the existing ones
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Add the content of the configuration file located at @path on top of
+    /// the existing layers
+    pub fn file(self, path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        Ok(self.layer(Layer::file(path)?))
+    }
+
+    /// Add the content of @source on top of the existing layers
+    pub fn source(self, source: Box<dyn ConfigSource>) -> Result<Self, ConfigFileError> {
+        Ok(self.layer(Layer::from_source(source)?))
+    }
+
+    /// Load the configuration of the application named @app following the
+    /// usual precedence of Unix systems, from the lowest to the highest:
+    ///
+    /// - the system wide `config.*` files located in `/etc/<app>`, or
+    ///   `%ProgramData%\<app>` on Windows
+    /// - the user `config.*` files located in `$XDG_CONFIG_HOME/<app>`,
+    ///   defaulting to `~/.config/<app>`, or `%APPDATA%\<app>` on Windows
+    /// - the file located at the path held by the environment variable @var,
+    ///   if set, which must exist
+    ///
+    /// Every `config.*` file in a supported format is loaded, in lexical
+    /// order. [`Layers::watch`] lists the files which were actually found.
+    ///
+    /// ```rust,no_run
+    /// use config_file::layers::Layers;
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    ///
+    /// let layers = Layers::cascade("myapp", "MYAPP_CONFIG").unwrap();
+    /// for file in layers.watch() {
+    ///     eprintln!("loaded {}", file.display());
+    /// }
+    /// let config: Config = layers.build().unwrap();
+    /// ```
+    pub fn cascade(app: &str, var: &str) -> Result<Self, ConfigFileError> {
+        let (system, user) = match cfg!(windows) {
+            true => (
+                std::env::var_os("ProgramData")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData")),
+                std::env::var_os("APPDATA").map(PathBuf::from),
+            ),
+            false => (PathBuf::from("/etc"), crate::paths::xdg_config_home()),
+        };
+        let dirs = std::iter::once(system).chain(user).map(|dir| dir.join(app));
+        Self::new().cascade_from(dirs, std::env::var_os(var).map(PathBuf::from))
+    }
+
+    /// Add the `config.*` files located in @dirs, then the file located at
+    /// @path, on top of the existing layers
+    fn cascade_from(
+        mut self,
+        dirs: impl IntoIterator<Item = PathBuf>,
+        path: Option<PathBuf>,
+    ) -> Result<Self, ConfigFileError> {
+        for dir in dirs {
+            let files = config_files(&dir, |file| {
+                file.file_stem().is_some_and(|stem| stem == "config")
+            })?;
+            for file in files {
+                self = self.file(file)?;
+            }
+        }
+        match path {
+            Some(path) => self.file(path),
+            None => Ok(self),
+        }
+    }
+
+    /// Enforce @policy: its values win over those of every other layer,
+    /// whatever the order they are added in
+    pub fn policy(mut self, policy: &Policy) -> Self {
+        self.policies.push(policy.layer());
+        self
+    }
+
+    /// Merge the values of the layers according to @strategy, unless
+    /// another one is set for their key with [`Layers::merge_strategy_at`]
+    pub fn merge_strategy(mut self, strategy: MergeStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Merge the values of the layers located at the dotted @key, such as
+    /// `servers`, according to @strategy.
+    ///
+    /// The keys of values held by arrays don't include their index, so
+    /// `servers.ports` designates the `ports` of every map held by the
+    /// `servers` array.
+    ///
+    /// ```rust
+    /// use config_file::layers::{Layer, Layers, MergeStrategy};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Server { name: String, port: u16 }
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Config { servers: Vec<Server> }
+    /// # let (system, user) = (Config { servers: vec![] }, Config { servers: vec![] });
+    ///
+    /// // Servers named the same in both layers are merged together
+    /// let config: Config = Layers::new()
+    ///     .merge_strategy_at("servers", MergeStrategy::MergeByKey("name".into()))
+    ///     .layer(Layer::serialized("system", &system).unwrap())
+    ///     .layer(Layer::serialized("user", &user).unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn merge_strategy_at(mut self, key: impl Into<String>, strategy: MergeStrategy) -> Self {
+        self.strategies.push((key.into(), strategy));
+        self
+    }
+
+    /// The layers, from the bottom one to the top one
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Read the layers from their sources again, such as when one of the
+    /// files returned by [`Layers::watch`] changed
+    pub fn reload(&mut self) -> Result<(), ConfigFileError> {
+        self.layers.iter_mut().try_for_each(Layer::reload)
+    }
+
+    /// The files to watch to know when the layers change
+    pub fn watch(&self) -> Vec<PathBuf> {
+        self.layers
+            .iter()
+            .filter_map(|layer| layer.source.as_ref())
+            .flat_map(|source| source.watch())
+            .collect()
+    }
+
+    /// Merge the layers into a single value, along with warnings about the
+    /// attempts at overriding locked keys or keys enforced by policies
+    pub fn merge(&self) -> (ConfigValue, Vec<Warning>) {
+        let mut merged = ConfigValue::Map(Default::default());
+        let mut locked = Vec::<(String, ConfigValue, &str)>::new();
+        let mut warnings = Vec::new();
+        for layer in &self.layers {
+            self.merge_value(&mut merged, layer.value.clone(), "");
+            for (key, value, locked_by) in &locked {
+                if merged.get_path(key) != Some(value) {
+                    warnings.push(Warning::LockedKey {
+                        key: key.clone(),
+                        layer: layer.name.clone(),
+                        locked_by: locked_by.to_string(),
+                    });
+                    merged.set_path(key, value.clone());
+                }
+            }
+            let keys = match layer.lock_all {
+                true => layer.value.leaf_paths(),
+                false => layer.locks.clone(),
+            };
+            for key in keys {
+                if let Some(value) = merged.get_path(&key) {
+                    let value = value.clone();
+                    locked.push((key, value, &layer.name));
+                }
+            }
+        }
+        for policy in &self.policies {
+            for key in policy.value.leaf_paths() {
+                let value = policy.value.get_path(&key);
+                for layer in &self.layers {
+                    if layer
+                        .value
+                        .get_path(&key)
+                        .is_some_and(|set| Some(set) != value)
+                    {
+                        warnings.push(Warning::LockedKey {
+                            key: key.clone(),
+                            layer: layer.name.clone(),
+                            locked_by: policy.name.clone(),
+                        });
+                    }
+                }
+            }
+            merged.merge(policy.value.clone());
+        }
+        (merged, warnings)
+    }
+
+    /// The layer the merged value of the dotted @key, such as `server.port`,
+    /// comes from, if any.
+    ///
+    /// This is the last layer holding the merged value, or the last one
+    /// holding the key when none does since its value was merged with those
+    /// of other layers, such as appended arrays.
+    ///
+    /// ```rust,no_run
+    /// use config_file::layers::Layers;
+    ///
+    /// let layers = Layers::new()
+    ///     .file("/etc/myapp/config.toml")
+    ///     .unwrap()
+    ///     .file("/home/user/.config/myapp/config.toml")
+    ///     .unwrap();
+    /// if let Some(layer) = layers.provenance("server.port") {
+    ///     println!("server.port comes from {}", layer.name());
+    /// }
+    /// ```
+    pub fn provenance(&self, key: &str) -> Option<&Layer> {
+        self.origin(&self.merge().0, key)
+    }
+
+    /// The layer each value of the merged configuration comes from, by
+    /// dotted key, as described in [`Layers::provenance`]
+    pub fn provenances(&self) -> BTreeMap<String, &Layer> {
+        let (merged, _) = self.merge();
+        merged
+            .leaf_paths()
+            .into_iter()
+            .filter_map(|key| Some((key.clone(), self.origin(&merged, &key)?)))
+            .collect()
+    }
+
+    /// The layer the value of the dotted @key in @merged comes from
+    fn origin(&self, merged: &ConfigValue, key: &str) -> Option<&Layer> {
+        let value = merged.get_path(key)?;
+        let layers = || self.layers.iter().chain(&self.policies).rev();
+        layers()
+            .find(|layer| layer.value.get_path(key) == Some(value))
+            .or_else(|| layers().find(|layer| layer.value.get_path(key).is_some()))
+    }
+
+    /// The strategy to merge the values located at the dotted @key with
+    fn strategy(&self, key: &str) -> &MergeStrategy {
+        // The layers themselves are always merged key by key
+        if key.is_empty() {
+            return &MergeStrategy::Deep;
+        }
+        self.strategies
+            .iter()
+            .rev()
+            .find(|(path, _)| path == key)
+            .map_or(&self.strategy, |(_, strategy)| strategy)
+    }
+
+    /// Merge @value, located at the dotted @key, into @merged
+    fn merge_value(&self, merged: &mut ConfigValue, value: ConfigValue, key: &str) {
+        let strategy = self.strategy(key);
+        match (merged, value) {
+            (merged, value) if *strategy == MergeStrategy::Replace => *merged = value,
+            _ if *strategy == MergeStrategy::Keep => {}
+            (ConfigValue::Map(merged), ConfigValue::Map(map)) => {
+                for (name, value) in map {
+                    let key = match key {
+                        "" => name.clone(),
+                        key => format!("{key}.{name}"),
+                    };
+                    match merged.get_mut(&name) {
+                        Some(existing) => self.merge_value(existing, value, &key),
+                        None => {
+                            merged.insert(name, value);
+                        }
+                    }
+                }
+            }
+            (ConfigValue::Array(merged), ConfigValue::Array(values)) => match strategy {
+                MergeStrategy::Append => merged.extend(values),
+                MergeStrategy::MergeByKey(id) => {
+                    let id_of = |value: &ConfigValue| value.as_map()?.get(id).cloned();
+                    for value in values {
+                        let index = id_of(&value).and_then(|wanted| {
+                            merged
+                                .iter()
+                                .position(|merged| id_of(merged).as_ref() == Some(&wanted))
+                        });
+                        match index {
+                            Some(index) => self.merge_value(&mut merged[index], value, key),
+                            None => merged.push(value),
+                        }
+                    }
+                }
+                _ => *merged = values,
+            },
+            (merged, value) => *merged = value,
+        }
+    }
+
+    /// Merge the layers once and for all, so that no layer can be added
+    /// anymore
+    ///
+    /// ```rust,no_run
+    /// use config_file::layers::Layers;
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    ///
+    /// let frozen = Layers::new().file("/etc/myapp/config.toml").unwrap().freeze();
+    /// let config: Config = frozen.build().unwrap();
+    /// ```
+    pub fn freeze(self) -> FrozenLayers {
+        let (merged, warnings) = self.merge();
+        FrozenLayers {
+            layers: self,
+            merged,
+            warnings,
+        }
+    }
+
+    /// Merge the layers into a `T`
+    pub fn build<T: DeserializeOwned>(&self) -> Result<T, ConfigFileError> {
+        Ok(self.build_with_warnings()?.0)
+    }
+
+    /// Merge the layers into a `T`, along with warnings about the attempts at
+    /// overriding locked keys
+    pub fn build_with_warnings<T: DeserializeOwned>(
+        &self,
+    ) -> Result<(T, Vec<Warning>), ConfigFileError> {
+        let (merged, warnings) = self.merge();
+        Ok((from_value(merged)?, warnings))
+    }
+}
+
+/// [`Layers`] merged once and for all by [`Layers::freeze`], which can't be
+/// changed anymore
+#[derive(Debug, Clone)]
+pub struct FrozenLayers {
+    layers: Layers,
+    merged: ConfigValue,
+    warnings: Vec<Warning>,
+}
+
+impl FrozenLayers {
+    /// The layers, from the bottom one to the top one
+    pub fn layers(&self) -> &[Layer] {
+        self.layers.layers()
+    }
+
+    /// The merged value
+    pub fn value(&self) -> &ConfigValue {
+        &self.merged
+    }
+
+    /// The warnings raised when merging the layers
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The layer the merged value of the dotted @key comes from, as described
+    /// in [`Layers::provenance`]
+    pub fn provenance(&self, key: &str) -> Option<&Layer> {
+        self.layers.origin(&self.merged, key)
+    }
+
+    /// The SHA-256 checksum of the merged value, in hexadecimal, which only
+    /// depends on its content and not on the files or formats it comes from,
+    /// so that the effective configuration of two runs can be compared
+    #[cfg(feature = "checksum")]
+    pub fn checksum(&self) -> String {
+        crate::checksum::value_digest(&self.merged)
+    }
+
+    /// Convert the merged value into a `T`
+    pub fn build<T: DeserializeOwned>(&self) -> Result<T, ConfigFileError> {
+        Ok(from_value(self.merged.clone())?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::TestConfig;
+
+    #[test]
+    fn test_merge() {
+        let user = BTreeMap::from([("port", 8080)]);
+        let project = BTreeMap::from([("inner", BTreeMap::from([("answer", 0)]))]);
+        let config = Layers::new()
+            .layer(Layer::serialized("system", &TestConfig::example()).unwrap())
+            .layer(Layer::serialized("user", &user).unwrap())
+            .layer(Layer::serialized("project", &project).unwrap())
+            .build::<TestConfig>()
+            .unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.inner.answer, 0);
+        assert_eq!(config.host, "example.com");
+    }
+
+    #[test]
+    fn test_locked_keys() {
+        let user = BTreeMap::from([("port", 8080)]);
+        let project = BTreeMap::from([("inner", BTreeMap::from([("answer", 0)]))]);
+        let layers = Layers::new()
+            .layer(
+                Layer::serialized("system", &TestConfig::example())
+                    .unwrap()
+                    .lock("port"),
+            )
+            .layer(Layer::serialized("user", &user).unwrap().lock_all())
+            .layer(Layer::serialized("project", &project).unwrap());
+        let (config, warnings) = layers.build_with_warnings::<TestConfig>().unwrap();
+        assert_eq!(config.port, 443);
+        assert_eq!(config.inner.answer, 0);
+        assert_eq!(
+            warnings,
+            [Warning::LockedKey {
+                key: "port".into(),
+                layer: "user".into(),
+                locked_by: "system".into(),
+            }]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_sources() {
+        use crate::{source::MemorySource, ToConfigFile};
+
+        let path = std::env::temp_dir().join("config-layers-sources.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let user = to_value(&BTreeMap::from([("port", 8080)])).unwrap();
+        let mut layers = Layers::new()
+            .file(&path)
+            .unwrap()
+            .source(Box::new(MemorySource::new("user", user)))
+            .unwrap();
+        assert_eq!(layers.layers()[1].name(), "user");
+        assert_eq!(layers.watch(), std::slice::from_ref(&path));
+        assert_eq!(layers.build::<TestConfig>().unwrap().port, 8080);
+
+        let mut changed = TestConfig::example();
+        changed.host = "example.org".into();
+        changed.to_config_file(&path).unwrap();
+        layers.reload().unwrap();
+        assert_eq!(layers.build::<TestConfig>().unwrap().host, "example.org");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_merge_strategies() {
+        let tags = |tag: &'static str| BTreeMap::from([("tags", [tag])]);
+        let inner = BTreeMap::from([("inner", BTreeMap::from([("answer", 0)]))]);
+        let layers = Layers::new()
+            .layer(Layer::serialized("system", &TestConfig::example()).unwrap())
+            .layer(Layer::serialized("user", &tags("user")).unwrap())
+            .layer(Layer::serialized("project", &inner).unwrap());
+        let config = layers.clone().build::<TestConfig>().unwrap();
+        assert_eq!(config.tags, ["user"]);
+
+        let config = layers
+            .clone()
+            .merge_strategy(MergeStrategy::Append)
+            .build::<TestConfig>()
+            .unwrap();
+        assert_eq!(config.tags, ["example", "test", "user"]);
+        assert_eq!(config.inner.answer, 0);
+
+        let config = layers
+            .merge_strategy(MergeStrategy::Append)
+            .merge_strategy_at("tags", MergeStrategy::Replace)
+            .layer(Layer::serialized("local", &tags("local")).unwrap())
+            .build::<TestConfig>()
+            .unwrap();
+        assert_eq!(config.tags, ["local"]);
+
+        let config = Layers::new()
+            .merge_strategy(MergeStrategy::Keep)
+            .layer(Layer::serialized("user", &tags("user")).unwrap())
+            .layer(Layer::serialized("system", &TestConfig::example()).unwrap())
+            .build::<TestConfig>()
+            .unwrap();
+        assert_eq!(config.tags, ["user"]);
+        assert_eq!(config.port, 443);
+    }
+
+    #[test]
+    fn test_merge_by_key() {
+        let server = |name: &str, port: i128| {
+            ConfigValue::from(BTreeMap::from([
+                ("name".to_string(), ConfigValue::String(name.to_string())),
+                ("port".to_string(), ConfigValue::Integer(port)),
+            ]))
+        };
+        let servers = |servers: Vec<ConfigValue>| {
+            ConfigValue::from(BTreeMap::from([(
+                "servers".to_string(),
+                ConfigValue::Array(servers),
+            )]))
+        };
+        let (merged, _) = Layers::new()
+            .merge_strategy_at("servers", MergeStrategy::MergeByKey("name".into()))
+            .layer(Layer::new(
+                "system",
+                servers(vec![server("a", 1), server("b", 2)]),
+            ))
+            .layer(Layer::new(
+                "user",
+                servers(vec![server("b", 3), server("c", 4)]),
+            ))
+            .merge();
+        let expected = servers(vec![server("a", 1), server("b", 3), server("c", 4)]);
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn test_provenance() {
+        let user = BTreeMap::from([("port", "8080"), ("host", "localhost")]);
+        let layers = Layers::new()
+            .layer(
+                Layer::serialized("system", &TestConfig::example())
+                    .unwrap()
+                    .lock("host"),
+            )
+            .layer(Layer::serialized("user", &user).unwrap());
+        assert_eq!(layers.provenance("port").unwrap().name(), "user");
+        assert_eq!(layers.provenance("inner.answer").unwrap().name(), "system");
+        assert!(layers.provenance("missing").is_none());
+        let provenances = layers.provenances();
+        assert_eq!(provenances["host"].name(), "system");
+        assert_eq!(provenances["tags"].name(), "system");
+        assert_eq!(provenances.len(), 4);
+    }
+
+    #[test]
+    fn test_freeze() {
+        let user = BTreeMap::from([("port", 8080)]);
+        let frozen = Layers::new()
+            .layer(Layer::serialized("system", &TestConfig::example()).unwrap())
+            .layer(Layer::serialized("user", &user).unwrap())
+            .freeze();
+        assert_eq!(frozen.layers().len(), 2);
+        assert!(frozen.warnings().is_empty());
+        assert_eq!(frozen.provenance("port").unwrap().name(), "user");
+        assert_eq!(frozen.build::<TestConfig>().unwrap().port, 8080);
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn test_checksum() {
+        let system = Layer::serialized("system", &TestConfig::example()).unwrap();
+        let frozen = Layers::new().layer(system.clone()).freeze();
+        let same = Layers::new()
+            .layer(system.clone())
+            .layer(Layer::serialized("user", &BTreeMap::from([("port", 443)])).unwrap())
+            .freeze();
+        let changed = Layers::new()
+            .layer(system)
+            .layer(Layer::serialized("user", &BTreeMap::from([("port", 8080)])).unwrap())
+            .freeze();
+        assert_eq!(frozen.checksum().len(), 64);
+        assert_eq!(frozen.checksum(), same.checksum());
+        assert_ne!(frozen.checksum(), changed.checksum());
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_cascade() {
+        use std::env::temp_dir;
+
+        use crate::ToConfigFile;
+
+        let dir = temp_dir().join("config-cascade");
+        let _ = std::fs::remove_dir_all(&dir);
+        let (system, user) = (dir.join("etc/myapp"), dir.join("home/myapp"));
+        TestConfig::example()
+            .to_config_file(system.join("config.toml"))
+            .unwrap();
+        std::fs::write(system.join("other.toml"), "port = 0").unwrap();
+        std::fs::create_dir_all(&user).unwrap();
+        std::fs::write(user.join("config.toml"), "port = 8080").unwrap();
+        let custom = dir.join("custom.toml");
+        std::fs::write(&custom, "[inner]\nanswer = 0").unwrap();
+
+        let dirs = || [system.clone(), dir.join("missing"), user.clone()];
+        let layers = Layers::new().cascade_from(dirs(), None).unwrap();
+        assert_eq!(
+            layers.watch(),
+            [system.join("config.toml"), user.join("config.toml")]
+        );
+        let layers = Layers::new()
+            .cascade_from(dirs(), Some(custom.clone()))
+            .unwrap();
+        let config = layers.build::<TestConfig>().unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.inner.answer, 0);
+        assert_eq!(layers.watch().len(), 3);
+        assert!(Layers::new()
+            .cascade_from(dirs(), Some(dir.join("missing.toml")))
+            .is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -10,9 +10,39 @@
 //! # Features
 //!
 //! - toml is enabled by default
-//! - json is optional
+//! - json is optional, and provides [`load_iter`] to load huge JSON arrays
+//!   one element at a time
 //! - xml is optional
 //! - yaml is optional
+//! - arbitrary_precision is optional and keeps JSON integers beyond the
+//!   range of `u64` exact in [`ConfigValue`], through serde_json's feature of
+//!   the same name
+//! - async is optional and provides [`AsyncFromConfigFile`] and
+//!   [`AsyncToConfigFile`], backed by tokio
+//! - checksum is optional and stores a SHA-256 checksum next to configuration
+//!   files to detect their corruption when loading them
+//! - clap is optional and layers parsed command line arguments over
+//!   configuration files, see [`layers::Layer::args`]
+//! - chaos is optional and provides the [`chaos`] module to inject failures
+//!   when testing applications
+//! - directories is optional and provides [`paths::ConfigLocation`], the
+//!   configuration directory of an application on every platform
+//! - figment is optional and lets [`layers::Layer`] and [`layers::Layers`]
+//!   act as Figment providers, and Figment providers act as layers
+//! - hot is optional and provides [`watch::HotConfig`], a lock-free handle on
+//!   the content of watched files, backed by arc-swap
+//! - lock is optional and provides advisory locking of configuration files
+//!   shared by several processes
+//! - preserve_order is optional and keeps the keys of [`value::ConfigValue`]
+//!   maps in the order of the document, so that editing a file doesn't
+//!   reorder it
+//! - testing is optional and provides the [`testing`] module to compare
+//!   serialized configurations with golden files
+//! - toml_edit is optional and keeps the comments, whitespace and key
+//!   ordering of existing TOML files when storing them, backed by toml_edit
+//! - uring is optional and provides the [`uring`] module, which performs file
+//!   IO through io_uring on Linux
+//! - watch is optional and provides the [`watch`] module, backed by notify
 //!
 //! # Examples
 //!
@@ -32,13 +62,12 @@
 //! Config { host: "example.com".into() }.to_config_file("/tmp/myconfig.toml").unwrap();
 //! ```
 
-#[cfg(feature = "xml")]
-use std::io::BufReader;
 use std::{
-    ffi::OsStr,
     fs::{File, OpenOptions},
-    io::Write,
-    path::Path,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
 
 use serde::{de::DeserializeOwned, Serialize};
@@ -46,6 +75,89 @@ use thiserror::Error;
 #[cfg(feature = "toml")]
 use toml_crate as toml;
 
+#[cfg(feature = "async")]
+pub use crate::async_io::{AsyncFromConfigFile, AsyncToConfigFile, DEFAULT_BLOCKING_THRESHOLD};
+#[cfg(feature = "json")]
+pub use crate::options::NonFinitePolicy;
+#[cfg(feature = "json")]
+pub use crate::stream::{load_iter, LoadIter};
+pub use crate::{
+    borrowed::load_borrowed,
+    cache::CachedLoader,
+    convert::{convert, convert_with_formats, reformat},
+    detect::{load_any, load_detect},
+    dir::load_dir,
+    edit::{edit, update_key},
+    fallback::{load_or_else, load_or_else_with, load_or_store_default, LoadFailure},
+    field_error::FieldError,
+    format::{ConfigFormat, UnknownFormat},
+    handle::Config,
+    limits::{Limit, Limits},
+    options::{export_overrides, LoadOptions, OverwritePolicy, StoreOptions},
+    paths::{load_any_format, load_first, load_from_env_or},
+    recovery::{recover, Recovery},
+    retry::RetryPolicy,
+    symlink::SymlinkPolicy,
+    transaction::Transaction,
+    value::ConfigValue,
+    warning::Warning,
+};
+
+mod alias;
+#[cfg(feature = "async")]
+mod async_io;
+mod backup;
+mod borrowed;
+mod cache;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "checksum")]
+mod checksum;
+#[cfg(feature = "clap")]
+mod cli;
+mod convert;
+mod detect;
+mod dir;
+mod edit;
+mod fallback;
+mod field_error;
+#[cfg(feature = "figment")]
+mod figment_provider;
+mod format;
+mod handle;
+mod include;
+#[cfg(feature = "json")]
+mod jsonc;
+pub mod layers;
+mod limits;
+#[cfg(feature = "lock")]
+mod lock;
+pub mod map_keys;
+pub mod metadata;
+mod options;
+pub mod paths;
+pub mod policy;
+#[cfg(feature = "toml_edit")]
+mod preserve;
+mod recovery;
+pub mod relative;
+mod retry;
+pub mod source;
+#[cfg(feature = "json")]
+mod stream;
+mod symlink;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod transaction;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod uring;
+pub mod value;
+mod warning;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(windows)]
+mod windows;
+
 /// Trait for loading a struct from a configuration file.
 /// This trait is automatically implemented when [`serde::Deserialize`] is.
 pub trait FromConfigFile {
@@ -53,6 +165,62 @@ pub trait FromConfigFile {
     fn from_config_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigFileError>
     where
         Self: Sized;
+
+    /// Load ourselves from the configuration file located at @path according
+    /// to @options
+    fn from_config_file_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+    ) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Load ourselves from the configuration file located at @path according
+    /// to @options, along with the warnings raised while doing so
+    fn from_config_file_with_warnings<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+    ) -> Result<(Self, Vec<Warning>), ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Load ourselves from the configuration file located at @path while
+    /// holding a shared advisory lock on it, waiting at most @timeout for
+    /// other processes storing it to release theirs
+    #[cfg(feature = "lock")]
+    fn from_config_file_locked<P: AsRef<Path>>(
+        path: P,
+        timeout: Duration,
+    ) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Load ourselves from the configuration file located at @path, failing
+    /// with [`ConfigFileError::Timeout`] if reading it takes longer than
+    /// @timeout, as described in [`LoadOptions::timeout`]
+    fn from_config_file_with_timeout<P: AsRef<Path>>(
+        path: P,
+        timeout: Duration,
+    ) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Load ourselves from @config, a configuration in @format, such as one
+    /// fetched from a database or over the network
+    fn from_config_str(config: &str, format: ConfigFormat) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Load ourselves from @config, the bytes of a configuration in @format
+    fn from_config_slice(config: &[u8], format: ConfigFormat) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Load ourselves from the configuration in @format read from @reader,
+    /// such as a socket, a pipe or a decrypting wrapper
+    fn from_config_reader(reader: impl Read, format: ConfigFormat) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized;
 }
 
 impl<C: DeserializeOwned> FromConfigFile for C {
@@ -61,42 +229,148 @@ impl<C: DeserializeOwned> FromConfigFile for C {
         Self: Sized,
     {
         let path = path.as_ref();
-        let extension = path
-            .extension()
-            .and_then(OsStr::to_str)
-            .map(|extension| extension.to_lowercase());
-        match extension.as_deref() {
-            #[cfg(feature = "json")]
-            Some("json") => {
-                serde_json::from_reader(open_file(path)?).map_err(ConfigFileError::Json)
-            }
-            #[cfg(feature = "toml")]
-            Some("toml") => Ok(toml::from_str(
-                std::fs::read_to_string(path)
-                    .map_err(ConfigFileError::FileAccess)?
-                    .as_str(),
-            )
-            .map_err(TomlError::DeserializationError)?),
-            #[cfg(feature = "xml")]
-            Some("xml") => Ok(quick_xml::de::from_reader(BufReader::new(open_file(
-                path,
-            )?))?),
-            #[cfg(feature = "yaml")]
-            Some("yaml") | Some("yml") => {
-                serde_yaml::from_reader(open_file(path)?).map_err(ConfigFileError::Yaml)
-            }
-            _ => Err(ConfigFileError::UnsupportedFormat),
-        }
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        format.deserialize(&read_file(path)?)
+    }
+
+    fn from_config_file_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+    ) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized,
+    {
+        Ok(options.load(path.as_ref())?.0)
+    }
+
+    fn from_config_file_with_warnings<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+    ) -> Result<(Self, Vec<Warning>), ConfigFileError>
+    where
+        Self: Sized,
+    {
+        options.load(path.as_ref())
+    }
+
+    #[cfg(feature = "lock")]
+    fn from_config_file_locked<P: AsRef<Path>>(
+        path: P,
+        timeout: Duration,
+    ) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        let _lock = lock::FileLock::acquire(path, false, timeout)?;
+        Self::from_config_file(path)
+    }
+
+    fn from_config_file_with_timeout<P: AsRef<Path>>(
+        path: P,
+        timeout: Duration,
+    ) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized,
+    {
+        Self::from_config_file_with_options(path, &LoadOptions::new().timeout(timeout))
+    }
+
+    fn from_config_str(config: &str, format: ConfigFormat) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized,
+    {
+        format.deserialize(config.as_bytes())
+    }
+
+    fn from_config_slice(config: &[u8], format: ConfigFormat) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized,
+    {
+        format.deserialize(config)
+    }
+
+    fn from_config_reader(
+        mut reader: impl Read,
+        format: ConfigFormat,
+    ) -> Result<Self, ConfigFileError>
+    where
+        Self: Sized,
+    {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(ConfigFileError::FileAccess)?;
+        format.deserialize(&data)
     }
 }
 
 /// Trait for storing a struct into a configuration file.
 /// This trait is automatically implemented when [`serde::Serialize`] is.
+///
+/// Files are replaced atomically: a crash or a full disk while storing never
+/// leaves a truncated file behind.
 pub trait ToConfigFile {
     /// Load ourselves from the configuration file located at @path
     fn to_config_file(self, path: impl AsRef<Path>) -> Result<(), ConfigFileError>
     where
         Self: Sized;
+
+    /// Store ourselves into the configuration file located at @path, preceded
+    /// by a [`Metadata`](metadata::Metadata) header
+    fn to_config_file_with_metadata(
+        self,
+        path: impl AsRef<Path>,
+        metadata: &metadata::Metadata,
+    ) -> Result<(), ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Store ourselves into the configuration file located at @path according
+    /// to @options
+    fn to_config_file_with_options(
+        self,
+        path: impl AsRef<Path>,
+        options: &StoreOptions,
+    ) -> Result<(), ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Store ourselves into the configuration file located at @path, unless
+    /// it already holds the same content, so that saving periodically doesn't
+    /// update its modification time nor wake up the programs watching it.
+    /// Return whether the file was written.
+    fn to_config_file_if_changed(self, path: impl AsRef<Path>) -> Result<bool, ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Store ourselves into the configuration file located at @path while
+    /// holding an exclusive advisory lock on it, waiting at most @timeout for
+    /// other processes loading or storing it to release theirs
+    #[cfg(feature = "lock")]
+    fn to_config_file_locked(
+        self,
+        path: impl AsRef<Path>,
+        timeout: Duration,
+    ) -> Result<(), ConfigFileError>
+    where
+        Self: Sized;
+
+    /// Render ourselves as a pretty printed configuration in @format, as
+    /// it would be stored into a file
+    fn to_config_string(&self, format: ConfigFormat) -> Result<String, ConfigFileError>;
+
+    /// Render ourselves as the bytes of a configuration in @format, as they
+    /// would be stored into a file
+    fn to_config_vec(&self, format: ConfigFormat) -> Result<Vec<u8>, ConfigFileError>;
+
+    /// Write ourselves as a configuration in @format to @writer, which is
+    /// flushed afterwards
+    fn to_config_writer(
+        &self,
+        writer: impl Write,
+        format: ConfigFormat,
+    ) -> Result<(), ConfigFileError>;
 }
 
 impl<C: Serialize> ToConfigFile for C {
@@ -105,54 +379,454 @@ impl<C: Serialize> ToConfigFile for C {
         Self: Sized,
     {
         let path = path.as_ref();
-        let extension = path
-            .extension()
-            .and_then(OsStr::to_str)
-            .map(|extension| extension.to_lowercase());
-        match extension.as_deref() {
-            #[cfg(feature = "json")]
-            Some("json") => serde_json::to_writer_pretty(open_write_file(path)?, &self)
-                .map_err(ConfigFileError::Json),
-            #[cfg(feature = "toml")]
-            Some("toml") => {
-                open_write_file(path)?.write_all(
-                    toml::to_string_pretty(&self)
-                        .map_err(TomlError::SerializationError)?
-                        .as_bytes(),
-                )?;
-                Ok(())
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        write_file(
+            path,
+            &preserve_layout(path, format, format.serialize(&self)?),
+        )
+    }
+
+    fn to_config_file_with_metadata(
+        self,
+        path: impl AsRef<Path>,
+        metadata: &metadata::Metadata,
+    ) -> Result<(), ConfigFileError>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        write_file(path, &metadata.attach(format, format.serialize(&self)?)?)
+    }
+
+    fn to_config_file_with_options(
+        self,
+        path: impl AsRef<Path>,
+        options: &StoreOptions,
+    ) -> Result<(), ConfigFileError>
+    where
+        Self: Sized,
+    {
+        let path = &*options.path(path.as_ref())?;
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        store_file(path, format, options.serialize(format, &self)?, options)
+    }
+
+    fn to_config_file_if_changed(self, path: impl AsRef<Path>) -> Result<bool, ConfigFileError>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        let data = preserve_layout(path, format, format.serialize(&self)?);
+        match read_file(path) {
+            Ok(current) if current == data => return Ok(false),
+            Err(ConfigFileError::FileAccess(err)) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(err.into())
+            }
+            _ => {}
+        }
+        write_file(path, &data)?;
+        Ok(true)
+    }
+
+    #[cfg(feature = "lock")]
+    fn to_config_file_locked(
+        self,
+        path: impl AsRef<Path>,
+        timeout: Duration,
+    ) -> Result<(), ConfigFileError>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        let _lock = lock::FileLock::acquire(path, true, timeout)?;
+        self.to_config_file(path)
+    }
+
+    fn to_config_string(&self, format: ConfigFormat) -> Result<String, ConfigFileError> {
+        // Every supported format is text
+        Ok(String::from_utf8_lossy(&format.serialize(self)?).into_owned())
+    }
+
+    fn to_config_vec(&self, format: ConfigFormat) -> Result<Vec<u8>, ConfigFileError> {
+        format.serialize(self)
+    }
+
+    fn to_config_writer(
+        &self,
+        mut writer: impl Write,
+        format: ConfigFormat,
+    ) -> Result<(), ConfigFileError> {
+        writer.write_all(&format.serialize(self)?)?;
+        Ok(writer.flush()?)
+    }
+}
+
+/// Read the whole content of a file
+fn read_file(path: &Path) -> Result<Vec<u8>, ConfigFileError> {
+    read_file_up_to(path, u64::MAX)
+}
+
+/// Read the first @max bytes of a file
+fn read_file_up_to(path: &Path, max: u64) -> Result<Vec<u8>, ConfigFileError> {
+    #[cfg(feature = "chaos")]
+    {
+        chaos::read_error(path)?;
+        if let Some(delay) = chaos::read_delay(path) {
+            std::thread::sleep(delay);
+        }
+    }
+    let mut data = Vec::new();
+    open_file(path)?
+        .take(max)
+        .read_to_end(&mut data)
+        .map_err(ConfigFileError::FileAccess)?;
+    #[cfg(feature = "chaos")]
+    let data = chaos::corrupt(path, data);
+    Ok(data)
+}
+
+/// Keep the layout of the file located at @path, if any, in @data, its new
+/// content in @format
+#[allow(unused_variables)]
+fn preserve_layout(path: &Path, format: ConfigFormat, data: Vec<u8>) -> Vec<u8> {
+    #[cfg(feature = "toml_edit")]
+    if format == ConfigFormat::Toml {
+        return preserve::toml(path, data);
+    }
+    data
+}
+
+/// Replace the content of a file, creating it if needed.
+///
+/// The data is written to a temporary file in the same directory which is
+/// then renamed over the target, so the previous content is left untouched
+/// if writing fails midway.
+fn write_file(path: &Path, data: &[u8]) -> Result<(), ConfigFileError> {
+    write_file_with_options(path, data, &StoreOptions::default())
+}
+
+/// Store @data, serialized as @format according to @options, into the file
+/// located at @path according to @options
+fn store_file(
+    path: &Path,
+    format: ConfigFormat,
+    data: Vec<u8>,
+    options: &StoreOptions,
+) -> Result<(), ConfigFileError> {
+    let data = preserve_layout(path, format, options.overwritten(path, format, data)?);
+    write_file_with_options(path, &data, options)?;
+    #[cfg(feature = "checksum")]
+    if options.checksum {
+        checksum::write(path, &data, options.fsync)?;
+    }
+    Ok(())
+}
+
+/// Replace the content of a file as [`write_file`] does, according to
+/// @options
+fn write_file_with_options(
+    path: &Path,
+    data: &[u8],
+    options: &StoreOptions,
+) -> Result<(), ConfigFileError> {
+    cancelled(options)?;
+    let target = symlink::resolve(path, options.symlinks)?;
+    let in_place =
+        options.in_place || (options.symlinks == SymlinkPolicy::Follow && target != path);
+    // Backups are taken once, so that retrying doesn't rotate them again
+    if let Some(backup) = options.effective_backup() {
+        backup::backup(&target, &backup, in_place).map_err(|err| write_error(path, err))?;
+    }
+    retry::retry(options.retry.as_ref(), || match in_place {
+        true => overwrite_file(&target, data, options),
+        false => replace_file(&target, data, options),
+    })
+    .map_err(|err| write_error(path, err))
+}
+
+/// Overwrite the content of the file located at @path in place, which unlike
+/// [`replace_file`] keeps the file itself but isn't atomic
+fn overwrite_file(path: &Path, data: &[u8], options: &StoreOptions) -> Result<(), ConfigFileError> {
+    let mut open = OpenOptions::new();
+    match options.overwrite {
+        OverwritePolicy::ErrorIfExists => open.write(true).create_new(true),
+        _ => open.write(true).create(true).truncate(true),
+    };
+    let mut file = open_creating_dirs(&open, path, options).map_err(|err| exists(path, err))?;
+    write_all(&mut file, data, options)?;
+    Ok(())
+}
+
+/// Replace the content of the file located at @path with @data through a
+/// temporary file, as [`write_file`] does
+fn replace_file(path: &Path, data: &[u8], options: &StoreOptions) -> Result<(), ConfigFileError> {
+    cancelled(options)?;
+    let temp = write_temp_file(path, data, options)?;
+    // The temporary file is removed when dropped
+    cancelled(options)?;
+    rename_temp_file(temp, path, options)
+}
+
+/// Write @data to a temporary file meant to replace the one located at @path
+fn write_temp_file(
+    path: &Path,
+    data: &[u8],
+    options: &StoreOptions,
+) -> Result<TempFile, ConfigFileError> {
+    let temp = TempFile(Some(temp_path(path)));
+    let temp_path = temp.0.as_deref().unwrap_or(path);
+    #[cfg(feature = "chaos")]
+    {
+        chaos::write_error(path)?;
+        if let Some(written) = chaos::partial_write(path) {
+            open_temp_file(temp_path, path, options)?
+                .write_all(&data[..written.min(data.len())])?;
+            return Err(chaos::partial_write_error().into());
+        }
+    }
+    let mut file = open_temp_file(temp_path, path, options)?;
+    if let Some(permissions) = permissions(path, options) {
+        file.set_permissions(permissions)?;
+    }
+    write_all(&mut file, data, options)?;
+    Ok(temp)
+}
+
+/// Write the whole @data to @file and sync it if @options ask for it,
+/// through io_uring if they ask for it
+fn write_all(file: &mut File, data: &[u8], options: &StoreOptions) -> std::io::Result<()> {
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    if options.uring {
+        return uring::write(file, data, options.fsync);
+    }
+    file.write_all(data)?;
+    if options.fsync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Move @temp, written by [`write_temp_file`], over the file located at
+/// @path
+fn rename_temp_file(
+    mut temp: TempFile,
+    path: &Path,
+    options: &StoreOptions,
+) -> Result<(), ConfigFileError> {
+    #[cfg(windows)]
+    windows::make_writable(path)?;
+    match (&temp.0, options.overwrite) {
+        // Unlike renaming, linking fails when the file exists, even if it was
+        // created in the meantime. The temporary file is removed when dropped.
+        (Some(temp_path), OverwritePolicy::ErrorIfExists) => {
+            match std::fs::hard_link(temp_path, path) {
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        std::io::ErrorKind::Unsupported | std::io::ErrorKind::PermissionDenied
+                    ) =>
+                {
+                    create_copy(temp_path, path, options)?
+                }
+                linked => linked.map_err(|err| exists(path, err.into()))?,
             }
-            #[cfg(feature = "xml")]
-            Some("xml") => Ok(std::fs::write(path, quick_xml::se::to_string(&self)?)?),
-            #[cfg(feature = "yaml")]
-            Some("yaml") | Some("yml") => {
-                serde_yaml::to_writer(open_write_file(path)?, &self).map_err(ConfigFileError::Yaml)
+        }
+        (Some(temp_path), _) => {
+            std::fs::rename(temp_path, path)?;
+            temp.0 = None;
+        }
+        (None, _) => {}
+    }
+    if options.fsync {
+        sync_parent(path)?;
+    }
+    Ok(())
+}
+
+/// Create the file located at @path as a copy of the temporary file located
+/// at @temp, failing if it exists, on filesystems which can't link files
+/// such as FAT, many SMB shares and some FUSE mounts. Unlike linking, the file
+/// can be seen before it is completely written.
+fn create_copy(temp: &Path, path: &Path, options: &StoreOptions) -> Result<(), ConfigFileError> {
+    let mut open = OpenOptions::new();
+    open.write(true).create_new(true);
+    #[cfg(unix)]
+    if let Some(mode) = options.mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        open.mode(mode);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        open.attributes(windows::attributes(path, options));
+    }
+    let mut file = open.open(path).map_err(|err| exists(path, err.into()))?;
+    let copied =
+        std::io::copy(&mut File::open(temp)?, &mut file).and_then(|_| match options.fsync {
+            true => file.sync_all(),
+            false => Ok(()),
+        });
+    // The file was created above, so it is ours to remove
+    if let Err(err) = copied {
+        let _ = std::fs::remove_file(path);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Fail if the asynchronous store writing with @options was cancelled
+fn cancelled(options: &StoreOptions) -> Result<(), ConfigFileError> {
+    #[cfg(feature = "async")]
+    if let Some(cancelled) = &options.cancelled {
+        if cancelled.load(std::sync::atomic::Ordering::Acquire) {
+            return Err(std::io::Error::other("config file store was cancelled").into());
+        }
+    }
+    #[cfg(not(feature = "async"))]
+    let _ = options;
+    Ok(())
+}
+
+/// Report @err, raised when creating the file located at @path, as an
+/// explicit error when the file already exists
+fn exists(path: &Path, err: ConfigFileError) -> ConfigFileError {
+    match err {
+        ConfigFileError::FileAccess(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            std::io::Error::new(err.kind(), format!("{} already exists", path.display())).into()
+        }
+        err => err,
+    }
+}
+
+/// Report @err, raised when writing the file located at @path, as
+/// [`ConfigFileError::ReadOnlyFilesystem`] when the filesystem is read-only
+fn write_error(path: &Path, err: ConfigFileError) -> ConfigFileError {
+    // EROFS on Unix, ERROR_WRITE_PROTECT on Windows
+    const READ_ONLY: i32 = if cfg!(windows) { 19 } else { 30 };
+    match err {
+        ConfigFileError::FileAccess(err) if err.raw_os_error() == Some(READ_ONLY) => {
+            ConfigFileError::ReadOnlyFilesystem(path.to_path_buf())
+        }
+        err => err,
+    }
+}
+
+/// Open the temporary file located at @path which will replace @target, with
+/// the permissions requested by @options from the start so that it is never
+/// readable by others
+fn open_temp_file(
+    path: &Path,
+    target: &Path,
+    options: &StoreOptions,
+) -> Result<File, ConfigFileError> {
+    let mut open = OpenOptions::new();
+    open.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if let Some(mode) = options.mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        open.mode(mode);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        open.attributes(windows::attributes(target, options));
+    }
+    #[cfg(not(windows))]
+    let _ = target;
+    #[cfg(not(any(unix, windows)))]
+    let _ = options;
+    open_creating_dirs(&open, path, options)
+}
+
+/// Open the file located at @path with @open, creating its missing parent
+/// directories unless @options prevent it
+fn open_creating_dirs(
+    open: &OpenOptions,
+    path: &Path,
+    options: &StoreOptions,
+) -> Result<File, ConfigFileError> {
+    // Only look for missing directories when needed, to spare syscalls to
+    // applications storing many files
+    match open.open(path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && !options.keep_dirs => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
             }
-            _ => Err(ConfigFileError::UnsupportedFormat),
+            open.open(path).map_err(ConfigFileError::FileAccess)
+        }
+        file => file.map_err(ConfigFileError::FileAccess),
+    }
+}
+
+/// The permissions to give to the file replacing the one located at @path:
+/// those requested by @options, or else the ones of the file being replaced
+fn permissions(path: &Path, options: &StoreOptions) -> Option<std::fs::Permissions> {
+    // The mode given when creating the file is restricted by the umask
+    #[cfg(unix)]
+    if let Some(mode) = options.mode {
+        use std::os::unix::fs::PermissionsExt;
+        return Some(std::fs::Permissions::from_mode(mode));
+    }
+    #[cfg(not(unix))]
+    let _ = options;
+    // The read-only attribute was given when creating the file on Windows
+    if cfg!(windows) {
+        return None;
+    }
+    std::fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.permissions())
+}
+
+/// Flush the directory entry of @path to disk, so that renaming it survives a
+/// power loss
+fn sync_parent(path: &Path) -> Result<(), ConfigFileError> {
+    // Directories can't be opened as files on Windows, where renames are
+    // made durable by the filesystem journal instead
+    #[cfg(unix)]
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Compute a unique temporary path living in the same directory as @path
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// Temporary file removed when dropped, unless it was renamed in the meantime
+struct TempFile(Option<PathBuf>);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Some(path) = &self.0 {
+            #[cfg(windows)]
+            let _ = windows::make_writable(path);
+            let _ = std::fs::remove_file(path);
         }
     }
 }
 
 /// Open a file in read-only mode
-#[allow(unused)]
 fn open_file(path: &Path) -> Result<File, ConfigFileError> {
     File::open(path).map_err(ConfigFileError::FileAccess)
 }
 
-/// Open a file in write mode
-#[allow(unused)]
-fn open_write_file(path: &Path) -> Result<File, ConfigFileError> {
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-    OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(path)
-        .map_err(ConfigFileError::FileAccess)
-}
-
 /// This type represents all possible errors that can occur when loading or
 /// storing data from a configuration file.
 #[derive(Error, Debug)]
@@ -176,6 +850,73 @@ pub enum ConfigFileError {
     #[error("couldn't parse YAML file")]
     /// There was an error while parsing the YAML data
     Yaml(#[from] serde_yaml::Error),
+    #[cfg(feature = "figment")]
+    #[error("couldn't extract config from Figment provider")]
+    /// There was an error while extracting a configuration from a Figment
+    /// provider
+    Figment(#[source] Box<figment::Error>),
+    #[cfg(feature = "watch")]
+    #[error("couldn't watch config file")]
+    /// There was an error while watching the configuration file
+    Watch(#[from] notify::Error),
+    #[cfg(feature = "watch")]
+    #[error("config file kept failing to load and was moved to {0}")]
+    /// The watched configuration file kept failing to load, so it was moved
+    /// to the given path and replaced by its last valid content
+    Quarantined(std::path::PathBuf),
+    #[cfg(feature = "checksum")]
+    #[error("config file {0} doesn't match its checksum")]
+    /// The configuration file doesn't match the checksum stored next to it,
+    /// so it was corrupted or modified behind our back
+    ChecksumMismatch(std::path::PathBuf),
+    #[cfg(feature = "lock")]
+    #[error("timed out waiting for config file lock")]
+    /// Another process kept the configuration file locked for too long
+    LockTimeout,
+    #[error("config file {0} includes itself")]
+    /// The configuration file includes itself, directly or through the files
+    /// it includes
+    IncludeCycle(std::path::PathBuf),
+    #[error("config file {0} includes files too deeply")]
+    /// The configuration file is included through too many nested files,
+    /// which is most likely a mistake
+    IncludeDepth(std::path::PathBuf),
+    #[error("config file {0} is on a read-only filesystem")]
+    /// The configuration file couldn't be written because it lives on a
+    /// read-only filesystem or write-protected media, so it should be stored
+    /// elsewhere
+    ReadOnlyFilesystem(std::path::PathBuf),
+    #[error("config file {0} is a symbolic link")]
+    /// The configuration file is a symbolic link, which
+    /// [`SymlinkPolicy::RefuseSymlink`] refuses to go through
+    Symlink(std::path::PathBuf),
+    #[error("config file {0} exists in several formats")]
+    /// [`load_any_format`] found the configuration file in several formats
+    AmbiguousFormat(std::path::PathBuf),
+    #[error("couldn't expand {0} in config file path")]
+    /// [`paths::expand_path`] found an unset environment variable or an
+    /// unknown user in a path, given as written in the path
+    PathExpansion(String),
+    #[error("timed out reading config file {0}")]
+    /// Reading the configuration file took longer than the timeout given to
+    /// [`LoadOptions::timeout`]
+    Timeout(std::path::PathBuf),
+    #[error("config file exceeds the limit on its {0}")]
+    /// The configuration file exceeds the [`Limits`] given to
+    /// [`LoadOptions::limits`], with the exceeded limit
+    LimitExceeded(Limit),
+    #[error("metadata value {0:?} can't be written in a comment")]
+    /// A [`metadata::Metadata`] value holds control characters or `--`,
+    /// which could end the comment holding it
+    InvalidMetadata(String),
+    #[error("invalid config: {0}")]
+    /// The configuration was rejected by a validation callback, with the
+    /// given reason
+    Validation(String),
+    #[error("couldn't convert config value")]
+    /// There was an error while converting from or to a
+    /// [`ConfigValue`]
+    Value(#[from] value::ValueError),
     #[error("don't know how to parse file")]
     /// We don't know how to parse this format according to the file extension
     UnsupportedFormat,
@@ -202,21 +943,21 @@ mod test {
     use super::*;
 
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
-    struct TestConfig {
-        host: String,
-        port: u64,
-        tags: Vec<String>,
-        inner: TestConfigInner,
+    pub(crate) struct TestConfig {
+        pub(crate) host: String,
+        pub(crate) port: u64,
+        pub(crate) tags: Vec<String>,
+        pub(crate) inner: TestConfigInner,
     }
 
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
-    struct TestConfigInner {
-        answer: u8,
+    pub(crate) struct TestConfigInner {
+        pub(crate) answer: u8,
     }
 
     impl TestConfig {
         #[allow(unused)]
-        fn example() -> Self {
+        pub(crate) fn example() -> Self {
             Self {
                 host: "example.com".to_string(),
                 port: 443,
@@ -257,6 +998,122 @@ mod test {
         assert!(matches!(config, Err(ConfigFileError::FileAccess(_))));
     }
 
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_config_string() {
+        let config = TestConfig::example()
+            .to_config_string(ConfigFormat::Toml)
+            .unwrap();
+        assert!(config.contains("port = 443"));
+        assert_eq!(
+            TestConfig::from_config_str(&config, ConfigFormat::Toml).unwrap(),
+            TestConfig::example()
+        );
+        assert!(TestConfig::from_config_str("port = ", ConfigFormat::Toml).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_config_io() {
+        let mut buffer = Vec::new();
+        TestConfig::example()
+            .to_config_writer(&mut buffer, ConfigFormat::Toml)
+            .unwrap();
+        assert_eq!(
+            TestConfig::from_config_reader(buffer.as_slice(), ConfigFormat::Toml).unwrap(),
+            TestConfig::example()
+        );
+        let data = TestConfig::example()
+            .to_config_vec(ConfigFormat::Toml)
+            .unwrap();
+        assert_eq!(data, buffer);
+        assert_eq!(
+            TestConfig::from_config_slice(&data, ConfigFormat::Toml).unwrap(),
+            TestConfig::example()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_if_changed() {
+        let path = temp_dir().join("config-if-changed.toml");
+        let _ = std::fs::remove_file(&path);
+        assert!(TestConfig::example()
+            .to_config_file_if_changed(&path)
+            .unwrap());
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert!(!TestConfig::example()
+            .to_config_file_if_changed(&path)
+            .unwrap());
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().modified().unwrap(),
+            modified
+        );
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        assert!(changed.to_config_file_if_changed(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_create_copy() {
+        let dir = temp_dir().join("config-create-copy");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (temp, path) = (dir.join("temp"), dir.join("config.toml"));
+        std::fs::write(&temp, "port = 443").unwrap();
+        create_copy(&temp, &path, &StoreOptions::new()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "port = 443");
+        std::fs::write(&path, "port = 8080").unwrap();
+        assert!(matches!(
+            create_copy(&temp, &path, &StoreOptions::new()),
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == std::io::ErrorKind::AlreadyExists
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "port = 8080");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_only_filesystem() {
+        let path = Path::new("/media/cdrom/config.toml");
+        let code = if cfg!(windows) { 19 } else { 30 };
+        let err = std::io::Error::from_raw_os_error(code);
+        assert!(matches!(
+            write_error(path, err.into()),
+            ConfigFileError::ReadOnlyFilesystem(error_path) if error_path == path
+        ));
+        let err = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(matches!(
+            write_error(path, err.into()),
+            ConfigFileError::FileAccess(_)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_atomic_write() {
+        let dir = temp_dir().join("config-atomic-write");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 8080);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     #[cfg(feature = "json")]
     fn test_json() {
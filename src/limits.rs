@@ -0,0 +1,644 @@
+// Nothing is parsed without any format enabled
+#![cfg_attr(
+    not(any(feature = "json", feature = "toml", feature = "xml", feature = "yaml")),
+    allow(dead_code)
+)]
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    path::Path,
+};
+
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+
+use crate::{read_file_up_to, ConfigFileError};
+
+/// Limits on the configuration files loaded with [`crate::LoadOptions::limits`],
+/// for services accepting files from untrusted users.
+///
+/// They are enforced while parsing, whatever the format, so that a small
+/// file expanding into a huge document, such as a YAML file using aliases,
+/// is rejected before exhausting memory.
+///
+/// ```rust
+/// use config_file::{LoadOptions, Limits};
+///
+/// let options = LoadOptions::new().limits(Limits {
+///     max_bytes: 64 * 1024,
+///     ..Limits::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum size of the files, in bytes, 1 MiB by default
+    pub max_bytes: u64,
+    /// The maximum nesting depth of maps and arrays in each file, 64 by
+    /// default
+    pub max_depth: usize,
+    /// The maximum number of map entries and array elements in all the
+    /// files, 65536 by default
+    pub max_keys: usize,
+}
+
+impl Limits {
+    /// No limit at all, for documents only parsed strictly
+    pub(crate) const UNLIMITED: Self = Self {
+        max_bytes: u64::MAX,
+        max_depth: usize::MAX,
+        max_keys: usize::MAX,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            max_depth: 64,
+            max_keys: 65536,
+        }
+    }
+}
+
+/// One of the [`Limits`], as reported by [`crate::ConfigFileError::LimitExceeded`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    /// [`Limits::max_bytes`]
+    Size,
+    /// [`Limits::max_depth`]
+    Depth,
+    /// [`Limits::max_keys`]
+    Keys,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Size => "size",
+            Self::Depth => "nesting depth",
+            Self::Keys => "number of keys",
+        })
+    }
+}
+
+/// What is left of the limits while loading a configuration, spent by all
+/// the files and documents merged into it, and whether its unknown fields
+/// are rejected
+pub(crate) struct Budget {
+    bytes: Cell<u64>,
+    max_depth: usize,
+    keys: Cell<usize>,
+    exceeded: Cell<Option<Limit>>,
+    strict: bool,
+    /// Whether JSON documents may hold floats as `"NaN"`, `"Infinity"` and
+    /// `"-Infinity"`
+    non_finite: bool,
+    /// The last map key seen, named when its field turns out to be unknown
+    key: RefCell<String>,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: &Limits) -> Self {
+        Self {
+            bytes: Cell::new(limits.max_bytes),
+            max_depth: limits.max_depth,
+            keys: Cell::new(limits.max_keys),
+            exceeded: Cell::new(None),
+            strict: false,
+            non_finite: false,
+            key: RefCell::new(String::new()),
+        }
+    }
+
+    /// Reject the fields which `T` ignores if @strict, as
+    /// `#[serde(deny_unknown_fields)]` does
+    pub(crate) fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Accept the strings `"NaN"`, `"Infinity"` and `"-Infinity"` for the
+    /// floats of JSON documents if @accept
+    pub(crate) fn non_finite_strings(mut self, accept: bool) -> Self {
+        self.non_finite = accept;
+        self
+    }
+
+    /// The limit which was exceeded, if any
+    pub(crate) fn exceeded(&self) -> Option<Limit> {
+        self.exceeded.get()
+    }
+
+    /// Read the file located at @path, unless it holds more bytes than are
+    /// left, without reading any further than that
+    pub(crate) fn read(&self, path: &Path) -> Result<Vec<u8>, ConfigFileError> {
+        let data = read_file_up_to(path, self.bytes.get().saturating_add(1))?;
+        self.spend(data.len())?;
+        Ok(data)
+    }
+
+    /// Spend @len bytes of the budget
+    pub(crate) fn spend(&self, len: usize) -> Result<(), ConfigFileError> {
+        match self.bytes.get().checked_sub(len as u64) {
+            Some(bytes) => {
+                self.bytes.set(bytes);
+                Ok(())
+            }
+            None => {
+                self.exceeded.set(Some(Limit::Size));
+                Err(ConfigFileError::LimitExceeded(Limit::Size))
+            }
+        }
+    }
+
+    /// Deserialize a `T` from @deserializer within this budget
+    pub(crate) fn deserialize<'de, T: de::Deserialize<'de>, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        self.deserialize_with(deserializer, false)
+    }
+
+    /// Deserialize a `T` from the JSON @deserializer within this budget,
+    /// along with the non-finite floats it accepts
+    #[cfg(feature = "json")]
+    pub(crate) fn deserialize_json<'de, T: de::Deserialize<'de>, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        self.deserialize_with(deserializer, self.non_finite)
+    }
+
+    fn deserialize_with<'de, T: de::Deserialize<'de>, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+        non_finite: bool,
+    ) -> Result<T, D::Error> {
+        T::deserialize(Limited {
+            inner: deserializer,
+            budget: self,
+            depth: 0,
+            key: false,
+            non_finite,
+        })
+    }
+
+    /// Fail because @limit was exceeded
+    fn exceed<E: de::Error>(&self, limit: Limit) -> E {
+        self.exceeded.set(Some(limit));
+        E::custom(format_args!(
+            "configuration exceeds the limit on its {limit}"
+        ))
+    }
+
+    /// Count one more map entry or array element
+    fn charge<E: de::Error>(&self) -> Result<(), E> {
+        match self.keys.get().checked_sub(1) {
+            Some(keys) => {
+                self.keys.set(keys);
+                Ok(())
+            }
+            None => Err(self.exceed(Limit::Keys)),
+        }
+    }
+
+    /// Enter a map or an array nested at @depth
+    fn enter<E: de::Error>(&self, depth: usize) -> Result<usize, E> {
+        match depth < self.max_depth {
+            true => Ok(depth + 1),
+            false => Err(self.exceed(Limit::Depth)),
+        }
+    }
+}
+
+/// Deserializer enforcing a [`Budget`] over the deserializer of a format
+struct Limited<'b, T> {
+    inner: T,
+    budget: &'b Budget,
+    depth: usize,
+    /// Whether this is a map key rather than a value
+    key: bool,
+    /// Whether floats may be given as non-finite strings
+    non_finite: bool,
+}
+
+impl<'b, T> Limited<'b, T> {
+    /// Wrap @inner, found at the same depth as this value
+    fn wrap<U>(&self, inner: U) -> Limited<'b, U> {
+        Limited {
+            inner,
+            budget: self.budget,
+            depth: self.depth,
+            key: self.key,
+            non_finite: self.non_finite,
+        }
+    }
+
+    /// Remember @name if this is a map key, to report it as an unknown field
+    fn name(&self, name: &str) {
+        if self.key && self.budget.strict {
+            name.clone_into(&mut self.budget.key.borrow_mut());
+        }
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $type:ty),*),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $type,)*
+                visitor: V,
+            ) -> Result<V::Value, D::Error> {
+                let visitor = self.wrap(visitor);
+                self.inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Limited<'_, D> {
+    type Error = D::Error;
+
+    forward_deserialize! {
+        deserialize_any(), deserialize_bool(), deserialize_i8(), deserialize_i16(),
+        deserialize_i32(), deserialize_i64(), deserialize_i128(), deserialize_u8(),
+        deserialize_u16(), deserialize_u32(), deserialize_u64(), deserialize_u128(),
+        deserialize_char(), deserialize_str(),
+        deserialize_string(), deserialize_bytes(), deserialize_byte_buf(), deserialize_option(),
+        deserialize_unit(), deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str), deserialize_seq(),
+        deserialize_tuple(len: usize), deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(), deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        let visitor = self.wrap(visitor);
+        match self.non_finite {
+            // Formats only hand strings to visitors asking for anything
+            true => self.inner.deserialize_any(NonFinite(visitor)),
+            false => self.inner.deserialize_f32(visitor),
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        let visitor = self.wrap(visitor);
+        match self.non_finite {
+            true => self.inner.deserialize_any(NonFinite(visitor)),
+            false => self.inner.deserialize_f64(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        // Only the values of unknown fields are ignored
+        if self.budget.strict && !self.key {
+            return Err(de::Error::custom(format_args!(
+                "unknown field `{}`",
+                self.budget.key.borrow()
+            )));
+        }
+        let visitor = self.wrap(visitor);
+        self.inner.deserialize_ignored_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($type:ty),)*) => {
+        $(
+            fn $method<E: de::Error>(self, value: $type) -> Result<V::Value, E> {
+                self.inner.$method(value)
+            }
+        )*
+    };
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Limited<'_, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    forward_visit! {
+        visit_bool(bool), visit_i8(i8), visit_i16(i16), visit_i32(i32), visit_i64(i64),
+        visit_i128(i128), visit_u8(u8), visit_u16(u16), visit_u32(u32), visit_u64(u64),
+        visit_u128(u128), visit_f32(f32), visit_f64(f64), visit_char(char), visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]), visit_byte_buf(Vec<u8>),
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<V::Value, E> {
+        self.name(value);
+        self.inner.visit_str(value)
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<V::Value, E> {
+        self.name(value);
+        self.inner.visit_borrowed_str(value)
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<V::Value, E> {
+        self.name(&value);
+        self.inner.visit_string(value)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<V::Value, E> {
+        self.inner.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<V::Value, E> {
+        self.inner.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        let deserializer = self.wrap(deserializer);
+        self.inner.visit_some(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<V::Value, D::Error> {
+        let deserializer = self.wrap(deserializer);
+        self.inner.visit_newtype_struct(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        let depth = self.budget.enter(self.depth)?;
+        self.inner.visit_seq(Limited {
+            inner: seq,
+            budget: self.budget,
+            depth,
+            key: false,
+            non_finite: self.non_finite,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        let depth = self.budget.enter(self.depth)?;
+        self.inner.visit_map(Limited {
+            inner: map,
+            budget: self.budget,
+            depth,
+            key: false,
+            non_finite: self.non_finite,
+        })
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        let data = self.wrap(data);
+        self.inner.visit_enum(data)
+    }
+}
+
+/// Visitor of a float also accepting the strings `"NaN"`, `"Infinity"` and
+/// `"-Infinity"`, and nothing else that a float doesn't accept
+struct NonFinite<V>(V);
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for NonFinite<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<V::Value, E> {
+        self.0.visit_i64(value)
+    }
+
+    fn visit_i128<E: de::Error>(self, value: i128) -> Result<V::Value, E> {
+        self.0.visit_i128(value)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<V::Value, E> {
+        self.0.visit_u64(value)
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<V::Value, E> {
+        self.0.visit_u128(value)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<V::Value, E> {
+        self.0.visit_f64(value)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<V::Value, E> {
+        match value {
+            "NaN" => self.0.visit_f64(f64::NAN),
+            "Infinity" => self.0.visit_f64(f64::INFINITY),
+            "-Infinity" => self.0.visit_f64(f64::NEG_INFINITY),
+            _ => self.0.visit_str(value),
+        }
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<V::Value, A::Error> {
+        // serde_json hands numbers to visitors asking for anything as a map
+        // holding their text when using arbitrary precision
+        match map.next_key::<String>()? {
+            Some(key) if key == crate::value::JSON_NUMBER => {
+                let number = map.next_value::<String>()?;
+                let number = number.parse().map_err(de::Error::custom)?;
+                self.0.visit_f64(number)
+            }
+            _ => Err(de::Error::invalid_type(de::Unexpected::Map, &self)),
+        }
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for Limited<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        let deserializer = self.wrap(deserializer);
+        self.inner.deserialize(deserializer)
+    }
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for Limited<'_, A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, A::Error> {
+        let seed = self.wrap(seed);
+        let element = self.inner.next_element_seed(seed)?;
+        if element.is_some() {
+            self.budget.charge()?;
+        }
+        Ok(element)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Limited<'_, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        let seed = Limited {
+            inner: seed,
+            budget: self.budget,
+            depth: self.depth,
+            key: true,
+            non_finite: self.non_finite,
+        };
+        let key = self.inner.next_key_seed(seed)?;
+        if key.is_some() {
+            self.budget.charge()?;
+        }
+        Ok(key)
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, A::Error> {
+        let seed = self.wrap(seed);
+        self.inner.next_value_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'b, 'de, A: EnumAccess<'de>> EnumAccess<'de> for Limited<'b, A> {
+    type Error = A::Error;
+    type Variant = Limited<'b, A::Variant>;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self::Variant), A::Error> {
+        let (value, variant) = self.inner.variant_seed(seed)?;
+        Ok((
+            value,
+            Limited {
+                inner: variant,
+                budget: self.budget,
+                depth: self.depth,
+                key: false,
+                non_finite: self.non_finite,
+            },
+        ))
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> VariantAccess<'de> for Limited<'_, A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.inner.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, A::Error> {
+        let seed = self.wrap(seed);
+        self.inner.newtype_variant_seed(seed)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, A::Error> {
+        let visitor = self.wrap(visitor);
+        self.inner.tuple_variant(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        let visitor = self.wrap(visitor);
+        self.inner.struct_variant(fields, visitor)
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, ConfigFileError, FromConfigFile, LoadOptions, ToConfigFile};
+
+    #[test]
+    fn test_limits() {
+        let path = temp_dir().join("config-limits.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let load = |limits| {
+            TestConfig::from_config_file_with_options(&path, &LoadOptions::new().limits(limits))
+        };
+        assert_eq!(load(Limits::default()).unwrap(), TestConfig::example());
+        let exceeded = |limits| match load(limits) {
+            Err(ConfigFileError::LimitExceeded(limit)) => limit,
+            config => panic!("unexpected {config:?}"),
+        };
+        let size = std::fs::metadata(&path).unwrap().len();
+        let limits = |max_bytes, max_depth, max_keys| Limits {
+            max_bytes,
+            max_depth,
+            max_keys,
+        };
+        assert_eq!(exceeded(limits(size - 1, 64, 100)), Limit::Size);
+        assert_eq!(exceeded(limits(size, 1, 100)), Limit::Depth);
+        assert_eq!(exceeded(limits(size, 64, 5)), Limit::Keys);
+        assert_eq!(load(limits(size, 2, 8)).unwrap(), TestConfig::example());
+
+        // The files merged into the configuration share the limits
+        let local = temp_dir().join("config-limits.local.toml");
+        std::fs::write(&local, "port = 8080\n").unwrap();
+        let load = |limits| {
+            let options = LoadOptions::new().limits(limits).local_override(true);
+            TestConfig::from_config_file_with_options(&path, &options)
+        };
+        let local_size = std::fs::metadata(&local).unwrap().len();
+        assert_eq!(load(limits(size + local_size, 2, 8)).unwrap().port, 8080);
+        let exceeded = |limits| match load(limits) {
+            Err(ConfigFileError::LimitExceeded(limit)) => limit,
+            config => panic!("unexpected {config:?}"),
+        };
+        assert_eq!(exceeded(limits(size + local_size - 1, 2, 8)), Limit::Size);
+        assert_eq!(exceeded(limits(size + local_size, 2, 7)), Limit::Keys);
+        std::fs::remove_file(local).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let options = LoadOptions::new().limits(Limits::default());
+        for format in crate::ConfigFormat::enabled() {
+            let path = temp_dir().join(format!("config-limits-format.{format}"));
+            TestConfig::example().to_config_file(&path).unwrap();
+            let config = TestConfig::from_config_file_with_options(&path, &options).unwrap();
+            assert_eq!(config, TestConfig::example());
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_aliases() {
+        let path = temp_dir().join("config-limits.yaml");
+        let mut bomb = String::from("a: &a [1, 1, 1, 1, 1, 1, 1, 1, 1, 1]\n");
+        for (name, alias) in ["b", "c", "d", "e", "f", "g", "h", "i"]
+            .iter()
+            .zip("abcdefgh".chars())
+        {
+            let aliases = vec![format!("*{alias}"); 10].join(", ");
+            bomb.push_str(&format!("{name}: &{name} [{aliases}]\n"));
+        }
+        std::fs::write(&path, bomb).unwrap();
+        let options = LoadOptions::new().limits(Limits::default());
+        assert!(matches!(
+            crate::ConfigValue::from_config_file_with_options(&path, &options),
+            Err(ConfigFileError::LimitExceeded(Limit::Keys))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use fs4::fs_std::FileExt;
+
+use crate::ConfigFileError;
+
+/// Longest time to wait between two attempts at taking a lock
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Advisory lock guarding the configuration file it was taken for, released
+/// when dropped
+pub(crate) struct FileLock(#[allow(unused)] File);
+
+impl FileLock {
+    /// Lock the configuration file located at @path, waiting at most
+    /// @timeout for other processes to release it.
+    ///
+    /// Since files are replaced when stored, the lock is taken on a sibling
+    /// `<name>.lock` file, which is left behind afterwards.
+    pub(crate) fn acquire(
+        path: &Path,
+        exclusive: bool,
+        timeout: Duration,
+    ) -> Result<Self, ConfigFileError> {
+        let path = lock_path(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let locked = if exclusive {
+                FileExt::try_lock_exclusive(&file)?
+            } else {
+                FileExt::try_lock_shared(&file)?
+            };
+            if locked {
+                return Ok(Self(file));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ConfigFileError::LockTimeout);
+            }
+            std::thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, ToConfigFile};
+
+    #[test]
+    fn test_lock() {
+        let dir = temp_dir().join("config-lock");
+        let path = dir.join("config.toml");
+        let timeout = Duration::from_millis(50);
+        TestConfig::example()
+            .to_config_file_locked(&path, timeout)
+            .unwrap();
+
+        let shared = FileLock::acquire(&path, false, timeout).unwrap();
+        assert_eq!(
+            TestConfig::from_config_file_locked(&path, timeout).unwrap(),
+            TestConfig::example()
+        );
+        let result = TestConfig::example().to_config_file_locked(&path, timeout);
+        assert!(matches!(result, Err(ConfigFileError::LockTimeout)));
+        drop(shared);
+
+        let exclusive = FileLock::acquire(&path, true, timeout).unwrap();
+        let result = TestConfig::from_config_file_locked(&path, timeout);
+        assert!(matches!(result, Err(ConfigFileError::LockTimeout)));
+        drop(exclusive);
+        assert!(TestConfig::from_config_file_locked(&path, timeout).is_ok());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
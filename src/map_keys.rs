@@ -0,0 +1,417 @@
+//! Serde adapter for maps whose keys are not strings.
+//!
+//! Most configuration formats only accept string keys: TOML rejects a
+//! `HashMap<u32, T>` outright, JSON silently stringifies it and YAML keeps
+//! the integers as they are. Annotating such a field with
+//! `#[serde(with = "config_file::map_keys")]` always writes the keys as
+//! strings and parses them back into their original type, so the map
+//! round-trips the same way in JSON, TOML and YAML.
+//!
+//! Integers, floats, booleans, chars, strings and unit enum variants are
+//! supported as keys.
+//!
+//! XML stores map keys as element names, so storing an XML file fails with
+//! [`ConfigFileError::Xml`](crate::ConfigFileError::Xml) when a key isn't a
+//! valid XML name, such as integers and floats, or strings holding spaces.
+//! Booleans and enum variants named as XML names round-trip.
+//!
+//! ```rust
+//! use std::collections::BTreeMap;
+//!
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+//! #[serde(rename_all = "lowercase")]
+//! enum Level {
+//!     Low,
+//!     High,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     #[serde(with = "config_file::map_keys")]
+//!     ports: BTreeMap<u16, String>,
+//!     #[serde(with = "config_file::map_keys")]
+//!     limits: BTreeMap<Level, u32>,
+//! }
+//! ```
+
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{self, IntoDeserializer, MapAccess, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Impossible, SerializeMap},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// Serialize a map, turning every key into a string
+pub fn serialize<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    let mut out = serializer.serialize_map(None)?;
+    for (key, value) in map {
+        let key = key.serialize(KeySerializer).map_err(ser::Error::custom)?;
+        out.serialize_entry(&key, value)?;
+    }
+    out.end()
+}
+
+/// Deserialize a map, parsing every string key back into `K`
+pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: FromIterator<(K, V)>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    struct MapVisitor<M, K, V>(PhantomData<(M, K, V)>);
+
+    impl<'de, M, K, V> Visitor<'de> for MapVisitor<M, K, V>
+    where
+        M: FromIterator<(K, V)>,
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+    {
+        type Value = M;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a map")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<M, A::Error> {
+            let mut entries = Vec::with_capacity(access.size_hint().unwrap_or(0));
+            while let Some((key, value)) = access.next_entry::<String, V>()? {
+                let key = K::deserialize(KeyDeserializer(key)).map_err(de::Error::custom)?;
+                entries.push((key, value));
+            }
+            Ok(entries.into_iter().collect())
+        }
+    }
+
+    deserializer.deserialize_map(MapVisitor(PhantomData))
+}
+
+/// Error raised when a key cannot be represented as a string
+#[derive(Debug)]
+struct KeyError(String);
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl ser::Error for KeyError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for KeyError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Serializer turning a map key into its string representation
+struct KeySerializer;
+
+macro_rules! serialize_display {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, value: $ty) -> Result<String, KeyError> {
+                Ok(value.to_string())
+            }
+        )*
+    };
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = KeyError;
+    type SerializeSeq = Impossible<String, KeyError>;
+    type SerializeTuple = Impossible<String, KeyError>;
+    type SerializeTupleStruct = Impossible<String, KeyError>;
+    type SerializeTupleVariant = Impossible<String, KeyError>;
+    type SerializeMap = Impossible<String, KeyError>;
+    type SerializeStruct = Impossible<String, KeyError>;
+    type SerializeStructVariant = Impossible<String, KeyError>;
+
+    serialize_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<String, KeyError> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<String, KeyError> {
+        Err(unsupported("none"))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<String, KeyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, KeyError> {
+        Err(unsupported("unit"))
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<String, KeyError> {
+        Err(unsupported("unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<String, KeyError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<String, KeyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, KeyError> {
+        Err(unsupported("newtype variant"))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, KeyError> {
+        Err(unsupported("sequence"))
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, KeyError> {
+        Err(unsupported("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, KeyError> {
+        Err(unsupported("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, KeyError> {
+        Err(unsupported("tuple variant"))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, KeyError> {
+        Err(unsupported("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, KeyError> {
+        Err(unsupported("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, KeyError> {
+        Err(unsupported("struct variant"))
+    }
+}
+
+fn unsupported(kind: &str) -> KeyError {
+    KeyError(format!("{kind} cannot be used as a map key"))
+}
+
+/// Deserializer parsing a string key into whatever type is requested
+struct KeyDeserializer(String);
+
+macro_rules! deserialize_parse {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(
+                        de::Unexpected::Str(&self.0),
+                        &visitor,
+                    )),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+    type Error = KeyError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parse! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        IntoDeserializer::<KeyError>::into_deserializer(self.0)
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::{Deserialize, Serialize};
+
+    #[allow(unused)]
+    use crate::{ConfigFileError, FromConfigFile, ToConfigFile};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    enum Level {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Keyed {
+        #[serde(with = "super")]
+        ports: HashMap<u32, String>,
+        #[serde(with = "super")]
+        levels: BTreeMap<Level, i8>,
+    }
+
+    #[allow(unused)]
+    fn example() -> Keyed {
+        Keyed {
+            ports: HashMap::from([(80, "http".into()), (443, "https".into())]),
+            levels: BTreeMap::from([(Level::Low, -1), (Level::High, 1)]),
+        }
+    }
+
+    #[allow(unused)]
+    fn test_round_trip(extension: &str) {
+        let mut path = std::env::temp_dir().join("config-map-keys");
+        path.set_extension(extension);
+        example().to_config_file(&path).unwrap();
+        assert_eq!(Keyed::from_config_file(&path).unwrap(), example());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_toml() {
+        test_round_trip("toml");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json() {
+        test_round_trip("json");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_yaml() {
+        test_round_trip("yaml");
+        let text = serde_yaml::to_string(&example()).unwrap();
+        assert!(text.contains("\"443\""));
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn test_xml() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Levels {
+            #[serde(with = "super")]
+            levels: BTreeMap<Level, i8>,
+        }
+
+        let path = std::env::temp_dir().join("config-map-keys.xml");
+        let levels = Levels {
+            levels: example().levels,
+        };
+        (&levels).to_config_file(&path).unwrap();
+        assert_eq!(Levels::from_config_file(&path).unwrap(), levels);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            example().to_config_file(&path),
+            Err(ConfigFileError::Xml(_))
+        ));
+    }
+}
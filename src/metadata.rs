@@ -0,0 +1,290 @@
+//! Self-describing metadata header for stored configuration files.
+//!
+//! TOML and YAML files get the metadata as a leading comment block, XML files
+//! as a leading XML comment and JSON files, which have no comments, as a
+//! reserved top-level [`METADATA_KEY`] entry, which is left out when loading
+//! the file.
+//!
+//! ```text
+//! # app: myapp
+//! # schema-version: 3
+//! # generated-at: 1700000000
+//! # config-file: 0.2.3
+//! host = "example.com"
+//! ```
+
+#[cfg(feature = "json")]
+use std::borrow::Cow;
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{format::ConfigFormat, read_file, ConfigFileError};
+
+/// Name of the reserved key holding the metadata in formats without comments
+pub const METADATA_KEY: &str = "$metadata";
+
+/// Information identifying the application and schema a file was written for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Name of the application owning the file
+    pub app: Option<String>,
+    /// Version of the configuration schema
+    pub schema_version: Option<u32>,
+    /// When the file was written, in seconds since the Unix epoch
+    pub generated_at: Option<u64>,
+    /// Version of config-file which wrote the file
+    pub crate_version: Option<String>,
+}
+
+impl Metadata {
+    /// Describe a file written now by @app using schema @schema_version
+    pub fn new(app: impl Into<String>, schema_version: u32) -> Self {
+        Self {
+            app: Some(app.into()),
+            schema_version: Some(schema_version),
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            crate_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        }
+    }
+
+    /// Read back the metadata stored in the configuration file located at
+    /// @path, if any
+    pub fn read(path: impl AsRef<Path>) -> Result<Option<Self>, ConfigFileError> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        let data = read_file(path)?;
+        match format {
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => {
+                let document: serde_json::Value = serde_json::from_slice(&data)?;
+                Ok(document.get(METADATA_KEY).map(|metadata| Self {
+                    app: metadata["app"].as_str().map(str::to_string),
+                    schema_version: metadata["schema-version"]
+                        .as_u64()
+                        .and_then(|version| version.try_into().ok()),
+                    generated_at: metadata["generated-at"].as_u64(),
+                    crate_version: metadata["config-file"].as_str().map(str::to_string),
+                }))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Ok(Self::parse_comments(&String::from_utf8_lossy(&data))),
+        }
+    }
+
+    /// Add ourselves to @data, already serialized as @format
+    pub(crate) fn attach(
+        &self,
+        format: ConfigFormat,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, ConfigFileError> {
+        let (prefix, suffix) = match format {
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => {
+                let mut document: serde_json::Value = serde_json::from_slice(&data)?;
+                if let Some(object) = document.as_object_mut() {
+                    let mut metadata = serde_json::Map::new();
+                    if let Some(app) = &self.app {
+                        metadata.insert("app".into(), app.as_str().into());
+                    }
+                    if let Some(schema_version) = self.schema_version {
+                        metadata.insert("schema-version".into(), schema_version.into());
+                    }
+                    if let Some(generated_at) = self.generated_at {
+                        metadata.insert("generated-at".into(), generated_at.into());
+                    }
+                    if let Some(crate_version) = &self.crate_version {
+                        metadata.insert("config-file".into(), crate_version.as_str().into());
+                    }
+                    object.insert(METADATA_KEY.to_string(), metadata.into());
+                }
+                return Ok(serde_json::to_vec_pretty(&document)?);
+            }
+            #[cfg(feature = "xml")]
+            ConfigFormat::Xml => ("<!--\n", "-->\n"),
+            #[allow(unreachable_patterns)]
+            _ => ("", ""),
+        };
+        let comment = if prefix.is_empty() { "# " } else { "  " };
+        let mut header = prefix.to_string();
+        for (key, value) in self.entries() {
+            // Anything else could end the comment or break the document
+            if value.contains(|c: char| c.is_control()) || value.contains("--") {
+                return Err(ConfigFileError::InvalidMetadata(value));
+            }
+            header.push_str(&format!("{comment}{key}: {value}\n"));
+        }
+        header.push_str(suffix);
+        let mut out = header.into_bytes();
+        out.extend(data);
+        Ok(out)
+    }
+
+    /// The entries of the header, as written in comments
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = Vec::new();
+        if let Some(app) = &self.app {
+            entries.push(("app", app.clone()));
+        }
+        if let Some(schema_version) = self.schema_version {
+            entries.push(("schema-version", schema_version.to_string()));
+        }
+        if let Some(generated_at) = self.generated_at {
+            entries.push(("generated-at", generated_at.to_string()));
+        }
+        if let Some(crate_version) = &self.crate_version {
+            entries.push(("config-file", crate_version.clone()));
+        }
+        entries
+    }
+
+    /// Parse the leading comment block of a TOML, YAML or XML document
+    fn parse_comments(text: &str) -> Option<Self> {
+        let mut metadata = Self::default();
+        let mut found = false;
+        let mut in_xml_comment = false;
+        for line in text.lines() {
+            let line = line.trim();
+            let line = match line.strip_prefix('#') {
+                Some(line) => line,
+                None if line == "<!--" => {
+                    in_xml_comment = true;
+                    continue;
+                }
+                None if line.is_empty() || line.starts_with("<?xml") => continue,
+                None if in_xml_comment && line != "-->" => line,
+                None => break,
+            };
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "app" => metadata.app = Some(value.to_string()),
+                "schema-version" => metadata.schema_version = value.parse().ok(),
+                "generated-at" => metadata.generated_at = value.parse().ok(),
+                "config-file" => metadata.crate_version = Some(value.to_string()),
+                _ => continue,
+            }
+            found = true;
+        }
+        found.then_some(metadata)
+    }
+}
+
+/// Remove the [`METADATA_KEY`] entry from @data, a JSON document, so that it
+/// doesn't reach the configuration
+#[cfg(feature = "json")]
+pub(crate) fn strip(data: &[u8]) -> Result<Cow<'_, [u8]>, serde_json::Error> {
+    let key = format!("\"{METADATA_KEY}\"");
+    if !data
+        .windows(key.len())
+        .any(|window| window == key.as_bytes())
+    {
+        return Ok(Cow::Borrowed(data));
+    }
+    let mut document: serde_json::Value = serde_json::from_slice(data)?;
+    match document
+        .as_object_mut()
+        .map(|object| object.remove(METADATA_KEY))
+    {
+        Some(Some(_)) => Ok(Cow::Owned(serde_json::to_vec(&document)?)),
+        _ => Ok(Cow::Borrowed(data)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, ToConfigFile};
+
+    #[allow(unused)]
+    fn test_with_extension(extension: &str) {
+        let mut path = temp_dir().join("config-metadata");
+        path.set_extension(extension);
+        let metadata = Metadata::new("myapp", 3);
+        TestConfig::example()
+            .to_config_file_with_metadata(&path, &metadata)
+            .unwrap();
+        assert_eq!(Metadata::read(&path).unwrap(), Some(metadata));
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json() {
+        test_with_extension("json");
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_toml() {
+        test_with_extension("toml");
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn test_xml() {
+        test_with_extension("xml");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_yaml() {
+        test_with_extension("yaml");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_strict() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Strict {
+            port: u64,
+        }
+
+        let path = temp_dir().join("config-metadata-strict.json");
+        let config = crate::ConfigValue::from_config_file("testdata/config.json").unwrap();
+        config
+            .to_config_file_with_metadata(&path, &Metadata::new("myapp", 3))
+            .unwrap();
+        let options = crate::LoadOptions::new().deny_unknown_fields(true);
+        assert!(TestConfig::from_config_file_with_options(&path, &options).is_ok());
+        std::fs::write(&path, r#"{"port": 443, "$metadata": {"app": "myapp"}}"#).unwrap();
+        assert_eq!(
+            Strict::from_config_file(&path).unwrap(),
+            Strict { port: 443 }
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_invalid() {
+        let path = temp_dir().join("config-metadata-invalid.toml");
+        for app in ["my\napp = 1", "my--app"] {
+            let metadata = Metadata::new(app, 3);
+            assert!(matches!(
+                TestConfig::example().to_config_file_with_metadata(&path, &metadata),
+                Err(ConfigFileError::InvalidMetadata(_))
+            ));
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_missing() {
+        assert_eq!(Metadata::read("testdata/config.toml").unwrap(), None);
+    }
+}
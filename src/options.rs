@@ -0,0 +1,1410 @@
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    io::ErrorKind,
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "json")]
+use serde::ser;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "json")]
+use crate::value::ValueError;
+use crate::{
+    backup::Backup,
+    format::ConfigFormat,
+    limits::{Budget, Limits},
+    read_file,
+    retry::{retry, RetryPolicy},
+    symlink::{self, SymlinkPolicy},
+    value::{self, from_value, to_value, ConfigValue},
+    ConfigFileError, Warning,
+};
+
+/// Options controlling how a configuration file is loaded
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    #[cfg(feature = "json")]
+    pub(crate) jsonc: bool,
+    #[cfg(feature = "json")]
+    pub(crate) non_finite_strings: bool,
+    #[cfg(feature = "checksum")]
+    pub(crate) verify_checksum: bool,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) aliases: Vec<(String, String)>,
+    pub(crate) symlinks: SymlinkPolicy,
+    pub(crate) profile: Option<String>,
+    pub(crate) includes: bool,
+    pub(crate) defaults: Option<String>,
+    pub(crate) local_override: bool,
+    pub(crate) expand_path: bool,
+    pub(crate) allow_missing: bool,
+    pub(crate) limits: Option<Limits>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) deny_unknown_fields: bool,
+    /// When the helper thread reading files for [`LoadOptions::timeout`]
+    /// stops reading them
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl LoadOptions {
+    /// Create the default options, which parse files strictly
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `//` and `/* */` comments as well as trailing commas in JSON
+    /// files, as most editors do
+    #[cfg(feature = "json")]
+    pub fn jsonc(mut self, jsonc: bool) -> Self {
+        self.jsonc = jsonc;
+        self
+    }
+
+    /// Accept the strings `"NaN"`, `"Infinity"` and `"-Infinity"` for floats
+    /// in JSON files, as stored with [`NonFinitePolicy::String`]. Other
+    /// strings are still rejected where the configuration expects numbers
+    /// or booleans.
+    #[cfg(feature = "json")]
+    pub fn non_finite_strings(mut self, accept: bool) -> Self {
+        self.non_finite_strings = accept;
+        self
+    }
+
+    /// Check the file against the checksum stored next to it when storing it
+    /// with [`StoreOptions::checksum`], failing with
+    /// [`ConfigFileError::ChecksumMismatch`] if they don't match
+    #[cfg(feature = "checksum")]
+    pub fn verify_checksum(mut self, verify: bool) -> Self {
+        self.verify_checksum = verify;
+        self
+    }
+
+    /// Retry reading the file according to @policy when it fails with a
+    /// transient error
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Handle configuration files which are symbolic links according to
+    /// @policy. They are read through by default.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Accept @alias as a deprecated name for @key, raising a
+    /// [`Warning::DeprecatedKey`] when it is used.
+    ///
+    /// Both are dotted paths such as `server.max_conn`. When both are present,
+    /// @key wins. Since the alias is only known when loading, storing always
+    /// writes the canonical name.
+    ///
+    /// ```rust,no_run
+    /// use config_file::{FromConfigFile, LoadOptions};
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    ///
+    /// let options = LoadOptions::new().alias("max_conn", "max_connections");
+    /// let (config, warnings) =
+    ///     Config::from_config_file_with_warnings("/etc/myconfig.toml", &options).unwrap();
+    /// for warning in warnings {
+    ///     eprintln!("warning: {warning}");
+    /// }
+    /// ```
+    pub fn alias(mut self, alias: impl Into<String>, key: impl Into<String>) -> Self {
+        self.aliases.push((alias.into(), key.into()));
+        self
+    }
+
+    /// Replace the `include` key of the file, holding the path of a file or
+    /// an array of them, with the content of these files.
+    ///
+    /// Included files are merged in order, then the including file is merged
+    /// over them. Relative paths are relative to the directory holding the
+    /// including file, and included files may include other files, as long
+    /// as no file includes itself.
+    ///
+    /// ```toml
+    /// include = ["defaults.toml", "secrets.toml"]
+    /// port = 8080
+    /// ```
+    pub fn includes(mut self, includes: bool) -> Self {
+        self.includes = includes;
+        self
+    }
+
+    /// Merge the local override file named after the file, such as
+    /// `config.local.toml` for `config.toml`, over it when it exists.
+    ///
+    /// Local override files hold the settings of a single machine or
+    /// developer, and are meant to be left out of version control. They are
+    /// merged over the profile, if any.
+    pub fn local_override(mut self, local_override: bool) -> Self {
+        self.local_override = local_override;
+        self
+    }
+
+    /// Merge the file over @defaults, a document in the same format as the
+    /// file, usually embedded with [`include_str!`], so that the keys missing
+    /// from the file get their default value.
+    ///
+    /// ```rust,no_run
+    /// use config_file::{FromConfigFile, LoadOptions};
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    ///
+    /// let options = LoadOptions::new().defaults("port = 8080\nworkers = 4\n");
+    /// let config = Config::from_config_file_with_options("config.toml", &options).unwrap();
+    /// ```
+    pub fn defaults(mut self, defaults: impl Into<String>) -> Self {
+        self.defaults = Some(defaults.into());
+        self
+    }
+
+    /// Load the @profile variant of the configuration, such as `prod`.
+    ///
+    /// The `profile.<profile>` section of the file, if any, is merged over
+    /// the rest of the file, then the file named after it with the profile
+    /// inserted before its extension, such as `config.prod.toml` for
+    /// `config.toml`, is merged over the result if it exists. The `profile`
+    /// section of the file is left out of the configuration.
+    ///
+    /// ```rust,no_run
+    /// use config_file::{FromConfigFile, LoadOptions};
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    ///
+    /// let options = LoadOptions::new().profile("dev").profile_env("MYAPP_PROFILE");
+    /// let config = Config::from_config_file_with_options("config.toml", &options).unwrap();
+    /// ```
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Load the profile named by the environment variable @var, as
+    /// [`LoadOptions::profile`] does, when it is set and not empty
+    pub fn profile_env(self, var: impl AsRef<OsStr>) -> Self {
+        match std::env::var(var) {
+            Ok(profile) if !profile.is_empty() => self.profile(profile),
+            _ => self,
+        }
+    }
+
+    /// Expand `~`, `~user` and `${VAR}` in the path given when loading, as
+    /// [`paths::expand_path`](crate::paths::expand_path) does, such as when
+    /// it comes from a command line argument
+    pub fn expand_path(mut self, expand: bool) -> Self {
+        self.expand_path = expand;
+        self
+    }
+
+    /// Load a missing file as an empty document instead of failing, so that
+    /// the default values of the fields, or those given to
+    /// [`defaults`](Self::defaults), make up the configuration
+    pub fn allow_missing(mut self, allow: bool) -> Self {
+        self.allow_missing = allow;
+        self
+    }
+
+    /// Reject files exceeding @limits, such as files provided by untrusted
+    /// users, with [`ConfigFileError::LimitExceeded`].
+    ///
+    /// The size and the number of keys are limited for the whole
+    /// configuration, adding up the includes, profile, local override and
+    /// defaults merged into the file.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Reject the fields of the file which the configuration doesn't have, as
+    /// `#[serde(deny_unknown_fields)]` does, so that misspelled settings
+    /// aren't silently ignored
+    pub fn deny_unknown_fields(mut self, deny: bool) -> Self {
+        self.deny_unknown_fields = deny;
+        self
+    }
+
+    /// Give up loading the file after @timeout with
+    /// [`ConfigFileError::Timeout`], so that starting up doesn't hang
+    /// forever when it lives on an unreachable network filesystem.
+    ///
+    /// Everything touching the filesystem, from resolving the path to
+    /// reading the files merged into it, is done by a helper thread within
+    /// this single deadline. The thread is left behind when it hangs.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Load the configuration file located at @path according to these
+    /// options
+    pub(crate) fn load<C: DeserializeOwned>(
+        &self,
+        path: &Path,
+    ) -> Result<(C, Vec<Warning>), ConfigFileError> {
+        let (content, warnings) = match self.timeout {
+            Some(timeout) => self.read_all_within(path, Instant::now() + timeout)?,
+            None => self.read_all(path)?,
+        };
+        let config = match content {
+            Content::Data(format, data) => {
+                self.deserialize(format, &data, self.budget().as_ref())?
+            }
+            Content::Value(value) => self.convert(value)?,
+        };
+        Ok((config, warnings))
+    }
+
+    /// Read the configuration file located at @path and the files merged
+    /// into it on a helper thread, giving up at @deadline
+    fn read_all_within(
+        &self,
+        path: &Path,
+        deadline: Instant,
+    ) -> Result<(Content, Vec<Warning>), ConfigFileError> {
+        // Reading can't be interrupted, so a hung read is left behind in its
+        // thread, which stops reading files once the deadline has passed
+        let (sender, receiver) = mpsc::channel();
+        let options = Self {
+            deadline: Some(deadline),
+            ..self.clone()
+        };
+        let file = path.to_path_buf();
+        std::thread::spawn(move || sender.send(options.read_all(&file)));
+        match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(content) => content,
+            Err(RecvTimeoutError::Timeout) => Err(ConfigFileError::Timeout(path.to_path_buf())),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(std::io::Error::other("config file reader thread panicked").into())
+            }
+        }
+    }
+
+    /// Read the configuration file located at @path along with the files
+    /// merged into it, which is everything touching the filesystem
+    fn read_all(&self, path: &Path) -> Result<(Content, Vec<Warning>), ConfigFileError> {
+        let path = &*expand(path, self.expand_path)?;
+        let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        let target = symlink::resolve(path, self.symlinks)?;
+        let budget = self.budget();
+        let budget = budget.as_ref();
+        let data = match self.read(&target, budget) {
+            Err(ConfigFileError::FileAccess(err))
+                if self.allow_missing && err.kind() == ErrorKind::NotFound =>
+            {
+                None
+            }
+            data => Some(data?),
+        };
+        #[cfg(feature = "checksum")]
+        if let (true, Some(data)) = (self.verify_checksum, &data) {
+            crate::checksum::verify(path, data)?;
+        }
+        let mut warnings = Vec::new();
+        let mut value = match data {
+            Some(data)
+                if self.aliases.is_empty()
+                    && self.profile.is_none()
+                    && !self.includes
+                    && self.defaults.is_none()
+                    && !self.local_override =>
+            {
+                return Ok((Content::Data(format, data), warnings));
+            }
+            Some(data) => self.deserialize(format, &data, budget)?,
+            None => ConfigValue::Map(Default::default()),
+        };
+        if self.includes {
+            crate::include::splice(&mut value, path, self, budget)?;
+        }
+        if let Some(profile) = &self.profile {
+            self.apply_profile(path, format, profile, &mut value, budget)?;
+        }
+        if self.local_override {
+            self.merge_variant(path, format, "local", &mut value, budget)?;
+        }
+        for (alias, key) in &self.aliases {
+            crate::alias::rename(&mut value, alias, key, &mut warnings);
+        }
+        if let Some(defaults) = &self.defaults {
+            if let Some(budget) = budget {
+                budget.spend(defaults.len())?;
+            }
+            let mut defaults: ConfigValue =
+                self.deserialize(format, defaults.as_bytes(), budget)?;
+            defaults.merge(value);
+            value = defaults;
+        }
+        Ok((Content::Value(value), warnings))
+    }
+
+    /// Merge the @profile variant of the configuration file located at @path
+    /// over its @value
+    fn apply_profile(
+        &self,
+        path: &Path,
+        format: ConfigFormat,
+        profile: &str,
+        value: &mut ConfigValue,
+        budget: Option<&Budget>,
+    ) -> Result<(), ConfigFileError> {
+        let section = value
+            .as_map_mut()
+            .and_then(|map| value::remove(map, "profile"))
+            .and_then(|mut profiles| value::remove(profiles.as_map_mut()?, profile));
+        if let Some(section) = section {
+            value.merge(section);
+        }
+        self.merge_variant(path, format, profile, value, budget)
+    }
+
+    /// Merge the file named after the one located at @path with @variant
+    /// inserted before its extension, such as `config.local.toml` for
+    /// `config.toml`, over @value if it exists
+    fn merge_variant(
+        &self,
+        path: &Path,
+        format: ConfigFormat,
+        variant: &str,
+        value: &mut ConfigValue,
+        budget: Option<&Budget>,
+    ) -> Result<(), ConfigFileError> {
+        let mut name = path.file_stem().unwrap_or_default().to_os_string();
+        name.push(format!(".{variant}"));
+        if let Some(extension) = path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        let variant = path.with_file_name(name);
+        let target = symlink::resolve(&variant, self.symlinks)?;
+        match self.read(&target, budget) {
+            Ok(data) => value.merge(self.deserialize(format, &data, budget)?),
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// What the configuration loaded according to these options may spend,
+    /// if they limit it, reject unknown fields or accept non-finite strings
+    fn budget(&self) -> Option<Budget> {
+        #[cfg(feature = "json")]
+        let non_finite = self.non_finite_strings;
+        #[cfg(not(feature = "json"))]
+        let non_finite = false;
+        match (&self.limits, self.deny_unknown_fields, non_finite) {
+            (None, false, false) => None,
+            (limits, strict, non_finite) => Some(
+                Budget::new(limits.as_ref().unwrap_or(&Limits::UNLIMITED))
+                    .strict(strict)
+                    .non_finite_strings(non_finite),
+            ),
+        }
+    }
+
+    /// Read the file located at @path within @budget, unless the deadline
+    /// has passed, retrying according to these options
+    pub(crate) fn read(
+        &self,
+        path: &Path,
+        budget: Option<&Budget>,
+    ) -> Result<Vec<u8>, ConfigFileError> {
+        retry(self.retry.as_ref(), || match (self.deadline, budget) {
+            (Some(deadline), _) if Instant::now() >= deadline => {
+                Err(ConfigFileError::Timeout(path.to_path_buf()))
+            }
+            (_, Some(budget)) => budget.read(path),
+            (_, None) => read_file(path),
+        })
+    }
+
+    /// Parse @data as @format within @budget according to these options
+    pub(crate) fn deserialize<C: DeserializeOwned>(
+        &self,
+        format: ConfigFormat,
+        data: &[u8],
+        budget: Option<&Budget>,
+    ) -> Result<C, ConfigFileError> {
+        #[cfg(feature = "json")]
+        if format == ConfigFormat::Json && self.jsonc {
+            return Self::parse(format, &crate::jsonc::strip(data), budget);
+        }
+        Self::parse(format, data, budget)
+    }
+
+    /// Parse @data as @format within @budget
+    fn parse<C: DeserializeOwned>(
+        format: ConfigFormat,
+        data: &[u8],
+        budget: Option<&Budget>,
+    ) -> Result<C, ConfigFileError> {
+        match budget {
+            Some(budget) => format.deserialize_limited(data, budget),
+            None => format.deserialize(data),
+        }
+    }
+
+    /// Convert @value into the configuration, as strictly as these options
+    /// require
+    fn convert<C: DeserializeOwned>(&self, value: ConfigValue) -> Result<C, ConfigFileError> {
+        match self.deny_unknown_fields {
+            true => Ok(Budget::new(&Limits::UNLIMITED)
+                .strict(true)
+                .deserialize(value)?),
+            false => Ok(from_value(value)?),
+        }
+    }
+}
+
+/// The content of a configuration file, as read by [`LoadOptions::read_all`]
+enum Content {
+    /// The file alone, still to be parsed as the given format
+    Data(ConfigFormat, Vec<u8>),
+    /// The file merged with the other files making up the configuration
+    Value(ConfigValue),
+}
+
+/// Options controlling how a configuration file is stored
+#[derive(Debug, Clone, Default)]
+pub struct StoreOptions {
+    pub(crate) defaults: Option<ConfigValue>,
+    pub(crate) fsync: bool,
+    pub(crate) backup: Option<Backup>,
+    #[cfg(feature = "checksum")]
+    pub(crate) checksum: bool,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) symlinks: SymlinkPolicy,
+    #[cfg(unix)]
+    pub(crate) mode: Option<u32>,
+    #[cfg(windows)]
+    pub(crate) hidden: Option<bool>,
+    #[cfg(windows)]
+    pub(crate) read_only: Option<bool>,
+    pub(crate) expand_path: bool,
+    #[cfg(feature = "json")]
+    pub(crate) non_finite: NonFinitePolicy,
+    pub(crate) keep_dirs: bool,
+    pub(crate) in_place: bool,
+    pub(crate) overwrite: OverwritePolicy,
+    /// Whether files are written through [`crate::uring`]
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub(crate) uring: bool,
+    /// Set once the asynchronous store writing with these options is
+    /// cancelled, so that it stops before replacing the file
+    #[cfg(feature = "async")]
+    pub(crate) cancelled: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+}
+
+/// What to do when storing a configuration file which already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Replace the file
+    #[default]
+    Overwrite,
+    /// Fail with an [`std::io::ErrorKind::AlreadyExists`] error, leaving the
+    /// file untouched
+    ErrorIfExists,
+    /// Keep the previous content of the file next to it, in a file named
+    /// after it followed by `.bak` unless another backup was requested, then
+    /// replace it
+    BackupThenWrite,
+    /// Keep the values set by the file, only adding those it lacks, such as
+    /// new default settings.
+    ///
+    /// Reading, merging and storing the file isn't atomic: changes made to it
+    /// by another process in the meantime are lost.
+    MergeInto,
+}
+
+/// What to store in JSON files in place of non-finite floats, NaN and the
+/// infinities, which JSON can't represent
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFinitePolicy {
+    /// Store `null`, losing the value
+    #[default]
+    Null,
+    /// Fail with [`ConfigFileError::Value`]
+    Error,
+    /// Store the strings `"NaN"`, `"Infinity"` and `"-Infinity"`, loaded
+    /// back with [`LoadOptions::non_finite_strings`]
+    String,
+}
+
+impl StoreOptions {
+    /// Create the default options, which write every field
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip the fields equal to the ones of @defaults, producing a minimal
+    /// file only holding what was overridden
+    pub fn skip_defaults<D: Serialize + ?Sized>(
+        mut self,
+        defaults: &D,
+    ) -> Result<Self, ConfigFileError> {
+        self.defaults = Some(to_value(defaults)?);
+        Ok(self)
+    }
+
+    /// Write every field, even those equal to their default value
+    pub fn write_defaults(mut self) -> Self {
+        self.defaults = None;
+        self
+    }
+
+    /// Flush the file and its parent directory to disk after writing it, so
+    /// that the new content survives a power loss.
+    ///
+    /// This is disabled by default since it makes storing much slower.
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Keep the previous content of the file next to it, in a file named
+    /// after it followed by @suffix, such as `config.toml.bak` for `.bak`
+    pub fn backup(mut self, suffix: impl Into<OsString>) -> Self {
+        self.backup = Some(Backup::Suffix(suffix.into()));
+        self
+    }
+
+    /// Keep up to @count previous contents of the file next to it, in files
+    /// named after it followed by a number: `config.toml.1` is the most
+    /// recent one, `config.toml.2` the one before and so on
+    pub fn numbered_backups(mut self, count: usize) -> Self {
+        self.backup = Some(Backup::Numbered(count));
+        self
+    }
+
+    /// Keep up to @count previous contents of the file next to it, in files
+    /// named after it followed by the time they were replaced at, in
+    /// milliseconds since the Unix epoch, such as `config.toml.1700000000000`.
+    /// The oldest ones are removed first.
+    pub fn timestamped_backups(mut self, count: usize) -> Self {
+        self.backup = Some(Backup::Timestamped(count));
+        self
+    }
+
+    /// Store the SHA-256 checksum of the file next to it, in a file named
+    /// after it followed by `.sha256`, so that it can be checked when
+    /// loading it with [`LoadOptions::verify_checksum`]
+    #[cfg(feature = "checksum")]
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Give the file the Unix permissions @mode, such as `0o600` for files
+    /// holding secrets, instead of keeping those of the file it replaces.
+    ///
+    /// The file is created with these permissions, so it is never readable
+    /// by anyone else, even briefly.
+    #[cfg(unix)]
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set or clear the Windows hidden attribute of the file, as is customary
+    /// for dotfiles, instead of keeping the one of the file it replaces
+    #[cfg(windows)]
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = Some(hidden);
+        self
+    }
+
+    /// Set or clear the Windows read-only attribute of the file, instead of
+    /// keeping the one of the file it replaces.
+    ///
+    /// Read-only files are still replaced when storing them again.
+    #[cfg(windows)]
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = Some(read_only);
+        self
+    }
+
+    /// Handle configuration files which are symbolic links according to
+    /// @policy. By default, the file they point to is replaced and the links
+    /// are left in place.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Retry writing the file according to @policy when it fails with a
+    /// transient error
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Expand `~`, `~user` and `${VAR}` in the path given when storing, as
+    /// [`LoadOptions::expand_path`] does
+    pub fn expand_path(mut self, expand: bool) -> Self {
+        self.expand_path = expand;
+        self
+    }
+
+    /// Store non-finite floats in JSON files according to @policy
+    #[cfg(feature = "json")]
+    pub fn non_finite(mut self, policy: NonFinitePolicy) -> Self {
+        self.non_finite = policy;
+        self
+    }
+
+    /// Create the missing parent directories of the file, which is the
+    /// default
+    pub fn create_dirs(mut self, create: bool) -> Self {
+        self.keep_dirs = !create;
+        self
+    }
+
+    /// Write the file through a temporary file renamed over it, which is the
+    /// default, so that it is never left half written.
+    ///
+    /// Writing it in place instead keeps the file itself, along with its
+    /// other hard links and its permissions, for files which are bind
+    /// mounted or watched by inode.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.in_place = !atomic;
+        self
+    }
+
+    /// Handle a file which already exists according to @policy, which
+    /// replaces it by default
+    pub fn overwrite(mut self, policy: OverwritePolicy) -> Self {
+        self.overwrite = policy;
+        self
+    }
+
+    /// The backup to take before replacing the file, if any
+    pub(crate) fn effective_backup(&self) -> Option<Cow<'_, Backup>> {
+        match (&self.backup, self.overwrite) {
+            (Some(backup), _) => Some(Cow::Borrowed(backup)),
+            (None, OverwritePolicy::BackupThenWrite) => {
+                Some(Cow::Owned(Backup::Suffix(".bak".into())))
+            }
+            (None, _) => None,
+        }
+    }
+
+    /// Apply the overwrite policy to @data, about to be stored as @format
+    /// into the file located at @path
+    pub(crate) fn overwritten(
+        &self,
+        path: &Path,
+        format: ConfigFormat,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, ConfigFileError> {
+        match self.overwrite {
+            // Existing files are refused when writing, so that one created
+            // in the meantime isn't replaced either
+            OverwritePolicy::Overwrite
+            | OverwritePolicy::ErrorIfExists
+            | OverwritePolicy::BackupThenWrite => Ok(data),
+            OverwritePolicy::MergeInto => {
+                let existing = match retry(self.retry.as_ref(), || read_file(path)) {
+                    Err(ConfigFileError::FileAccess(err)) if err.kind() == ErrorKind::NotFound => {
+                        return Ok(data)
+                    }
+                    existing => existing?,
+                };
+                let mut merged: ConfigValue = format.deserialize(&data)?;
+                merged.merge(format.deserialize(&existing)?);
+                format.serialize(&merged)
+            }
+        }
+    }
+
+    /// The path to store to when given @path
+    pub(crate) fn path<'a>(&self, path: &'a Path) -> Result<Cow<'a, Path>, ConfigFileError> {
+        expand(path, self.expand_path)
+    }
+
+    /// Render @config as @format according to these options
+    pub(crate) fn serialize<C: Serialize + ?Sized>(
+        &self,
+        format: ConfigFormat,
+        config: &C,
+    ) -> Result<Vec<u8>, ConfigFileError> {
+        #[cfg(feature = "json")]
+        let non_finite = format == ConfigFormat::Json && self.non_finite != NonFinitePolicy::Null;
+        #[cfg(not(feature = "json"))]
+        let non_finite = false;
+        if self.defaults.is_none() && !non_finite {
+            return format.serialize(config);
+        }
+        let mut value = to_value(config)?;
+        if let Some(defaults) = &self.defaults {
+            value.strip_defaults(defaults);
+        }
+        #[cfg(feature = "json")]
+        if non_finite {
+            replace_non_finite(&mut value, self.non_finite)?;
+        }
+        format.serialize(&value)
+    }
+}
+
+/// Replace the non-finite floats held by @value according to @policy
+#[cfg(feature = "json")]
+fn replace_non_finite(value: &mut ConfigValue, policy: NonFinitePolicy) -> Result<(), ValueError> {
+    match value {
+        ConfigValue::Float(float) if !float.is_finite() => match policy {
+            NonFinitePolicy::Null => {}
+            NonFinitePolicy::Error => {
+                return Err(ser::Error::custom(format!(
+                    "{float} can't be stored as JSON"
+                )))
+            }
+            NonFinitePolicy::String => {
+                *value = ConfigValue::String(match float.is_nan() {
+                    true => "NaN".to_string(),
+                    false if *float > 0.0 => "Infinity".to_string(),
+                    false => "-Infinity".to_string(),
+                })
+            }
+        },
+        ConfigValue::Array(values) => {
+            for value in values {
+                replace_non_finite(value, policy)?;
+            }
+        }
+        ConfigValue::Map(map) => {
+            for value in map.values_mut() {
+                replace_non_finite(value, policy)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expand @path if @enabled
+fn expand(path: &Path, enabled: bool) -> Result<Cow<'_, Path>, ConfigFileError> {
+    match enabled {
+        true => Ok(Cow::Owned(crate::paths::expand_path(path)?)),
+        false => Ok(Cow::Borrowed(path)),
+    }
+}
+
+/// Render as @format only the fields of @effective which differ from
+/// @defaults.
+///
+/// This turns the state of a running application into a minimal override
+/// file, which keeps following the defaults for everything it doesn't set.
+///
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use config_file::{export_overrides, ConfigFormat};
+/// # #[derive(serde::Serialize)]
+/// # struct Config { host: String, port: u16 }
+///
+/// let defaults = Config { host: "localhost".into(), port: 80 };
+/// let effective = Config { host: "localhost".into(), port: 8080 };
+/// let overrides = export_overrides(&effective, &defaults, ConfigFormat::Toml).unwrap();
+/// assert_eq!(String::from_utf8(overrides).unwrap().trim(), "port = 8080");
+/// # }
+/// ```
+pub fn export_overrides<C: Serialize + ?Sized, D: Serialize + ?Sized>(
+    effective: &C,
+    defaults: &D,
+    format: ConfigFormat,
+) -> Result<Vec<u8>, ConfigFileError> {
+    StoreOptions::new()
+        .skip_defaults(defaults)?
+        .serialize(format, effective)
+}
+
+#[cfg(test)]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, ToConfigFile};
+
+    #[allow(unused)]
+    fn test_skip_defaults_with_extension(extension: &str) {
+        let mut path = temp_dir().join("config-skip-defaults");
+        path.set_extension(extension);
+        let mut config = TestConfig::example();
+        config.port = 8080;
+        let options = StoreOptions::new()
+            .skip_defaults(&TestConfig::example())
+            .unwrap();
+        config.to_config_file_with_options(&path, &options).unwrap();
+        let stored = ConfigValue::from_config_file(&path).unwrap();
+        let stored = stored.as_map().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(
+            crate::value::from_value::<u64>(stored["port"].clone()).unwrap(),
+            8080
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_jsonc() {
+        let path = temp_dir().join("config-jsonc.json");
+        std::fs::write(
+            &path,
+            r#"{
+                // Served over HTTPS
+                "host": "example.com",
+                "port": 443,
+                "tags": ["example", "test",],
+                "inner": { "answer": 42 },
+            }"#,
+        )
+        .unwrap();
+        assert!(TestConfig::from_config_file(&path).is_err());
+        let options = LoadOptions::new().jsonc(true);
+        assert_eq!(
+            TestConfig::from_config_file_with_options(&path, &options).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_non_finite() {
+        let path = temp_dir().join("config-non-finite.json");
+        let values = vec![1.5, f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+        (&values).to_config_file(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .replace(char::is_whitespace, ""),
+            "[1.5,null,null,null]"
+        );
+
+        let options = StoreOptions::new().non_finite(NonFinitePolicy::Error);
+        assert!((&values)
+            .to_config_file_with_options(&path, &options)
+            .is_err());
+
+        let options = StoreOptions::new().non_finite(NonFinitePolicy::String);
+        values.to_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .replace(char::is_whitespace, ""),
+            r#"[1.5,"NaN","Infinity","-Infinity"]"#
+        );
+        assert!(Vec::<f64>::from_config_file(&path).is_err());
+        let options = LoadOptions::new().non_finite_strings(true);
+        let loaded = Vec::<f64>::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(loaded[0], 1.5);
+        assert!(loaded[1].is_nan());
+        assert_eq!(loaded[2..], [f64::INFINITY, f64::NEG_INFINITY]);
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Settings {
+            ratio: Option<f32>,
+            port: u16,
+            enabled: bool,
+            tags: Vec<String>,
+        }
+        let valid = r#"{"ratio": "-Infinity", "port": 8080, "enabled": true, "tags": ["one"]}"#;
+        std::fs::write(&path, valid).unwrap();
+        let settings = Settings::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(settings.ratio, Some(f32::NEG_INFINITY));
+        assert_eq!((settings.port, settings.enabled), (8080, true));
+        assert_eq!(settings.tags, ["one"]);
+        // Only non-finite floats are accepted as strings
+        for coerced in [
+            valid.replace("8080", r#""8080""#),
+            valid.replace("true", r#""true""#),
+            valid.replace(r#"["one"]"#, r#""one""#),
+            valid.replace("-Infinity", "-infinity"),
+        ] {
+            std::fs::write(&path, coerced).unwrap();
+            assert!(Settings::from_config_file_with_options(&path, &options).is_err());
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_alias() {
+        let path = temp_dir().join("config-alias.toml");
+        std::fs::write(
+            &path,
+            "hostname = \"example.com\"\nport = 443\ntags = [\"example\", \"test\"]\n\
+             [inner]\nthe_answer = 42\n",
+        )
+        .unwrap();
+        let options = LoadOptions::new()
+            .alias("hostname", "host")
+            .alias("inner.the_answer", "inner.answer");
+        let (config, warnings) =
+            TestConfig::from_config_file_with_warnings(&path, &options).unwrap();
+        assert_eq!(config, TestConfig::example());
+        assert_eq!(
+            warnings[0],
+            Warning::DeprecatedKey {
+                alias: "hostname".into(),
+                key: "host".into(),
+            }
+        );
+        assert_eq!(warnings.len(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_profile() {
+        let dir = temp_dir().join("config-profile");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            "host = \"example.com\"\nport = 443\ntags = [\"example\", \"test\"]\n\
+             [inner]\nanswer = 42\n[profile.prod]\nport = 8443\n[profile.dev]\nport = 8080\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("config.prod.toml"), "host = \"prod.example.com\"").unwrap();
+
+        let config = TestConfig::from_config_file(&path).unwrap();
+        assert_eq!(config, TestConfig::example());
+        let options = LoadOptions::new().profile("prod");
+        let config = TestConfig::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(config.host, "prod.example.com");
+        assert_eq!(config.port, 8443);
+        let options = LoadOptions::new()
+            .profile("dev")
+            .profile_env("CONFIG_FILE_TEST_PROFILE_UNSET");
+        let config = TestConfig::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(config.host, "example.com");
+        assert_eq!(config.port, 8080);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_defaults() {
+        let path = temp_dir().join("config-defaults.toml");
+        std::fs::write(&path, "hostname = \"example.com\"\n[inner]\nanswer = 42\n").unwrap();
+        assert!(TestConfig::from_config_file(&path).is_err());
+        let options = LoadOptions::new()
+            .alias("hostname", "host")
+            .defaults("host = \"localhost\"\nport = 443\ntags = [\"example\", \"test\"]\n");
+        assert_eq!(
+            TestConfig::from_config_file_with_options(&path, &options).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_local_override() {
+        let dir = temp_dir().join("config-local-override");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let options = LoadOptions::new().local_override(true);
+        assert_eq!(
+            TestConfig::from_config_file_with_options(&path, &options).unwrap(),
+            TestConfig::example()
+        );
+
+        std::fs::write(dir.join("config.local.toml"), "port = 8080").unwrap();
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 443);
+        let config = TestConfig::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.host, "example.com");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_expand_path() {
+        let dir = temp_dir().join("config-expand-path");
+        let _ = std::fs::remove_dir_all(&dir);
+        let var = |name: &str| (name == "CONFIG_DIR").then(|| dir.clone().into_os_string());
+        let path = Path::new("${CONFIG_DIR}/config.toml");
+        let expanded = crate::paths::expand(&var, &|_| None, path).unwrap();
+        assert_eq!(expanded, dir.join("config.toml"));
+        TestConfig::example().to_config_file(&expanded).unwrap();
+        let config: TestConfig = TestConfig::from_config_file(&expanded).unwrap();
+        assert_eq!(config, TestConfig::example());
+
+        // Variables are only expanded when asked for
+        let unset = "${CONFIG_FILE_TEST_UNSET}/config.toml";
+        let options = StoreOptions::new().expand_path(true);
+        assert!(matches!(
+            TestConfig::example().to_config_file_with_options(unset, &options),
+            Err(ConfigFileError::PathExpansion(_))
+        ));
+        let options = LoadOptions::new().expand_path(true);
+        assert!(matches!(
+            TestConfig::from_config_file_with_options(unset, &options),
+            Err(ConfigFileError::PathExpansion(_))
+        ));
+        assert!(matches!(
+            TestConfig::from_config_file_with_options(unset, &LoadOptions::new()),
+            Err(ConfigFileError::FileAccess(_))
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_fsync() {
+        let path = temp_dir().join("config-fsync.toml");
+        let options = StoreOptions::new().fsync(true);
+        TestConfig::example()
+            .to_config_file_with_options(&path, &options)
+            .unwrap();
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "toml"))]
+    fn test_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_dir().join("config-mode.toml");
+        for mode in [0o600, 0o640] {
+            let options = StoreOptions::new().mode(mode);
+            TestConfig::example()
+                .to_config_file_with_options(&path, &options)
+                .unwrap();
+            let permissions = std::fs::metadata(&path).unwrap().permissions();
+            assert_eq!(permissions.mode() & 0o777, mode);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "toml"))]
+    fn test_attributes() {
+        use std::os::windows::fs::MetadataExt;
+
+        let path = temp_dir().join("config-attributes.toml");
+        let options = StoreOptions::new().hidden(true).read_only(true);
+        for _ in 0..2 {
+            TestConfig::example()
+                .to_config_file_with_options(&path, &options)
+                .unwrap();
+            let metadata = std::fs::metadata(&path).unwrap();
+            assert!(metadata.permissions().readonly());
+            assert_ne!(metadata.file_attributes() & 0x2, 0);
+        }
+        // The attributes of the replaced file are kept by default
+        TestConfig::example().to_config_file(&path).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().permissions().readonly());
+
+        let options = StoreOptions::new().hidden(false).read_only(false);
+        TestConfig::example()
+            .to_config_file_with_options(&path, &options)
+            .unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert!(!metadata.permissions().readonly());
+        assert_eq!(metadata.file_attributes() & 0x2, 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_allow_missing() {
+        let path = temp_dir().join("config-allow-missing.toml");
+        let _ = std::fs::remove_file(&path);
+        let options = LoadOptions::new().allow_missing(true);
+        let config: ConfigValue =
+            ConfigValue::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(config, ConfigValue::Map(Default::default()));
+        assert!(TestConfig::from_config_file_with_options(&path, &options).is_err());
+        let options = options.defaults(
+            "host = \"example.com\"\nport = 443\ntags = [\"example\", \"test\"]\n[inner]\nanswer = 42\n",
+        );
+        assert_eq!(
+            TestConfig::from_config_file_with_options(&path, &options).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::write(&path, "port = 8080").unwrap();
+        let config = TestConfig::from_config_file_with_options(&path, &options).unwrap();
+        assert_eq!(config.port, 8080);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_create_dirs() {
+        let dir = temp_dir().join("config-create-dirs");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("nested/config.toml");
+        for atomic in [true, false] {
+            let options = StoreOptions::new().create_dirs(false).atomic(atomic);
+            assert!(TestConfig::example()
+                .to_config_file_with_options(&path, &options)
+                .is_err());
+            assert!(!dir.exists());
+        }
+        let options = StoreOptions::new().atomic(false);
+        TestConfig::example()
+            .to_config_file_with_options(&path, &options)
+            .unwrap();
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "toml"))]
+    fn test_atomic() {
+        use std::os::unix::fs::MetadataExt;
+
+        let path = temp_dir().join("config-atomic.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let inode = std::fs::metadata(&path).unwrap().ino();
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed
+            .to_config_file_with_options(&path, &StoreOptions::new().atomic(false))
+            .unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().ino(), inode);
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 8080);
+        TestConfig::example().to_config_file(&path).unwrap();
+        assert_ne!(std::fs::metadata(&path).unwrap().ino(), inode);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_atomic_backups() {
+        let dir = temp_dir().join("config-atomic-backups");
+        let path = dir.join("config.toml");
+        let options = StoreOptions::new().atomic(false);
+        for options in [
+            options.clone().backup(".bak"),
+            options.clone().numbered_backups(2),
+            options.timestamped_backups(2),
+        ] {
+            let _ = std::fs::remove_dir_all(&dir);
+            TestConfig::example()
+                .to_config_file_with_options(&path, &options)
+                .unwrap();
+            let previous = std::fs::read(&path).unwrap();
+            let mut changed = TestConfig::example();
+            changed.port = 8080;
+            changed
+                .to_config_file_with_options(&path, &options)
+                .unwrap();
+            assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 8080);
+            let backups = std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|backup| *backup != path)
+                .collect::<Vec<_>>();
+            assert_eq!(backups.len(), 1);
+            assert_eq!(std::fs::read(&backups[0]).unwrap(), previous);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_deny_unknown_fields() {
+        let strict = LoadOptions::new().deny_unknown_fields(true);
+        for format in ConfigFormat::enabled() {
+            let path = temp_dir().join(format!("config-deny-unknown.{format}"));
+            TestConfig::example().to_config_file(&path).unwrap();
+            assert_eq!(
+                TestConfig::from_config_file_with_options(&path, &strict).unwrap(),
+                TestConfig::example()
+            );
+            let mut value = to_value(&TestConfig::example()).unwrap();
+            value.set("inner.answr", ConfigValue::Integer(42)).unwrap();
+            value.to_config_file(&path).unwrap();
+            assert!(TestConfig::from_config_file(&path).is_ok());
+            let err = TestConfig::from_config_file_with_options(&path, &strict).unwrap_err();
+            assert!(
+                format!("{err:?}").contains("unknown field `answr`"),
+                "{err:?}"
+            );
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let path = temp_dir().join("config-deny-unknown-merged.toml");
+        std::fs::write(&path, "prot = 8080").unwrap();
+        let merged = strict
+            .defaults("host = \"example.com\"\nport = 443\ntags = []\n[inner]\nanswer = 42\n");
+        let err = TestConfig::from_config_file_with_options(&path, &merged).unwrap_err();
+        assert!(
+            format!("{err:?}").contains("unknown field `prot`"),
+            "{err:?}"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_overwrite() {
+        let dir = temp_dir().join("config-overwrite");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        let options = |policy| StoreOptions::new().overwrite(policy);
+
+        let exclusive = options(OverwritePolicy::ErrorIfExists);
+        TestConfig::example()
+            .to_config_file_with_options(&path, &exclusive)
+            .unwrap();
+        std::fs::write(&path, "port = 8080").unwrap();
+        assert!(matches!(
+            TestConfig::example().to_config_file_with_options(&path, &exclusive),
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == ErrorKind::AlreadyExists
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "port = 8080");
+        assert!(matches!(
+            TestConfig::example().to_config_file_with_options(&path, &exclusive.clone().atomic(false)),
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == ErrorKind::AlreadyExists
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "port = 8080");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        TestConfig::example()
+            .to_config_file_with_options(&path, &options(OverwritePolicy::MergeInto))
+            .unwrap();
+        let config = TestConfig::from_config_file(&path).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.host, "example.com");
+
+        let previous = std::fs::read(&path).unwrap();
+        TestConfig::example()
+            .to_config_file_with_options(&path, &options(OverwritePolicy::BackupThenWrite))
+            .unwrap();
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        assert_eq!(
+            std::fs::read(dir.join("config.toml.bak")).unwrap(),
+            previous
+        );
+
+        std::fs::remove_file(dir.join("config.toml.bak")).unwrap();
+        let options =
+            options(OverwritePolicy::BackupThenWrite).overwrite(OverwritePolicy::Overwrite);
+        TestConfig::example()
+            .to_config_file_with_options(&path, &options)
+            .unwrap();
+        assert!(!dir.join("config.toml.bak").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "toml"))]
+    fn test_timeout() {
+        let path = temp_dir().join("config-timeout.toml");
+        let _ = std::fs::remove_file(&path);
+        TestConfig::example().to_config_file(&path).unwrap();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            TestConfig::from_config_file_with_timeout(&path, timeout).unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        // Opening a FIFO blocks until something opens it for writing
+        let fifo = std::process::Command::new("mkfifo").arg(&path).status();
+        if !fifo.is_ok_and(|status| status.success()) {
+            return;
+        }
+        let timeout = Duration::from_millis(50);
+        assert!(matches!(
+            TestConfig::from_config_file_with_timeout(&path, timeout),
+            Err(ConfigFileError::Timeout(timed_out)) if timed_out == path
+        ));
+        drop(std::fs::OpenOptions::new().write(true).open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        // The deadline covers the files merged into the configuration too
+        let local = temp_dir().join("config-timeout.local.toml");
+        let _ = std::fs::remove_file(&local);
+        TestConfig::example().to_config_file(&path).unwrap();
+        std::process::Command::new("mkfifo")
+            .arg(&local)
+            .status()
+            .unwrap();
+        let options = LoadOptions::new().local_override(true).timeout(timeout);
+        assert!(matches!(
+            TestConfig::from_config_file_with_options(&path, &options),
+            Err(ConfigFileError::Timeout(_))
+        ));
+        drop(
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(&local)
+                .unwrap(),
+        );
+        std::fs::remove_file(local).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_backup() {
+        let dir = temp_dir().join("config-backup");
+        let path = dir.join("config.toml");
+        let backup = dir.join("config.toml.bak");
+        let _ = std::fs::remove_dir_all(&dir);
+        let options = StoreOptions::new().backup(".bak");
+        TestConfig::example()
+            .to_config_file_with_options(&path, &options)
+            .unwrap();
+        assert!(!backup.exists());
+
+        let previous = std::fs::read(&path).unwrap();
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed
+            .to_config_file_with_options(&path, &options)
+            .unwrap();
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 8080);
+        assert_eq!(std::fs::read(backup).unwrap(), previous);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_skip_defaults_json() {
+        test_skip_defaults_with_extension("json");
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_skip_defaults_toml() {
+        test_skip_defaults_with_extension("toml");
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn test_skip_defaults_xml() {
+        test_skip_defaults_with_extension("xml");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_skip_defaults_yaml() {
+        test_skip_defaults_with_extension("yaml");
+    }
+}
@@ -0,0 +1,693 @@
+//! Standard locations of configuration files.
+//!
+//! On Linux and other Unix systems, configuration files belong to the
+//! directories of the
+//! [XDG Base Directory Specification](https://specifications.freedesktop.org/basedir-spec/latest/):
+//! `$XDG_CONFIG_HOME`, defaulting to `~/.config`, for the user, and
+//! `$XDG_CONFIG_DIRS`, defaulting to `/etc/xdg`, for the system.
+//!
+//! On Windows, they belong to `%APPDATA%`, which roams with the user
+//! between machines of a domain, or `%LOCALAPPDATA%`, which doesn't, and on
+//! macOS to `~/Library/Application Support`. [`config_dir`] and
+//! [`local_config_dir`] pick the right one for the current platform.
+//!
+//! With the directories feature, [`ConfigLocation`] also follows the naming
+//! conventions of each platform.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use serde::de::DeserializeOwned;
+#[cfg(feature = "directories")]
+use serde::Serialize;
+
+#[cfg(feature = "directories")]
+use crate::ToConfigFile;
+use crate::{ConfigFileError, FromConfigFile};
+
+/// The configuration directory of an application on the current platform,
+/// such as `~/.config/myapp` on Linux, `~/Library/Application
+/// Support/com.Acme.MyApp` on macOS or `%APPDATA%\Acme\MyApp\config` on
+/// Windows
+///
+/// ```rust,no_run
+/// use config_file::paths::ConfigLocation;
+/// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let location = ConfigLocation::for_app("com", "Acme", "MyApp").unwrap();
+/// let config: Config = location.load_or_default("config.toml").unwrap();
+/// location.store("config.toml", &config).unwrap();
+/// ```
+#[cfg(feature = "directories")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigLocation {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "directories")]
+impl ConfigLocation {
+    /// The configuration directory of the application @application, made by
+    /// @organization whose reverse domain name starts with @qualifier, such
+    /// as `com`. `None` when the home directory of the user is unknown.
+    pub fn for_app(qualifier: &str, organization: &str, application: &str) -> Option<Self> {
+        let dirs = directories::ProjectDirs::from(qualifier, organization, application)?;
+        Some(Self {
+            dir: dirs.config_dir().to_path_buf(),
+        })
+    }
+
+    /// The configuration directory itself
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The location of the configuration file @file
+    pub fn path(&self, file: impl AsRef<Path>) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    /// Load the configuration file @file
+    pub fn load<T: DeserializeOwned>(&self, file: impl AsRef<Path>) -> Result<T, ConfigFileError> {
+        T::from_config_file(self.path(file))
+    }
+
+    /// Load the configuration file @file, or the default configuration if it
+    /// doesn't exist yet
+    pub fn load_or_default<T: DeserializeOwned + Default>(
+        &self,
+        file: impl AsRef<Path>,
+    ) -> Result<T, ConfigFileError> {
+        match self.load(file) {
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(T::default())
+            }
+            config => config,
+        }
+    }
+
+    /// Load the configuration file @file, or store the default configuration
+    /// into it if it doesn't exist yet, as [`crate::load_or_store_default`]
+    /// does
+    pub fn load_or_store_default<T: DeserializeOwned + Serialize + Default>(
+        &self,
+        file: impl AsRef<Path>,
+    ) -> Result<T, ConfigFileError> {
+        crate::load_or_store_default(self.path(file))
+    }
+
+    /// Store @config into the configuration file @file, creating the
+    /// configuration directory if needed
+    pub fn store<T: Serialize + ?Sized>(
+        &self,
+        file: impl AsRef<Path>,
+        config: &T,
+    ) -> Result<(), ConfigFileError> {
+        config.to_config_file(self.path(file))
+    }
+}
+
+/// Operating systems disagreeing on where configuration files belong
+#[derive(Clone, Copy)]
+enum Os {
+    Windows,
+    Mac,
+    Unix,
+}
+
+impl Os {
+    const CURRENT: Self = if cfg!(windows) {
+        Self::Windows
+    } else if cfg!(target_os = "macos") {
+        Self::Mac
+    } else {
+        Self::Unix
+    };
+}
+
+/// The directory holding the configuration files of the user on the current
+/// platform: `%APPDATA%` on Windows, `~/Library/Application Support` on
+/// macOS, and [`xdg_config_home`] elsewhere
+pub fn config_dir() -> Option<PathBuf> {
+    user_dir(&env, Os::CURRENT, false)
+}
+
+/// The directory holding the configuration files of the user which are
+/// specific to this machine: `%LOCALAPPDATA%` on Windows, and
+/// [`config_dir`] elsewhere
+pub fn local_config_dir() -> Option<PathBuf> {
+    user_dir(&env, Os::CURRENT, true)
+}
+
+/// The location of the configuration file @file of the application @app in
+/// [`config_dir`], such as `%APPDATA%\<app>\<file>` on Windows
+pub fn config_path(app: &str, file: impl AsRef<Path>) -> Option<PathBuf> {
+    Some(config_dir()?.join(app).join(file))
+}
+
+/// The configuration file @file sitting next to the executable, if any, for
+/// portable applications carried along with their configuration, such as
+/// on a USB stick
+pub fn portable_config_path(file: impl AsRef<Path>) -> Option<PathBuf> {
+    beside(&std::env::current_exe().ok()?, file.as_ref())
+}
+
+/// The location of the configuration file @file of the application @app:
+/// [`portable_config_path`] if the file sits next to the executable, or else
+/// [`config_path`]
+///
+/// ```rust,no_run
+/// use config_file::{paths::resolve_config_path, FromConfigFile};
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let path = resolve_config_path("myapp", "config.toml").unwrap();
+/// let config = Config::from_config_file(path).unwrap();
+/// ```
+pub fn resolve_config_path(app: &str, file: impl AsRef<Path>) -> Option<PathBuf> {
+    let file = file.as_ref();
+    portable_config_path(file).or_else(|| config_path(app, file))
+}
+
+/// The file @file sitting next to @exe, if it exists
+fn beside(exe: &Path, file: &Path) -> Option<PathBuf> {
+    Some(exe.parent()?.join(file)).filter(|path| path.is_file())
+}
+
+/// Like [`config_dir`] and [`local_config_dir`] on @os, reading environment
+/// variables with @var
+fn user_dir(var: &dyn Fn(&str) -> Option<OsString>, os: Os, local: bool) -> Option<PathBuf> {
+    let absolute = |name| var(name).map(PathBuf::from).filter(|dir| dir.is_absolute());
+    match os {
+        Os::Windows if local => absolute("LOCALAPPDATA"),
+        Os::Windows => absolute("APPDATA"),
+        Os::Mac => Some(PathBuf::from(var("HOME")?).join("Library/Application Support")),
+        Os::Unix => config_home(var),
+    }
+}
+
+/// The directory holding the configuration files of the user,
+/// `$XDG_CONFIG_HOME` or else `~/.config`
+pub fn xdg_config_home() -> Option<PathBuf> {
+    config_home(&env)
+}
+
+/// The directories holding the configuration files of the system, from the
+/// most to the least important, `$XDG_CONFIG_DIRS` or else `/etc/xdg`
+pub fn xdg_config_dirs() -> Vec<PathBuf> {
+    config_dirs(&env)
+}
+
+/// The locations of the configuration file @file of the application @app,
+/// such as `~/.config/<app>/<file>`, from the most to the least important,
+/// whether they exist or not
+pub fn xdg_config_paths(app: &str, file: impl AsRef<Path>) -> Vec<PathBuf> {
+    config_paths(&env, app, file.as_ref())
+}
+
+/// The location of the configuration file @file of the application @app:
+/// the most important one which exists, or else the one of the user, which
+/// is where the file should be created. `None` when the home directory of
+/// the user is unknown and no file exists.
+///
+/// ```rust,no_run
+/// use config_file::{paths::xdg_config_path, FromConfigFile};
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let path = xdg_config_path("myapp", "config.toml").unwrap();
+/// let config = Config::from_config_file(path).unwrap();
+/// ```
+pub fn xdg_config_path(app: &str, file: impl AsRef<Path>) -> Option<PathBuf> {
+    let file = file.as_ref();
+    let paths = config_paths(&env, app, file);
+    match paths.iter().find(|path| path.exists()) {
+        Some(path) => Some(path.clone()),
+        None => Some(config_home(&env)?.join(app).join(file)),
+    }
+}
+
+/// Load the first of the configuration files located at @candidates which
+/// exists and can be loaded, along with its location. Candidates are
+/// expanded as [`expand_path`] does, skipping those which can't be, such as
+/// `${XDG_CONFIG_HOME}/myapp.toml` when the variable isn't set.
+///
+/// When none can be loaded, the error raised by the first existing one is
+/// returned, or a [`std::io::ErrorKind::NotFound`] one if none exists.
+///
+/// ```rust,no_run
+/// use config_file::load_first;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let (config, path) = load_first::<Config>(&[
+///     "./myapp.toml",
+///     "~/.config/myapp/config.toml",
+///     "/etc/myapp.toml",
+/// ])
+/// .unwrap();
+/// ```
+pub fn load_first<T: DeserializeOwned>(
+    candidates: &[impl AsRef<Path>],
+) -> Result<(T, PathBuf), ConfigFileError> {
+    let mut error = None;
+    for candidate in candidates {
+        let Ok(path) = expand_path(candidate) else {
+            continue;
+        };
+        match T::from_config_file(&path) {
+            Ok(config) => return Ok((config, path)),
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+            }
+            Err(err) => {
+                error.get_or_insert(err);
+            }
+        }
+    }
+    Err(error.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "none of the configuration files exists",
+        )
+        .into()
+    }))
+}
+
+/// Load the configuration file located at @path with the extension of any
+/// enabled format, such as `config.toml` or `config.json` for `config`,
+/// failing with [`ConfigFileError::AmbiguousFormat`] if there are several.
+///
+/// ```rust,no_run
+/// use config_file::load_any_format;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let config: Config = load_any_format("/etc/myapp/config").unwrap();
+/// ```
+pub fn load_any_format<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, ConfigFileError> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default();
+    let files = crate::dir::config_files(dir, |file| file.file_stem() == Some(name))?;
+    match &files[..] {
+        [file] => T::from_config_file(file),
+        [] => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no config file named {}", path.display()),
+        )
+        .into()),
+        _ => Err(ConfigFileError::AmbiguousFormat(path.to_path_buf())),
+    }
+}
+
+/// Load the configuration file located at the path held by the environment
+/// variable @var, or at @default when it isn't set or is empty.
+///
+/// A file given by @var must exist: a mistyped path fails loudly rather
+/// than falling back to @default.
+///
+/// ```rust,no_run
+/// use config_file::load_from_env_or;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let config: Config = load_from_env_or("MYAPP_CONFIG", "/etc/myapp.toml").unwrap();
+/// ```
+pub fn load_from_env_or<T: DeserializeOwned>(
+    var: &str,
+    default: impl AsRef<Path>,
+) -> Result<T, ConfigFileError> {
+    from_var_or(&env, var, default.as_ref())
+}
+
+/// Like [`load_from_env_or`], reading environment variables with @env
+fn from_var_or<T: DeserializeOwned>(
+    env: &dyn Fn(&str) -> Option<OsString>,
+    var: &str,
+    default: &Path,
+) -> Result<T, ConfigFileError> {
+    match env(var).filter(|path| !path.is_empty()) {
+        Some(path) => T::from_config_file(path),
+        None => T::from_config_file(default),
+    }
+}
+
+/// Find the file named @file in the current directory or the closest of its
+/// ancestors, as git does with `.gitignore` files, for configuration files
+/// specific to a project
+///
+/// ```rust,no_run
+/// use config_file::{paths::discover_upwards, FromConfigFile};
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// if let Some(path) = discover_upwards("myapp.toml").unwrap() {
+///     let config = Config::from_config_file(path).unwrap();
+/// }
+/// ```
+pub fn discover_upwards(file: impl AsRef<Path>) -> Result<Option<PathBuf>, ConfigFileError> {
+    Ok(discover_upwards_from(std::env::current_dir()?, file))
+}
+
+/// Find the file named @file in the directory @start or the closest of its
+/// ancestors
+pub fn discover_upwards_from(start: impl AsRef<Path>, file: impl AsRef<Path>) -> Option<PathBuf> {
+    start
+        .as_ref()
+        .ancestors()
+        .map(|dir| dir.join(file.as_ref()))
+        .find(|path| path.is_file())
+}
+
+/// Expand @path as a shell would: a leading `~` or `~user` becomes the home
+/// directory of the current user or of `user`, and `${VAR}` becomes the
+/// value of the environment variable `VAR`, failing with
+/// [`ConfigFileError::PathExpansion`] when it isn't set.
+///
+/// ```rust,no_run
+/// use config_file::paths::expand_path;
+///
+/// let path = expand_path("${XDG_STATE_HOME}/myapp/config.toml").unwrap();
+/// ```
+pub fn expand_path(path: impl AsRef<Path>) -> Result<PathBuf, ConfigFileError> {
+    expand(&env, &user_home, path.as_ref())
+}
+
+/// Like [`expand_path`], reading environment variables with @var and the
+/// home directories of other users with @home
+pub(crate) fn expand(
+    var: &dyn Fn(&str) -> Option<OsString>,
+    home: &dyn Fn(&str) -> Option<PathBuf>,
+    path: &Path,
+) -> Result<PathBuf, ConfigFileError> {
+    let Some(mut rest) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+    let failed = |what: &str| ConfigFileError::PathExpansion(what.to_string());
+    let mut expanded = OsString::new();
+    if let Some(tilde) = rest.strip_prefix('~') {
+        let end = tilde
+            .find(['/', std::path::MAIN_SEPARATOR])
+            .unwrap_or(tilde.len());
+        let (user, after) = tilde.split_at(end);
+        let dir = match user {
+            "" => var(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from),
+            user => home(user),
+        };
+        expanded.push(dir.ok_or_else(|| failed(&format!("~{user}")))?);
+        rest = after;
+    }
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| failed(&rest[start..]))?;
+        let name = &rest[start + 2..start + end];
+        expanded.push(&rest[..start]);
+        expanded.push(var(name).ok_or_else(|| failed(&format!("${{{name}}}")))?);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push(rest);
+    Ok(expanded.into())
+}
+
+/// The home directory of @user, read from `/etc/passwd` on Unix and guessed
+/// from the one of the current user on Windows
+fn user_home(user: &str) -> Option<PathBuf> {
+    if cfg!(windows) {
+        let profile = PathBuf::from(env("USERPROFILE")?);
+        return Some(profile.parent()?.join(user));
+    }
+    let passwd = std::fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|entry| {
+        let fields: Vec<_> = entry.split(':').collect();
+        match fields[..] {
+            [name, _, _, _, _, home, ..] if name == user => Some(PathBuf::from(home)),
+            _ => None,
+        }
+    })
+}
+
+fn env(var: &str) -> Option<OsString> {
+    std::env::var_os(var)
+}
+
+/// Like [`xdg_config_home`], reading environment variables with @var
+fn config_home(var: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    // Relative paths are invalid and must be ignored
+    var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(PathBuf::from(var("HOME")?).join(".config")))
+}
+
+/// Like [`xdg_config_dirs`], reading environment variables with @var
+fn config_dirs(var: &dyn Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+    let dirs: Vec<_> = var("XDG_CONFIG_DIRS")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .filter(|dir| dir.is_absolute())
+        .collect();
+    match dirs.is_empty() {
+        true => vec![PathBuf::from("/etc/xdg")],
+        false => dirs,
+    }
+}
+
+/// Like [`xdg_config_paths`], reading environment variables with @var
+fn config_paths(var: &dyn Fn(&str) -> Option<OsString>, app: &str, file: &Path) -> Vec<PathBuf> {
+    config_home(var)
+        .into_iter()
+        .chain(config_dirs(var))
+        .map(|dir| dir.join(app).join(file))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(all(feature = "directories", feature = "toml"))]
+    fn test_config_location() {
+        use crate::test::TestConfig;
+
+        let dir = std::env::temp_dir().join("config-location");
+        let _ = std::fs::remove_dir_all(&dir);
+        let location = ConfigLocation { dir: dir.clone() };
+        assert_eq!(location.path("config.toml"), dir.join("config.toml"));
+        assert!(location.load::<TestConfig>("config.toml").is_err());
+        let config: Option<TestConfig> = location.load_or_default("config.toml").unwrap();
+        assert!(config.is_none());
+        location
+            .store("config.toml", &TestConfig::example())
+            .unwrap();
+        let config: TestConfig = location.load("config.toml").unwrap();
+        assert_eq!(config, TestConfig::example());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_load_first() {
+        use crate::{test::TestConfig, ToConfigFile};
+
+        let dir = std::env::temp_dir().join("config-load-first");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (missing, broken, valid) = (
+            dir.join("missing.toml"),
+            dir.join("broken.toml"),
+            dir.join("valid.toml"),
+        );
+        assert!(load_first::<TestConfig>(&[&missing]).is_err());
+        std::fs::write(&broken, "port = ").unwrap();
+        TestConfig::example().to_config_file(&valid).unwrap();
+        let (config, path) = load_first::<TestConfig>(&[&missing, &broken, &valid]).unwrap();
+        assert_eq!((config, path), (TestConfig::example(), valid.clone()));
+        let unset = Path::new("${CONFIG_FILE_UNSET}/config.toml");
+        let (_, path) = load_first::<TestConfig>(&[unset, &valid]).unwrap();
+        assert_eq!(path, valid);
+        assert!(matches!(
+            load_first::<TestConfig>(&[&missing, &broken]),
+            Err(ConfigFileError::Toml(_))
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "toml"))]
+    fn test_load_any_format() {
+        use crate::{test::TestConfig, ToConfigFile};
+
+        let dir = std::env::temp_dir().join("config-load-any-format");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        assert!(matches!(
+            load_any_format::<TestConfig>(&path),
+            Err(ConfigFileError::FileAccess(_))
+        ));
+        TestConfig::example()
+            .to_config_file(dir.join("config.toml"))
+            .unwrap();
+        std::fs::write(dir.join("config.local.json"), "{}").unwrap();
+        std::fs::write(dir.join("config.txt"), "").unwrap();
+        let config: TestConfig = load_any_format(&path).unwrap();
+        assert_eq!(config, TestConfig::example());
+        TestConfig::example()
+            .to_config_file(dir.join("config.json"))
+            .unwrap();
+        assert!(matches!(
+            load_any_format::<TestConfig>(&path),
+            Err(ConfigFileError::AmbiguousFormat(ambiguous)) if ambiguous == path
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_load_from_env_or() {
+        use crate::{test::TestConfig, ToConfigFile};
+
+        let dir = std::env::temp_dir().join("config-load-from-env-or");
+        let _ = std::fs::remove_dir_all(&dir);
+        let (default, custom) = (dir.join("default.toml"), dir.join("custom.toml"));
+        TestConfig::example().to_config_file(&default).unwrap();
+        let var = "MYAPP_CONFIG";
+        let unset = |_: &str| None;
+        let config: TestConfig = from_var_or(&unset, var, &default).unwrap();
+        assert_eq!(config, TestConfig::example());
+        let empty = |_: &str| Some(OsString::new());
+        let config: TestConfig = from_var_or(&empty, var, &default).unwrap();
+        assert_eq!(config, TestConfig::example());
+        let set = |name: &str| (name == var).then(|| custom.clone().into_os_string());
+        assert!(from_var_or::<TestConfig>(&set, var, &default).is_err());
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&custom).unwrap();
+        let config: TestConfig = from_var_or(&set, var, &default).unwrap();
+        assert_eq!(config.port, 8080);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_discover_upwards() {
+        let root = std::env::temp_dir().join("config-discover-upwards");
+        let _ = std::fs::remove_dir_all(&root);
+        let nested = root.join("project/src/module");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(discover_upwards_from(&nested, "myapp.toml"), None);
+        std::fs::write(root.join("myapp.toml"), "").unwrap();
+        std::fs::write(root.join("project/myapp.toml"), "").unwrap();
+        assert_eq!(
+            discover_upwards_from(&nested, "myapp.toml"),
+            Some(root.join("project/myapp.toml"))
+        );
+        std::fs::create_dir(nested.join("myapp.toml")).unwrap();
+        assert_eq!(
+            discover_upwards_from(&nested, "myapp.toml"),
+            Some(root.join("project/myapp.toml"))
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_expand() {
+        let var = |name: &str| match name {
+            "HOME" | "USERPROFILE" => Some("/home/user".into()),
+            "APP" => Some("myapp".into()),
+            _ => None,
+        };
+        let home = |user: &str| (user == "other").then(|| PathBuf::from("/home/other"));
+        let expand = |path: &str| expand(&var, &home, Path::new(path));
+        assert_eq!(
+            expand("~/.config/${APP}/${APP}.toml").unwrap(),
+            Path::new("/home/user/.config/myapp/myapp.toml")
+        );
+        assert_eq!(
+            expand("~other/app.toml").unwrap(),
+            Path::new("/home/other/app.toml")
+        );
+        assert_eq!(
+            expand("/etc/a~b$c.toml").unwrap(),
+            Path::new("/etc/a~b$c.toml")
+        );
+        for (path, failed) in [
+            ("~nobody/app.toml", "~nobody"),
+            ("/etc/${MISSING}.toml", "${MISSING}"),
+            ("/etc/${APP.toml", "${APP.toml"),
+        ] {
+            assert!(
+                matches!(expand(path), Err(ConfigFileError::PathExpansion(what)) if what == failed)
+            );
+        }
+    }
+
+    #[test]
+    fn test_portable() {
+        let dir = std::env::temp_dir().join("config-portable");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("myapp.exe");
+        assert_eq!(beside(&exe, Path::new("config.toml")), None);
+        std::fs::write(dir.join("config.toml"), "").unwrap();
+        assert_eq!(
+            beside(&exe, Path::new("config.toml")),
+            Some(dir.join("config.toml"))
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_user_dir() {
+        let var = |name: &str| match name {
+            "APPDATA" => Some(r"C:\Users\user\AppData\Roaming".into()),
+            "LOCALAPPDATA" => Some("relative".into()),
+            "HOME" => Some("/home/user".into()),
+            _ => None,
+        };
+        assert_eq!(
+            user_dir(&var, Os::Mac, true),
+            Some(PathBuf::from("/home/user/Library/Application Support"))
+        );
+        assert_eq!(
+            user_dir(&var, Os::Unix, false),
+            Some(PathBuf::from("/home/user/.config"))
+        );
+        assert_eq!(user_dir(&var, Os::Windows, true), None);
+        assert_eq!(user_dir(&var, Os::Windows, false).is_some(), cfg!(windows));
+    }
+
+    #[test]
+    fn test_xdg() {
+        let var = |name: &str| match name {
+            "HOME" => Some("/home/user".into()),
+            "XDG_CONFIG_DIRS" => Some("/etc/custom:relative:/etc/xdg".into()),
+            _ => None,
+        };
+        assert_eq!(config_home(&var), Some(PathBuf::from("/home/user/.config")));
+        assert_eq!(
+            config_paths(&var, "app", Path::new("config.toml")),
+            [
+                "/home/user/.config/app/config.toml",
+                "/etc/custom/app/config.toml",
+                "/etc/xdg/app/config.toml"
+            ]
+            .map(PathBuf::from)
+        );
+
+        let var = |name: &str| match name {
+            "XDG_CONFIG_HOME" => Some("/config".into()),
+            "XDG_CONFIG_DIRS" => Some("".into()),
+            _ => None,
+        };
+        assert_eq!(config_home(&var), Some(PathBuf::from("/config")));
+        assert_eq!(config_dirs(&var), [PathBuf::from("/etc/xdg")]);
+        assert_eq!(config_home(&|_| None), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! Settings enforced by administrators, also known as managed preferences.
+//!
+//! A policy is made of the configuration files found in a system wide
+//! directory, merged in lexical order:
+//!
+//! - `/etc/<app>/policies` on Linux and other Unix systems
+//! - `/Library/Managed Preferences/<app>` on macOS
+//! - `%ProgramData%\<app>\policies` on Windows
+//!
+//! Only files in a supported format are considered, so the binary property
+//! lists deployed by MDM solutions and the Windows registry aren't read.
+//!
+//! When added to [`Layers`](crate::layers::Layers), a policy takes precedence
+//! over every other layer, and applications can tell users which settings
+//! they can't change.
+//!
+//! ```rust,no_run
+//! use config_file::{layers::Layers, policy::Policy};
+//! # #[derive(serde::Deserialize)]
+//! # struct Config {}
+//!
+//! let policy = Policy::load("myapp").unwrap();
+//! let config = Layers::new()
+//!     .file("/home/user/.config/myapp/config.toml")
+//!     .unwrap()
+//!     .policy(&policy)
+//!     .build::<Config>()
+//!     .unwrap();
+//! if policy.is_controlled("telemetry.enabled") {
+//!     // grey out the setting
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::{dir::merge_dir, layers::Layer, ConfigFileError, ConfigValue};
+
+/// Settings enforced by administrators
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    value: ConfigValue,
+    files: Vec<PathBuf>,
+}
+
+impl Policy {
+    /// Load the policy of the application named @app from the platform
+    /// specific directory
+    pub fn load(app: &str) -> Result<Self, ConfigFileError> {
+        Self::from_dir(policy_dir(app))
+    }
+
+    /// Load the policy made of the configuration files located in @dir. A
+    /// missing directory is an empty policy.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let (value, files) = merge_dir(dir.as_ref())?;
+        Ok(Self { value, files })
+    }
+
+    /// The files the policy was loaded from
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Whether the policy doesn't enforce anything
+    pub fn is_empty(&self) -> bool {
+        self.controlled_keys().is_empty()
+    }
+
+    /// The dotted paths of the settings enforced by the policy, such as
+    /// `telemetry.enabled`
+    pub fn controlled_keys(&self) -> Vec<String> {
+        self.value.leaf_paths()
+    }
+
+    /// Whether the setting located at the dotted @key, or part of it, is
+    /// enforced by the policy
+    pub fn is_controlled(&self, key: &str) -> bool {
+        self.controlled_keys().iter().any(|controlled| {
+            let (longer, shorter) = match controlled.len() > key.len() {
+                true => (controlled.as_str(), key),
+                false => (key, controlled.as_str()),
+            };
+            longer == shorter
+                || longer
+                    .strip_prefix(shorter)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+
+    /// The value enforced by the policy
+    pub fn value(&self) -> &ConfigValue {
+        &self.value
+    }
+
+    /// The policy as a layer locking all its keys
+    pub fn layer(&self) -> Layer {
+        Layer::new("policy", self.value.clone()).lock_all()
+    }
+}
+
+/// The directory holding the policy of the application named @app
+pub fn policy_dir(app: &str) -> PathBuf {
+    if cfg!(windows) {
+        std::env::var_os("ProgramData")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+            .join(app)
+            .join("policies")
+    } else if cfg!(target_os = "macos") {
+        Path::new("/Library/Managed Preferences").join(app)
+    } else {
+        Path::new("/etc").join(app).join("policies")
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{layers::Layers, test::TestConfig, Warning};
+
+    #[test]
+    fn test_policy() {
+        let dir = temp_dir().join("config-policy");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("10-port.toml"), "port = 8443").unwrap();
+        std::fs::write(dir.join("20-inner.toml"), "[inner]\nanswer = 1").unwrap();
+        std::fs::write(dir.join("README"), "ignored").unwrap();
+
+        let policy = Policy::from_dir(&dir).unwrap();
+        assert_eq!(policy.files().len(), 2);
+        assert_eq!(policy.controlled_keys(), ["inner.answer", "port"]);
+        assert!(policy.is_controlled("inner"));
+        assert!(policy.is_controlled("port"));
+        assert!(!policy.is_controlled("portal"));
+
+        let user = std::collections::BTreeMap::from([("port", 8080)]);
+        let (config, warnings) = Layers::new()
+            .policy(&policy)
+            .layer(Layer::serialized("defaults", &TestConfig::example()).unwrap())
+            .layer(Layer::serialized("user", &user).unwrap())
+            .build_with_warnings::<TestConfig>()
+            .unwrap();
+        assert_eq!(config.port, 8443);
+        assert_eq!(config.inner.answer, 1);
+        assert_eq!(
+            warnings,
+            [
+                Warning::LockedKey {
+                    key: "inner.answer".into(),
+                    layer: "defaults".into(),
+                    locked_by: "policy".into(),
+                },
+                Warning::LockedKey {
+                    key: "port".into(),
+                    layer: "defaults".into(),
+                    locked_by: "policy".into(),
+                },
+                Warning::LockedKey {
+                    key: "port".into(),
+                    layer: "user".into(),
+                    locked_by: "policy".into(),
+                },
+            ]
+        );
+
+        assert!(Policy::from_dir(dir.join("missing")).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
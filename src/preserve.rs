@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+/// Apply the TOML document @data, about to be stored at @path, to the
+/// document already stored there, so that its comments, whitespace and key
+/// ordering are kept. @data is returned as is when there is no document to
+/// preserve or when either can't be parsed.
+pub(crate) fn toml(path: &Path, data: Vec<u8>) -> Vec<u8> {
+    let Ok(existing) = std::fs::read_to_string(path) else {
+        return data;
+    };
+    let new = std::str::from_utf8(&data).map(str::parse::<DocumentMut>);
+    let (Ok(mut document), Ok(Ok(new))) = (existing.parse::<DocumentMut>(), new) else {
+        return data;
+    };
+    update_table(document.as_table_mut(), new.as_table());
+    document.to_string().into_bytes()
+}
+
+/// Make @table hold the same entries as @new, keeping those which didn't
+/// change untouched
+fn update_table(table: &mut Table, new: &Table) {
+    table.retain(|key, _| new.contains_key(key));
+    for (key, item) in new.iter() {
+        match table.get_mut(key) {
+            Some(existing) => update_item(existing, item),
+            None => {
+                table.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// Make @item hold the same value as @new, keeping its layout as much as
+/// possible
+fn update_item(item: &mut Item, new: &Item) {
+    match (item, new) {
+        (Item::Table(table), Item::Table(new)) => update_table(table, new),
+        (Item::ArrayOfTables(tables), Item::ArrayOfTables(new)) if tables.len() == new.len() => {
+            for (table, new) in tables.iter_mut().zip(new.iter()) {
+                update_table(table, new);
+            }
+        }
+        (Item::Value(value), Item::Value(new)) => update_value(value, new.clone()),
+        (Item::Value(value @ Value::InlineTable(_)), Item::Table(new)) => {
+            update_value(value, Value::InlineTable(new.clone().into_inline_table()))
+        }
+        (item, new) => *item = new.clone(),
+    }
+}
+
+/// Replace @value with @new unless they are equal, keeping the whitespace
+/// and comments around it
+fn update_value(value: &mut Value, mut new: Value) {
+    if !equal(value, &new) {
+        *new.decor_mut() = value.decor().clone();
+        *value = new;
+    }
+}
+
+/// Whether @a and @b hold the same value, whatever their layout
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| equal(a, b))
+        }
+        (Value::InlineTable(a), Value::InlineTable(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| equal(a, b)))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env::temp_dir;
+
+    use crate::{test::TestConfig, update_key, FromConfigFile, ToConfigFile};
+
+    #[test]
+    fn test_preserve() {
+        let path = temp_dir().join("config-preserve.toml");
+        let original = "# Server settings\n\
+                        host = \"example.com\" # public name\n\
+                        tags = [\"example\", \"test\"]\n\
+                        port = 443\n\
+                        \n\
+                        # Deep thought\n\
+                        [inner]\n\
+                        answer = 42 # of course\n";
+        std::fs::write(&path, original).unwrap();
+        TestConfig::example().to_config_file(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        let mut config = TestConfig::example();
+        config.port = 8080;
+        config.to_config_file(&path).unwrap();
+        update_key(&path, "inner.answer", &0).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            original
+                .replace("port = 443", "port = 8080")
+                .replace("answer = 42", "answer = 0")
+        );
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 8080);
+        std::fs::remove_file(path).unwrap();
+    }
+}
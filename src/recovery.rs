@@ -0,0 +1,166 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{format::ConfigFormat, read_file, ConfigFileError};
+
+/// What [`recover`] did with the temporary files left behind next to a
+/// configuration file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recovery {
+    /// The temporary file moved in place of the missing or corrupted
+    /// configuration file, if any
+    pub restored: Option<PathBuf>,
+    /// The temporary files removed
+    pub removed: Vec<PathBuf>,
+}
+
+impl Recovery {
+    /// Whether no temporary file was found
+    pub fn is_empty(&self) -> bool {
+        self.restored.is_none() && self.removed.is_empty()
+    }
+}
+
+/// Clean up the temporary files left next to the configuration file located
+/// at @path by a process which crashed while storing it.
+///
+/// When the configuration file is missing or can't be loaded as a `T`, the
+/// most recent temporary file which can be loaded as a `T` replaces it,
+/// completing the interrupted write. Every other temporary file is removed,
+/// so a valid configuration file is never replaced.
+///
+/// A temporary file cut off by a crash often still parses, so `T` should
+/// require the settings which are always stored rather than default them: a
+/// truncated file lacking some of them is then removed rather than restored.
+///
+/// The temporary files of the current process are left alone, but those of
+/// other processes storing the file at the same time aren't, so this is
+/// meant to be called on startup.
+///
+/// ```rust,no_run
+/// use config_file::recover;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let recovery = recover::<Config>("/etc/myapp.toml").unwrap();
+/// ```
+pub fn recover<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<Recovery, ConfigFileError> {
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path);
+    let mut recovery = Recovery::default();
+    let mut valid = format.map_or(true, |format| is_valid::<T>(path, format));
+    for temp in temp_files(path)? {
+        if !valid && format.is_some_and(|format| is_valid::<T>(&temp, format)) {
+            std::fs::rename(&temp, path)?;
+            recovery.restored = Some(temp);
+            valid = true;
+        } else {
+            #[cfg(windows)]
+            crate::windows::make_writable(&temp)?;
+            std::fs::remove_file(&temp)?;
+            recovery.removed.push(temp);
+        }
+    }
+    Ok(recovery)
+}
+
+/// Whether the file located at @path can be loaded as a `T` in @format
+fn is_valid<T: DeserializeOwned>(path: &Path, format: ConfigFormat) -> bool {
+    read_file(path)
+        .and_then(|data| format.deserialize::<T>(&data))
+        .is_ok()
+}
+
+/// The temporary files of other processes named after @path, as created by
+/// [`crate::temp_path`], from the most recent to the oldest
+fn temp_files(path: &Path) -> Result<Vec<PathBuf>, ConfigFileError> {
+    let Some(name) = path.file_name().and_then(OsStr::to_str) else {
+        return Ok(Vec::new());
+    };
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let entries = match std::fs::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let prefix = format!("{name}.");
+    let mut temps = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(id) = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .and_then(|file_name| file_name.strip_suffix(".tmp"))
+        else {
+            continue;
+        };
+        let Some((pid, counter)) = id.split_once('-') else {
+            continue;
+        };
+        if !is_number(pid) || !is_number(counter) || pid == std::process::id().to_string() {
+            continue;
+        }
+        let modified = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        temps.push((modified, entry.path()));
+    }
+    temps.sort_by(|a, b| b.cmp(a));
+    Ok(temps.into_iter().map(|(_, temp)| temp).collect())
+}
+
+fn is_number(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile};
+
+    #[test]
+    fn test_recover() {
+        let dir = temp_dir().join("config-recover");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        let valid = dir.join("config.toml.0-1.tmp");
+        let partial = dir.join("config.toml.0-2.tmp");
+        let truncated = dir.join("config.toml.0-3.tmp");
+        let other = dir.join("config.toml.bak");
+        let data = ConfigFormat::Toml.serialize(&TestConfig::example());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&valid, data.unwrap()).unwrap();
+        std::fs::write(&partial, "host = \"exa").unwrap();
+        // Cut off at a line boundary, this still parses but lacks settings
+        std::fs::write(&truncated, "host = \"example.com\"\nport = 443\n").unwrap();
+        std::fs::write(&other, "").unwrap();
+
+        let recovery = recover::<TestConfig>(&path).unwrap();
+        assert_eq!(recovery.restored, Some(valid.clone()));
+        assert_eq!(recovery.removed, [truncated, partial]);
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        assert!(other.exists());
+
+        std::fs::write(&valid, "port = 8080\n").unwrap();
+        let recovery = recover::<TestConfig>(&path).unwrap();
+        assert_eq!(recovery.removed, [valid]);
+        assert_eq!(TestConfig::from_config_file(&path).unwrap().port, 443);
+        assert!(recover::<TestConfig>(&path).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
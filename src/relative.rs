@@ -0,0 +1,138 @@
+//! Configuration fields referencing other files relatively to the
+//! configuration file holding them.
+//!
+//! ```rust,no_run
+//! use config_file::{relative::Relative, FromConfigFile};
+//! # #[derive(serde::Deserialize)]
+//! # struct Tls {}
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Config {
+//!     // `tls = "tls.toml"` refers to `/etc/myapp/tls.toml`
+//!     tls: Relative<Tls>,
+//! }
+//!
+//! let config = Config::from_config_file("/etc/myapp/config.toml").unwrap();
+//! let tls = config.tls.load_relative("/etc/myapp/config.toml").unwrap();
+//! ```
+
+use std::{
+    fmt,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{ConfigFileError, FromConfigFile};
+
+/// Path to a configuration file holding a `T`, relative to the directory of
+/// the configuration file it was read from.
+///
+/// It is (de)serialized as a plain path.
+pub struct Relative<T> {
+    path: PathBuf,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Relative<T> {
+    /// Reference the file located at @path
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            marker: PhantomData,
+        }
+    }
+
+    /// The path as written in the configuration file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path of the referenced file when read from the configuration file
+    /// located at @config_path. Absolute paths are kept as is.
+    pub fn resolve(&self, config_path: impl AsRef<Path>) -> PathBuf {
+        match config_path.as_ref().parent() {
+            Some(base) => base.join(&self.path),
+            None => self.path.clone(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Relative<T> {
+    /// Load the referenced file, resolved against the configuration file
+    /// located at @config_path
+    pub fn load_relative(&self, config_path: impl AsRef<Path>) -> Result<T, ConfigFileError> {
+        T::from_config_file(self.resolve(config_path))
+    }
+}
+
+impl<T> Clone for Relative<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.path.clone())
+    }
+}
+
+impl<T> fmt::Debug for Relative<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Relative").field(&self.path).finish()
+    }
+}
+
+impl<T> PartialEq for Relative<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl<T> Eq for Relative<T> {}
+
+impl<T> Serialize for Relative<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.path.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Relative<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PathBuf::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{test::TestConfig, ToConfigFile};
+
+    #[derive(Deserialize)]
+    struct Main {
+        included: Relative<TestConfig>,
+    }
+
+    #[test]
+    fn test_load_relative() {
+        let dir = temp_dir().join("config-relative");
+        let path = dir.join("main.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "included = \"sub/included.toml\"").unwrap();
+        TestConfig::example()
+            .to_config_file(dir.join("sub/included.toml"))
+            .unwrap();
+
+        let main = Main::from_config_file(&path).unwrap();
+        assert_eq!(main.included.path(), Path::new("sub/included.toml"));
+        assert_eq!(
+            main.included.load_relative(&path).unwrap(),
+            TestConfig::example()
+        );
+        assert_eq!(
+            Relative::<TestConfig>::new("/etc/included.toml").resolve(&path),
+            Path::new("/etc/included.toml")
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
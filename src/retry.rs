@@ -0,0 +1,104 @@
+use std::{io, time::Duration};
+
+use crate::ConfigFileError;
+
+/// How many times to retry loading or storing a configuration file when it
+/// fails with a transient error, such as an interrupted system call or a
+/// sharing violation caused by an antivirus scanning the file on Windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    retries: u32,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Retry up to @retries times, waiting @backoff before the first retry and
+    /// twice as long before each subsequent one
+    pub fn new(retries: u32, backoff: Duration) -> Self {
+        Self { retries, backoff }
+    }
+
+    /// Run @operation, retrying it according to this policy
+    pub(crate) fn run<T>(
+        &self,
+        mut operation: impl FnMut() -> Result<T, ConfigFileError>,
+    ) -> Result<T, ConfigFileError> {
+        let mut backoff = self.backoff;
+        for _ in 0..self.retries {
+            match operation() {
+                Err(ConfigFileError::FileAccess(err)) if is_transient(&err) => {
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+        operation()
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Retry 3 times, starting with a 10ms backoff
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(10))
+    }
+}
+
+/// Run @operation, retrying it according to @policy if any
+pub(crate) fn retry<T>(
+    policy: Option<&RetryPolicy>,
+    mut operation: impl FnMut() -> Result<T, ConfigFileError>,
+) -> Result<T, ConfigFileError> {
+    match policy {
+        Some(policy) => policy.run(operation),
+        None => operation(),
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    #[cfg(windows)]
+    if matches!(err.raw_os_error(), Some(32 | 33)) {
+        return true;
+    }
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy::new(2, Duration::ZERO);
+        let mut attempts = 0;
+        let result = policy.run(|| {
+            attempts += 1;
+            match attempts {
+                1 => Err(io::Error::from(io::ErrorKind::Interrupted).into()),
+                2 => Err(io::Error::from(io::ErrorKind::WouldBlock).into()),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        attempts = 0;
+        let result = policy.run(|| -> Result<(), _> {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::Interrupted).into())
+        });
+        assert!(matches!(result, Err(ConfigFileError::FileAccess(_))));
+        assert_eq!(attempts, 3);
+
+        attempts = 0;
+        let result = policy.run(|| -> Result<(), _> {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound).into())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
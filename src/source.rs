@@ -0,0 +1,149 @@
+//! Where configuration layers come from.
+//!
+//! [`ConfigSource`] is implemented for files by [`FileSource`] and for values
+//! held in memory by [`MemorySource`]. Applications can implement it to load
+//! configurations from anywhere else, such as a database, and merge them with
+//! the other [`Layers`](crate::layers::Layers) like any file.
+//!
+//! ```rust,no_run
+//! use config_file::{
+//!     layers::Layers,
+//!     source::{ConfigSource, RawDocument},
+//!     ConfigFileError, ConfigValue,
+//! };
+//! # #[derive(serde::Deserialize)]
+//! # struct Config {}
+//!
+//! #[derive(Debug)]
+//! struct Database;
+//!
+//! impl ConfigSource for Database {
+//!     fn read(&self) -> Result<RawDocument, ConfigFileError> {
+//!         Ok(RawDocument::Value(ConfigValue::Null))
+//!     }
+//!
+//!     fn describe(&self) -> String {
+//!         "database".into()
+//!     }
+//! }
+//!
+//! let config = Layers::new()
+//!     .file("/etc/myapp/config.toml")
+//!     .unwrap()
+//!     .source(Box::new(Database))
+//!     .unwrap()
+//!     .build::<Config>()
+//!     .unwrap();
+//! ```
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{format::ConfigFormat, read_file, ConfigFileError, ConfigValue};
+
+/// The content of a [`ConfigSource`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawDocument {
+    /// A serialized document
+    Bytes {
+        /// The content of the document
+        data: Vec<u8>,
+        /// The format it is serialized in
+        format: ConfigFormat,
+    },
+    /// An already parsed document
+    Value(ConfigValue),
+}
+
+impl RawDocument {
+    /// Parse the document
+    pub fn parse(self) -> Result<ConfigValue, ConfigFileError> {
+        match self {
+            Self::Bytes { data, format } => format.deserialize(&data),
+            Self::Value(value) => Ok(value),
+        }
+    }
+}
+
+/// Something a configuration can be read from
+pub trait ConfigSource: fmt::Debug + Send + Sync {
+    /// Read the current content of the source
+    fn read(&self) -> Result<RawDocument, ConfigFileError>;
+
+    /// Describe the source to users, such as the path of a file. This names
+    /// its layer in warnings.
+    fn describe(&self) -> String;
+
+    /// The files to watch to know when the source changes. Sources which
+    /// can't be watched, which is the default, only change when reloading
+    /// them explicitly.
+    fn watch(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// A configuration file, in the format matching its extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    /// Read the configuration file located at @path
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl ConfigSource for FileSource {
+    fn read(&self) -> Result<RawDocument, ConfigFileError> {
+        let format =
+            ConfigFormat::from_path(&self.path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        Ok(RawDocument::Bytes {
+            data: read_file(&self.path)?,
+            format,
+        })
+    }
+
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn watch(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+}
+
+/// A configuration held in memory, such as defaults built by the application
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemorySource {
+    name: String,
+    value: ConfigValue,
+}
+
+impl MemorySource {
+    /// Create a source named @name holding @value
+    pub fn new(name: impl Into<String>, value: ConfigValue) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+}
+
+impl ConfigSource for MemorySource {
+    fn read(&self) -> Result<RawDocument, ConfigFileError> {
+        Ok(RawDocument::Value(self.value.clone()))
+    }
+
+    fn describe(&self) -> String {
+        self.name.clone()
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufRead, BufReader},
+    marker::PhantomData,
+    path::Path,
+};
+
+use serde::de::{DeserializeOwned, Error as _};
+
+use crate::{open_file, ConfigFileError};
+
+/// Load the elements of the JSON document located at @path one at a time,
+/// so that memory stays bounded by the largest element rather than the
+/// whole document.
+///
+/// The document is either a top-level JSON array, or JSON Lines, one value
+/// per line, when using the `.jsonl` or `.ndjson` extension.
+///
+/// ```rust,no_run
+/// use config_file::load_iter;
+/// # #[derive(serde::Deserialize)]
+/// # struct Host { name: String }
+///
+/// for host in load_iter::<Host>("/var/lib/myapp/hosts.json").unwrap() {
+///     println!("{}", host.unwrap().name);
+/// }
+/// ```
+pub fn load_iter<T: DeserializeOwned>(
+    path: impl AsRef<Path>,
+) -> Result<LoadIter<T>, ConfigFileError> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(OsStr::to_str);
+    Ok(LoadIter {
+        reader: BufReader::new(open_file(path)?),
+        lines: matches!(
+            extension.map(str::to_lowercase).as_deref(),
+            Some("jsonl" | "ndjson")
+        ),
+        started: false,
+        done: false,
+        element: Vec::new(),
+        _config: PhantomData,
+    })
+}
+
+/// Iterator over the elements of a JSON document, see [`load_iter`]
+pub struct LoadIter<T> {
+    reader: BufReader<File>,
+    /// Whether the document is made of JSON Lines rather than an array
+    lines: bool,
+    /// Whether the opening bracket of the array was read
+    started: bool,
+    done: bool,
+    /// Bytes of the element being read, kept to reuse its allocation
+    element: Vec<u8>,
+    _config: PhantomData<fn() -> T>,
+}
+
+impl<T> LoadIter<T> {
+    /// Read the next line which isn't blank into our element buffer, and
+    /// return whether there was one
+    fn next_line(&mut self) -> Result<bool, ConfigFileError> {
+        loop {
+            self.element.clear();
+            if self.reader.read_until(b'\n', &mut self.element)? == 0 {
+                return Ok(false);
+            }
+            if !self.element.iter().all(u8::is_ascii_whitespace) {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Read the next element of the array into our element buffer, and
+    /// return whether there was one
+    fn next_element(&mut self) -> Result<bool, ConfigFileError> {
+        let invalid = |message| ConfigFileError::Json(serde_json::Error::custom(message));
+        self.skip_whitespace()?;
+        let first = !self.started;
+        match (self.peek()?, first) {
+            (Some(b'['), true) => {
+                self.started = true;
+                self.reader.consume(1);
+                self.skip_whitespace()?;
+                if self.peek()? == Some(b']') {
+                    self.reader.consume(1);
+                    return self.end();
+                }
+            }
+            (_, true) => return Err(invalid("expected a top-level array")),
+            (Some(b','), false) => self.reader.consume(1),
+            (Some(b']'), false) => {
+                self.reader.consume(1);
+                return self.end();
+            }
+            (_, false) => return Err(invalid("expected `,` or `]` after array element")),
+        }
+        self.element.clear();
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        while let Some(byte) = self.peek()? {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' if in_string => escaped = true,
+                b'"' => in_string = !in_string,
+                _ if in_string => {}
+                b'[' | b'{' => depth += 1,
+                b',' | b']' if depth == 0 => break,
+                b']' | b'}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.element.push(byte);
+            self.reader.consume(1);
+        }
+        Ok(true)
+    }
+
+    /// Make sure nothing follows the array
+    fn end(&mut self) -> Result<bool, ConfigFileError> {
+        self.skip_whitespace()?;
+        match self.peek()? {
+            Some(_) => Err(ConfigFileError::Json(serde_json::Error::custom(
+                "trailing characters after array",
+            ))),
+            None => Ok(false),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), ConfigFileError> {
+        while self.peek()?.is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.reader.consume(1);
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, ConfigFileError> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for LoadIter<T> {
+    type Item = Result<T, ConfigFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = match self.lines {
+            true => self.next_line(),
+            false => self.next_element(),
+        };
+        let element = next.and_then(|found| match found {
+            true => Ok(Some(serde_json::from_slice(&self.element)?)),
+            false => Ok(None),
+        });
+        self.done = !matches!(element, Ok(Some(_)));
+        element.transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::test::TestConfig;
+
+    #[test]
+    fn test_load_iter() {
+        let path = temp_dir().join("config-load-iter.json");
+        let example = serde_json::to_string(&TestConfig::example()).unwrap();
+        std::fs::write(&path, format!(" [{example},\n {example} ]\n")).unwrap();
+        let configs: Vec<TestConfig> = load_iter(&path).unwrap().map(Result::unwrap).collect();
+        assert_eq!(configs, [TestConfig::example(), TestConfig::example()]);
+
+        std::fs::write(&path, "[]").unwrap();
+        assert_eq!(load_iter::<u8>(&path).unwrap().count(), 0);
+        std::fs::write(&path, r#"["a,]", "b\"]"]"#).unwrap();
+        let strings: Vec<String> = load_iter(&path).unwrap().map(Result::unwrap).collect();
+        assert_eq!(strings, ["a,]", "b\"]"]);
+        std::fs::write(&path, "[1, 2 3]").unwrap();
+        let numbers: Vec<_> = load_iter::<u8>(&path).unwrap().collect();
+        assert!(matches!(numbers[..], [Ok(1), Err(_)]));
+        std::fs::write(&path, "{}").unwrap();
+        assert!(load_iter::<u8>(&path).unwrap().next().unwrap().is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let path = path.with_extension("jsonl");
+        std::fs::write(&path, "1\n\n2\n").unwrap();
+        let numbers: Vec<u8> = load_iter(&path).unwrap().map(Result::unwrap).collect();
+        assert_eq!(numbers, [1, 2]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
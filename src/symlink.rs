@@ -0,0 +1,111 @@
+use std::{borrow::Cow, path::Path};
+
+use crate::ConfigFileError;
+
+/// Maximum number of symbolic links followed when resolving a path
+const MAX_LINKS: usize = 40;
+
+/// What to do with configuration files which are symbolic links, as created
+/// by dotfile managers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Read through the link, and store by overwriting the file it points to
+    /// in place. This isn't atomic, but keeps the file itself, along with its
+    /// other hard links.
+    Follow,
+    /// Fail with [`ConfigFileError::Symlink`] instead of loading or storing
+    /// through a link
+    RefuseSymlink,
+    /// Read through the link, and store by atomically replacing the file it
+    /// points to, leaving the link in place
+    #[default]
+    ReplaceTarget,
+    /// Read through the link, and store by atomically replacing the link
+    /// itself with a regular file
+    ReplaceLink,
+}
+
+/// The path of the file to load or store in place of the one located at
+/// @path according to @policy
+pub(crate) fn resolve(
+    path: &Path,
+    policy: SymlinkPolicy,
+) -> Result<Cow<'_, Path>, ConfigFileError> {
+    if policy == SymlinkPolicy::ReplaceLink {
+        return Ok(Cow::Borrowed(path));
+    }
+    let mut resolved = Cow::Borrowed(path);
+    for _ in 0..MAX_LINKS {
+        match std::fs::symlink_metadata(&resolved) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {}
+            _ => return Ok(resolved),
+        }
+        if policy == SymlinkPolicy::RefuseSymlink {
+            return Err(ConfigFileError::Symlink(path.to_path_buf()));
+        }
+        let link = std::fs::read_link(&resolved)?;
+        // Relative links are relative to the directory holding them
+        let parent = resolved.parent().map(Path::to_path_buf).unwrap_or_default();
+        resolved = Cow::Owned(parent.join(link));
+    }
+    Err(std::io::Error::other(format!(
+        "too many levels of symbolic links in {}",
+        path.display()
+    ))
+    .into())
+}
+
+#[cfg(all(test, unix, feature = "toml"))]
+mod test {
+    use std::{env::temp_dir, os::unix::fs::symlink};
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, LoadOptions, StoreOptions, ToConfigFile};
+
+    #[test]
+    fn test_policies() {
+        let dir = temp_dir().join("config-symlink");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("dotfiles")).unwrap();
+        let target = dir.join("dotfiles/config.toml");
+        let link = dir.join("config.toml");
+        TestConfig::example().to_config_file(&target).unwrap();
+        symlink("dotfiles/config.toml", &link).unwrap();
+        let config = |port| TestConfig {
+            port,
+            ..TestConfig::example()
+        };
+
+        let refuse = LoadOptions::new().symlinks(SymlinkPolicy::RefuseSymlink);
+        assert!(matches!(
+            TestConfig::from_config_file_with_options(&link, &refuse),
+            Err(ConfigFileError::Symlink(path)) if path == link
+        ));
+        let refuse = StoreOptions::new().symlinks(SymlinkPolicy::RefuseSymlink);
+        assert!(matches!(
+            config(0).to_config_file_with_options(&link, &refuse),
+            Err(ConfigFileError::Symlink(_))
+        ));
+
+        for (port, policy) in [
+            (1, SymlinkPolicy::Follow),
+            (2, SymlinkPolicy::ReplaceTarget),
+        ] {
+            let options = StoreOptions::new().symlinks(policy);
+            config(port)
+                .to_config_file_with_options(&link, &options)
+                .unwrap();
+            assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+            assert_eq!(TestConfig::from_config_file(&target).unwrap().port, port);
+        }
+
+        let options = StoreOptions::new().symlinks(SymlinkPolicy::ReplaceLink);
+        config(3)
+            .to_config_file_with_options(&link, &options)
+            .unwrap();
+        assert!(link.symlink_metadata().unwrap().is_file());
+        assert_eq!(TestConfig::from_config_file(&link).unwrap().port, 3);
+        assert_eq!(TestConfig::from_config_file(&target).unwrap().port, 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+//! Helpers to snapshot test how configurations are serialized.
+//!
+//! Golden files hold the expected serialization of a value. When the
+//! `CONFIG_FILE_BLESS` environment variable is set to anything but `0`, the
+//! golden files are rewritten from the current values instead of being
+//! compared against them, which is how they are created and updated.
+//!
+//! ```rust,no_run
+//! use config_file::testing::assert_config_matches;
+//! # #[derive(serde::Serialize)]
+//! # struct Config { port: u16 }
+//!
+//! assert_config_matches("tests/golden/config.toml", &Config { port: 8080 });
+//! ```
+
+use std::{fmt::Write, path::Path};
+
+use serde::Serialize;
+
+use crate::{format::ConfigFormat, read_file, write_file, ConfigFileError};
+
+/// Environment variable enabling the bless mode
+pub const BLESS_VAR: &str = "CONFIG_FILE_BLESS";
+
+/// Assert that @value serializes to the content of the golden file located at
+/// @path, using the format matching its extension.
+///
+/// # Panics
+///
+/// Panics with a line diff when the serialization differs from the golden
+/// file, or when the golden file can't be read or written.
+#[track_caller]
+pub fn assert_config_matches<T: Serialize + ?Sized>(path: impl AsRef<Path>, value: &T) {
+    let bless = std::env::var_os(BLESS_VAR).is_some_and(|bless| bless != "0");
+    if let Err(message) = check(path.as_ref(), value, bless) {
+        panic!("{message}");
+    }
+}
+
+/// Compare the serialization of @value with the golden file located at
+/// @path, or overwrite the latter with the former when @bless is set
+fn check<T: Serialize + ?Sized>(path: &Path, value: &T, bless: bool) -> Result<(), String> {
+    let error = |err: ConfigFileError| format!("{}: {err}", path.display());
+    let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat);
+    let actual = format
+        .and_then(|format| format.serialize(value))
+        .map_err(error)?;
+    let actual = String::from_utf8_lossy(&actual);
+    if bless {
+        return write_file(path, actual.as_bytes()).map_err(error);
+    }
+    let expected = read_file(path).map_err(|err| {
+        format!(
+            "{} (set {BLESS_VAR}=1 to create the golden file)",
+            error(err)
+        )
+    })?;
+    let expected = String::from_utf8_lossy(&expected);
+    if expected == actual {
+        return Ok(());
+    }
+    let mut message = format!(
+        "config doesn't match golden file {} (set {BLESS_VAR}=1 to update it)\n",
+        path.display()
+    );
+    for line in diff(&expected, &actual) {
+        let _ = writeln!(message, "{line}");
+    }
+    Err(message)
+}
+
+/// Diff @expected and @actual line by line, prefixing removed lines with `-`,
+/// added ones with `+` and common ones with a space
+fn diff(expected: &str, actual: &str) -> Vec<String> {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    // Length of the longest common subsequence of the suffixes
+    let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!(" {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(format!("-{}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::test::TestConfig;
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nd\nc\ne"),
+            [" a", "-b", "+d", " c", "+e"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_golden() {
+        let dir = temp_dir().join("config-golden");
+        let path = dir.join("config.toml");
+        assert!(check(&path, &TestConfig::example(), false)
+            .unwrap_err()
+            .contains(BLESS_VAR));
+        check(&path, &TestConfig::example(), true).unwrap();
+        assert_config_matches(&path, &TestConfig::example());
+
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        let message = check(&path, &changed, false).unwrap_err();
+        assert!(message.contains("-port = 443\n+port = 8080\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
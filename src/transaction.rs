@@ -0,0 +1,216 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{
+    backup, format::ConfigFormat, preserve_layout, rename_temp_file, replace_file, symlink,
+    write_error, write_temp_file, ConfigFileError, OverwritePolicy, StoreOptions,
+};
+
+/// Several configuration files stored together: either all of them are
+/// replaced, or none is.
+///
+/// Every configuration is serialized and written to a temporary file before
+/// any of the files is replaced. If replacing one of them fails, those
+/// already replaced get their previous content back.
+///
+/// ```rust,no_run
+/// use config_file::Transaction;
+/// # #[derive(serde::Serialize)]
+/// # struct Config {}
+/// # let (general, keys, plugins) = (Config {}, Config {}, Config {});
+///
+/// let mut transaction = Transaction::new();
+/// transaction.stage("/etc/myapp/general.toml", &general).unwrap();
+/// transaction.stage("/etc/myapp/keys.toml", &keys).unwrap();
+/// transaction.stage("/etc/myapp/plugins.toml", &plugins).unwrap();
+/// transaction.commit().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    options: StoreOptions,
+    staged: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Transaction {
+    /// Create an empty transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty transaction storing files according to @options.
+    ///
+    /// Files are always replaced atomically, so symbolic links are handled as
+    /// with [`SymlinkPolicy::ReplaceTarget`](crate::SymlinkPolicy) even when
+    /// following them is requested.
+    pub fn with_options(options: StoreOptions) -> Self {
+        Self {
+            options,
+            staged: Vec::new(),
+        }
+    }
+
+    /// Serialize @config, to be stored into the configuration file located
+    /// at @path when committing
+    pub fn stage<T: Serialize + ?Sized>(
+        &mut self,
+        path: impl Into<PathBuf>,
+        config: &T,
+    ) -> Result<(), ConfigFileError> {
+        let path = path.into();
+        let format = ConfigFormat::from_path(&path).ok_or(ConfigFileError::UnsupportedFormat)?;
+        let data = self.options.serialize(format, config)?;
+        self.staged.push((path, data));
+        Ok(())
+    }
+
+    /// Store every staged configuration
+    pub fn commit(self) -> Result<(), ConfigFileError> {
+        let options = &self.options;
+        let mut targets = Vec::with_capacity(self.staged.len());
+        for (path, data) in &self.staged {
+            let target = symlink::resolve(path, options.symlinks)?.into_owned();
+            let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+            let data = options.overwritten(&target, format, data.clone())?;
+            let data = preserve_layout(&target, format, data);
+            targets.push((path, target, data));
+        }
+        let mut temps = Vec::with_capacity(targets.len());
+        let mut previous = Vec::with_capacity(targets.len());
+        for (path, target, data) in &targets {
+            temps.push(
+                write_temp_file(target, data, options).map_err(|err| write_error(path, err))?,
+            );
+            previous.push(match std::fs::read(target) {
+                Ok(data) => Some(data),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err.into()),
+            });
+        }
+        for (index, temp) in temps.into_iter().enumerate() {
+            let (path, target, _) = &targets[index];
+            let backup = match options.effective_backup() {
+                Some(backup) => backup::backup(target, &backup, false),
+                None => Ok(()),
+            };
+            if let Err(err) = backup.and_then(|()| rename_temp_file(temp, target, options)) {
+                rollback(&targets[..index], &previous, options);
+                return Err(write_error(path, err));
+            }
+        }
+        #[cfg(feature = "checksum")]
+        if options.checksum {
+            for (path, _, data) in &targets {
+                crate::checksum::write(path, data, options.fsync)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Restore the @previous content of the @replaced files, on a best effort
+/// basis since we are already failing
+fn rollback(
+    replaced: &[(&PathBuf, PathBuf, Vec<u8>)],
+    previous: &[Option<Vec<u8>>],
+    options: &StoreOptions,
+) {
+    let options = StoreOptions {
+        backup: None,
+        overwrite: OverwritePolicy::Overwrite,
+        ..options.clone()
+    };
+    for ((_, target, _), previous) in replaced.iter().zip(previous) {
+        let _ = match previous {
+            Some(data) => replace_file(target, data, &options),
+            None => std::fs::remove_file(target).map_err(Into::into),
+        };
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{test::TestConfig, FromConfigFile, ToConfigFile};
+
+    #[test]
+    fn test_commit() {
+        let dir = temp_dir().join("config-transaction");
+        let _ = std::fs::remove_dir_all(&dir);
+        let first = dir.join("first.toml");
+        let second = dir.join("second.toml");
+        TestConfig::example().to_config_file(&first).unwrap();
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+
+        // The parent of the second file is a file, so it can't be written
+        let mut transaction = Transaction::new();
+        transaction.stage(&first, &changed).unwrap();
+        transaction
+            .stage(first.join("second.toml"), &changed)
+            .unwrap();
+        assert!(transaction.commit().is_err());
+        assert_eq!(TestConfig::from_config_file(&first).unwrap().port, 443);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let mut transaction = Transaction::new();
+        transaction.stage(&first, &changed).unwrap();
+        transaction.stage(&second, &changed).unwrap();
+        transaction.commit().unwrap();
+        assert_eq!(TestConfig::from_config_file(&first).unwrap().port, 8080);
+        assert_eq!(TestConfig::from_config_file(&second).unwrap().port, 8080);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let dir = temp_dir().join("config-transaction-overwrite");
+        let _ = std::fs::remove_dir_all(&dir);
+        let first = dir.join("first.toml");
+        let second = dir.join("second.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&second, "port = 8080").unwrap();
+
+        let options = StoreOptions::new().overwrite(OverwritePolicy::ErrorIfExists);
+        let mut transaction = Transaction::with_options(options);
+        transaction.stage(&first, &TestConfig::example()).unwrap();
+        transaction.stage(&second, &TestConfig::example()).unwrap();
+        assert!(transaction.commit().is_err());
+        assert!(!first.exists());
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "port = 8080");
+
+        let options = StoreOptions::new().overwrite(OverwritePolicy::MergeInto);
+        let mut transaction = Transaction::with_options(options);
+        transaction.stage(&second, &TestConfig::example()).unwrap();
+        transaction.commit().unwrap();
+        let config = TestConfig::from_config_file(&second).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.host, "example.com");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rollback() {
+        let dir = temp_dir().join("config-transaction-rollback");
+        let _ = std::fs::remove_dir_all(&dir);
+        let first = dir.join("first.toml");
+        let second = dir.join("second.toml");
+        TestConfig::example().to_config_file(&first).unwrap();
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        let options = StoreOptions::default();
+        let replaced = [
+            (&first, first.clone(), Vec::new()),
+            (&second, second.clone(), Vec::new()),
+        ];
+        let previous = [Some(std::fs::read(&first).unwrap()), None];
+        changed.to_config_file(&first).unwrap();
+        TestConfig::example().to_config_file(&second).unwrap();
+        rollback(&replaced, &previous, &options);
+        assert_eq!(TestConfig::from_config_file(&first).unwrap().port, 443);
+        assert!(!second.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,366 @@
+//! io_uring backed loading and storing, for workloads persisting many small
+//! files per second.
+//!
+//! Reads and writes go through a per-thread ring, set up once per thread, in
+//! chunks of up to 1 MiB. The chunks of a file are submitted at once, along
+//! with the `fsync` asked for by [`StoreOptions::fsync`] linked after the
+//! writes, so that a whole file usually takes a single system call instead of
+//! one per write and sync. When the kernel doesn't support io_uring, or the
+//! ring of a thread fails, regular file IO is used instead.
+//!
+//! Files are stored as [`ToConfigFile`](crate::ToConfigFile) stores them:
+//! the data is written to a temporary file which is then renamed over the
+//! configuration file, according to the given [`StoreOptions`].
+//!
+//! ```rust,no_run
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct State {}
+//! let state: State = config_file::uring::from_config_file("/var/lib/myapp/state.toml").unwrap();
+//! config_file::uring::to_config_file(&state, "/var/lib/myapp/state.toml").unwrap();
+//! ```
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use io_uring::{opcode, squeue, types, IoUring};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{format::ConfigFormat, open_file, store_file, ConfigFileError, StoreOptions};
+
+/// Number of entries of each per-thread ring
+const RING_ENTRIES: u32 = 64;
+
+/// Largest chunk submitted at once
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Largest number of chunks submitted at once, leaving room for an `fsync`
+const BATCH_SIZE: usize = RING_ENTRIES as usize - 1;
+
+/// Errors of `io_uring_enter` after which waiting again may succeed
+const EAGAIN: i32 = 11;
+const EBUSY: i32 = 16;
+
+thread_local! {
+    static RING: RefCell<Option<IoUring>> = RefCell::new(IoUring::new(RING_ENTRIES).ok());
+}
+
+/// `user_data` of the next submitted entry, unique to tell completions apart
+static USER_DATA: AtomicU64 = AtomicU64::new(0);
+
+/// Load a `C` from the configuration file located at @path
+pub fn from_config_file<C: DeserializeOwned>(path: impl AsRef<Path>) -> Result<C, ConfigFileError> {
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?;
+    let mut file = open_file(path)?;
+    let data = read(&mut file).map_err(ConfigFileError::FileAccess)?;
+    format.deserialize(&data)
+}
+
+/// Store @config into the configuration file located at @path
+pub fn to_config_file<C: Serialize>(
+    config: &C,
+    path: impl AsRef<Path>,
+) -> Result<(), ConfigFileError> {
+    to_config_file_with_options(config, path, &StoreOptions::default())
+}
+
+/// Store @config into the configuration file located at @path according to
+/// @options
+pub fn to_config_file_with_options<C: Serialize>(
+    config: &C,
+    path: impl AsRef<Path>,
+    options: &StoreOptions,
+) -> Result<(), ConfigFileError> {
+    let path = options.path(path.as_ref())?;
+    let format = ConfigFormat::from_path(&path).ok_or(ConfigFileError::UnsupportedFormat)?;
+    let data = options.serialize(format, config)?;
+    let options = StoreOptions {
+        uring: true,
+        ..options.clone()
+    };
+    store_file(&path, format, data, &options)
+}
+
+/// Read the whole content of @file
+fn read(file: &mut File) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; file.metadata()?.len() as usize];
+    let mut filled = 0;
+    let fallback = RING.with(|ring| -> io::Result<bool> {
+        let ring = &mut *ring.borrow_mut();
+        if ring.is_none() {
+            return Ok(true);
+        }
+        loop {
+            if filled == data.len() {
+                data.resize(filled + CHUNK_SIZE.min(filled.max(4096)), 0);
+            }
+            let chunks: Vec<_> = chunks(filled, data.len()).collect();
+            let entries: Vec<_> = chunks
+                .iter()
+                .map(|&(offset, len)| {
+                    let buffer = data[offset..].as_mut_ptr();
+                    opcode::Read::new(types::Fd(file.as_raw_fd()), buffer, len)
+                        .offset(offset as u64)
+                        .build()
+                })
+                .collect();
+            // SAFETY: the buffer outlives the operations since we wait for
+            // them, or is leaked when they are abandoned
+            let Some(results) = (unsafe { submit(ring, &entries) }) else {
+                std::mem::forget(std::mem::take(&mut data));
+                return Ok(true);
+            };
+            match transferred(&chunks, &results)? {
+                0 => return Ok(false),
+                read => filled += read,
+            }
+        }
+    })?;
+    if fallback {
+        data.clear();
+        file.read_to_end(&mut data)?;
+    } else {
+        data.truncate(filled);
+    }
+    Ok(data)
+}
+
+/// Write the whole @data to @file, and sync it to the disk if @sync is set
+pub(crate) fn write(file: &mut File, data: &[u8], sync: bool) -> io::Result<()> {
+    let mut written = 0;
+    let fallback = RING.with(|ring| -> io::Result<bool> {
+        let ring = &mut *ring.borrow_mut();
+        if ring.is_none() {
+            return Ok(true);
+        }
+        // The operations are given a copy of @data, which can be leaked if
+        // they are abandoned
+        let mut data = data.to_vec();
+        loop {
+            let chunks: Vec<_> = chunks(written, data.len()).collect();
+            let mut entries: Vec<_> = chunks
+                .iter()
+                .map(|&(offset, len)| {
+                    let buffer = data[offset..].as_ptr();
+                    opcode::Write::new(types::Fd(file.as_raw_fd()), buffer, len)
+                        .offset(offset as u64)
+                        .build()
+                })
+                .collect();
+            let last = chunks
+                .last()
+                .map_or(written, |&(offset, len)| offset + len as usize);
+            if last == data.len() && sync {
+                entries.push(opcode::Fsync::new(types::Fd(file.as_raw_fd())).build());
+            }
+            if entries.is_empty() {
+                return Ok(false);
+            }
+            // Link the operations so that they are performed in order, the
+            // sync only once every write succeeded
+            let linked = entries.len() - 1;
+            for entry in &mut entries[..linked] {
+                *entry = entry.clone().flags(squeue::Flags::IO_LINK);
+            }
+            // SAFETY: the buffer outlives the operations since we wait for
+            // them, or is leaked when they are abandoned
+            let Some(results) = (unsafe { submit(ring, &entries) }) else {
+                std::mem::forget(std::mem::take(&mut data));
+                return Ok(true);
+            };
+            match transferred(&chunks, &results)? {
+                0 if !chunks.is_empty() => return Err(io::ErrorKind::WriteZero.into()),
+                count => written += count,
+            }
+            if written == data.len() {
+                if let Some(&result) = results.get(chunks.len()).filter(|&&result| result < 0) {
+                    return Err(io::Error::from_raw_os_error(-result));
+                }
+                return Ok(false);
+            }
+        }
+    })?;
+    if fallback {
+        file.write_all(data)?;
+        if sync {
+            file.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+/// Offsets and lengths of the chunks covering @start..@end, up to the number
+/// submitted at once
+fn chunks(start: usize, end: usize) -> impl Iterator<Item = (usize, u32)> {
+    (start..end)
+        .step_by(CHUNK_SIZE)
+        .take(BATCH_SIZE)
+        .map(move |offset| (offset, (end - offset).min(CHUNK_SIZE) as u32))
+}
+
+/// Number of bytes contiguously transferred by the operations on @chunks
+/// given their @results, up to the first one which fell short
+fn transferred(chunks: &[(usize, u32)], results: &[i32]) -> io::Result<usize> {
+    let mut total = 0;
+    for (&(_, len), &result) in chunks.iter().zip(results) {
+        if result < 0 {
+            // The following chunks are retried, and report the error again
+            // if it persists
+            if total == 0 {
+                return Err(io::Error::from_raw_os_error(-result));
+            }
+            break;
+        }
+        total += result as usize;
+        if result as u32 != len {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// Submit @entries to @ring at once and wait for all of them to complete,
+/// returning their results in order, or `None` if the ring failed and the
+/// operations should be performed with regular file IO instead
+///
+/// Completions are matched to the entries through their `user_data`. When
+/// submitting fails, entries left in the submission queue would be submitted
+/// along with the next ones, once their buffers are gone: the ring is then
+/// replaced by a new one. When operations may still be in flight, the ring is
+/// leaked instead, so that the kernel never completes them into unmapped
+/// memory, and regular file IO is used on this thread from then on.
+///
+/// # Safety
+///
+/// The buffers referenced by @entries must stay valid until this returns, and
+/// forever when it returns `None`.
+unsafe fn submit(ring: &mut Option<IoUring>, entries: &[squeue::Entry]) -> Option<Vec<i32>> {
+    let uring = ring.as_mut()?;
+    let first = USER_DATA.fetch_add(entries.len() as u64, Ordering::Relaxed);
+    let mut results = vec![None; entries.len()];
+    let (mut submitted, mut completed) = (0, 0);
+    let mut failed = false;
+    for (index, entry) in entries.iter().enumerate() {
+        let entry = entry.clone().user_data(first + index as u64);
+        if uring.submission().push(&entry).is_err() {
+            failed = true;
+            break;
+        }
+    }
+    while !failed && completed < entries.len() {
+        match enter(uring, &mut submitted) {
+            Ok(()) => {}
+            Err(err)
+                if err.kind() == io::ErrorKind::Interrupted
+                    || matches!(err.raw_os_error(), Some(EAGAIN | EBUSY)) => {}
+            Err(_) => {
+                failed = true;
+                break;
+            }
+        }
+        for entry in uring.completion() {
+            let index = entry.user_data().wrapping_sub(first) as usize;
+            if let Some(result @ None) = results.get_mut(index) {
+                *result = Some(entry.result());
+                completed += 1;
+            }
+        }
+    }
+    if !failed {
+        return Some(results.into_iter().flatten().collect());
+    }
+    match submitted > completed {
+        true => {
+            std::mem::forget(ring.take());
+        }
+        false => *ring = IoUring::new(RING_ENTRIES).ok(),
+    }
+    None
+}
+
+/// Submit the entries queued in @uring and wait for a completion, counting
+/// the entries @submitted
+fn enter(uring: &mut IoUring, submitted: &mut usize) -> io::Result<()> {
+    *submitted += uring.submit_and_wait(1)?;
+    #[cfg(test)]
+    if test::FAIL_ENTER.with(std::cell::Cell::take) {
+        return Err(io::Error::other("injected io_uring failure"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, env::temp_dir};
+
+    use super::*;
+    use crate::test::TestConfig;
+
+    thread_local! {
+        /// Whether the next call to [`enter`] fails once it submitted entries
+        pub(super) static FAIL_ENTER: Cell<bool> = const { Cell::new(false) };
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_round_trip() {
+        let path = temp_dir().join("config-uring.toml");
+        to_config_file(&TestConfig::example(), &path).unwrap();
+        assert_eq!(
+            from_config_file::<TestConfig>(&path).unwrap(),
+            TestConfig::example()
+        );
+        assert_eq!(
+            from_config_file::<TestConfig>("testdata/config.toml").unwrap(),
+            TestConfig::example()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_options() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir().join("config-uring-options");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        let options = StoreOptions::new().mode(0o600).fsync(true);
+        to_config_file_with_options(&TestConfig::example(), &path, &options).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let options = options.overwrite(crate::OverwritePolicy::ErrorIfExists);
+        assert!(to_config_file_with_options(&TestConfig::example(), &path, &options).is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_chunks() {
+        let path = temp_dir().join("config-uring-chunks.bin");
+        let data: Vec<u8> = (0..3 * CHUNK_SIZE + 17).map(|i| i as u8).collect();
+        let mut file = File::create(&path).unwrap();
+        write(&mut file, &data, true).unwrap();
+        write(&mut file, &[], true).unwrap();
+        assert_eq!(read(&mut File::open(&path).unwrap()).unwrap(), data);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_abandoned() {
+        let path = temp_dir().join("config-uring-abandoned.bin");
+        let data: Vec<u8> = (0..2 * CHUNK_SIZE).map(|i| i as u8).collect();
+        let mut file = File::create(&path).unwrap();
+        FAIL_ENTER.with(|fail| fail.set(true));
+        write(&mut file, &data, true).unwrap();
+        assert!(RING.with(|ring| ring.borrow().is_none()));
+        assert_eq!(read(&mut File::open(&path).unwrap()).unwrap(), data);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,1466 @@
+//! Format independent representation of configuration documents.
+//!
+//! [`ConfigValue`] can be loaded from any supported format like any other
+//! type, and any serializable type can be converted to and from it with
+//! [`to_value`] and [`from_value`]. Converting from a [`ConfigValue`] is
+//! lenient: strings are parsed when a number or a boolean is expected, since
+//! some formats like XML only know about strings.
+//!
+//! Integers and floats are kept apart, and TOML datetimes are kept as maps
+//! holding their text, see [`ConfigValue::as_datetime`], so that they are
+//! stored back as datetimes. YAML tags are not kept.
+//!
+//! Integers are kept up to the range of `i128`, and formats which can't
+//! store an integer fail instead of rounding it, except for JSON integers
+//! beyond the range of `u64`, which are loaded as floats unless the
+//! arbitrary_precision feature is enabled.
+//!
+//! Map keys are sorted, unless the preserve_order feature is enabled, in
+//! which case they keep the order of the document they were loaded from, so
+//! that storing back a modified value doesn't shuffle the file.
+
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+
+use serde::{
+    de::{
+        self, value::StringDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess,
+        IntoDeserializer, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any, ser, Deserialize, Deserializer, Serialize, Serializer,
+};
+use thiserror::Error;
+
+/// A configuration value of any supported format
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ConfigValue {
+    /// A missing value
+    #[default]
+    Null,
+    /// A boolean
+    Bool(bool),
+    /// An integer
+    Integer(i128),
+    /// A floating point number
+    Float(f64),
+    /// A string
+    String(String),
+    /// An array of values
+    Array(Vec<ConfigValue>),
+    /// A map of values, also known as a table, an object or a section
+    Map(Map),
+}
+
+/// The map held by [`ConfigValue::Map`], sorting its keys, or keeping them in
+/// insertion order when the preserve_order feature is enabled
+#[cfg(not(feature = "preserve_order"))]
+pub type Map = BTreeMap<String, ConfigValue>;
+
+/// The map held by [`ConfigValue::Map`], sorting its keys, or keeping them in
+/// insertion order when the preserve_order feature is enabled
+#[cfg(feature = "preserve_order")]
+pub type Map = indexmap::IndexMap<String, ConfigValue>;
+
+/// Remove @key from @map, keeping the order of the other keys
+pub(crate) fn remove(map: &mut Map, key: &str) -> Option<ConfigValue> {
+    #[cfg(feature = "preserve_order")]
+    return map.shift_remove(key);
+    #[cfg(not(feature = "preserve_order"))]
+    return map.remove(key);
+}
+
+/// Key of the map toml represents datetimes with
+const DATETIME_FIELD: &str = "$__toml_private_datetime";
+
+/// Name of the struct toml represents datetimes with
+const DATETIME_NAME: &str = "$__toml_private_Datetime";
+
+/// Key of the map serde_json represents numbers with when using arbitrary
+/// precision
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) const JSON_NUMBER: &str = "$serde_json::private::Number";
+
+/// Error raised when converting from or to a [`ConfigValue`]
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct ValueError(String);
+
+impl ser::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Convert @value into a [`ConfigValue`]
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<ConfigValue, ValueError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Convert @value into a `T`
+pub fn from_value<T: DeserializeOwned>(value: ConfigValue) -> Result<T, ValueError> {
+    T::deserialize(value)
+}
+
+impl ConfigValue {
+    /// Get the map held by this value, if any
+    pub fn as_map(&self) -> Option<&Map> {
+        match self {
+            Self::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Get the map held by this value mutably, if any
+    pub fn as_map_mut(&mut self) -> Option<&mut Map> {
+        match self {
+            Self::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Get the text of the TOML datetime held by this value, if any, such as
+    /// `1979-05-27T07:32:00Z`
+    pub fn as_datetime(&self) -> Option<&str> {
+        match self.as_map()?.get(DATETIME_FIELD)? {
+            Self::String(datetime) if self.as_map()?.len() == 1 => Some(datetime),
+            _ => None,
+        }
+    }
+
+    /// Replace recursively the TOML datetimes held by this value with their
+    /// text, for formats which have no datetimes
+    pub(crate) fn flatten_datetimes(&mut self) {
+        if let Some(datetime) = self.as_datetime() {
+            *self = Self::String(datetime.to_string());
+        }
+        match self {
+            Self::Array(values) => values.iter_mut().for_each(Self::flatten_datetimes),
+            Self::Map(map) => map.values_mut().for_each(Self::flatten_datetimes),
+            _ => {}
+        }
+    }
+
+    /// Get the boolean held by this value, coercing the integers 1 and 0 and
+    /// the strings such as `true`, `yes`, `on`, `1` and their opposites, as
+    /// found in environment variables
+    pub fn as_bool_lenient(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            Self::Integer(1) => Some(true),
+            Self::Integer(0) => Some(false),
+            Self::String(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Some(true),
+                "false" | "no" | "off" | "0" => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the integer held by this value, coercing booleans to 1 and 0,
+    /// floats without fractional part, and strings holding an integer
+    pub fn as_int_lenient(&self) -> Option<i128> {
+        match self {
+            Self::Bool(value) => Some((*value).into()),
+            Self::Integer(value) => Some(*value),
+            Self::Float(value) if value.fract() == 0.0 && value.abs() < 2f64.powi(127) => {
+                Some(*value as i128)
+            }
+            Self::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the float held by this value, coercing integers and strings
+    /// holding a number
+    pub fn as_float_lenient(&self) -> Option<f64> {
+        match self {
+            Self::Integer(value) => Some(*value as f64),
+            Self::Float(value) => Some(*value),
+            Self::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the string held by this value, formatting booleans and numbers
+    pub fn as_str_lenient(&self) -> Option<Cow<'_, str>> {
+        match self {
+            Self::Bool(value) => Some(value.to_string().into()),
+            Self::Integer(value) => Some(value.to_string().into()),
+            Self::Float(value) => Some(value.to_string().into()),
+            Self::String(value) => Some(value.into()),
+            _ => None,
+        }
+    }
+
+    /// Remove recursively the entries of our maps which are equal to the ones
+    /// in @defaults, leaving only what differs from them
+    pub fn strip_defaults(&mut self, defaults: &ConfigValue) {
+        let (Self::Map(map), Self::Map(defaults)) = (self, defaults) else {
+            return;
+        };
+        map.retain(|key, value| match defaults.get(key) {
+            Some(default) if value == default => false,
+            Some(default @ Self::Map(_)) => {
+                value.strip_defaults(default);
+                !matches!(value, Self::Map(map) if map.is_empty())
+            }
+            _ => true,
+        });
+    }
+
+    /// Merge @other over ourselves: maps are merged recursively while any
+    /// other value of @other replaces ours
+    pub fn merge(&mut self, other: ConfigValue) {
+        match (self, other) {
+            (Self::Map(map), Self::Map(other)) => {
+                for (key, value) in other {
+                    match map.get_mut(&key) {
+                        Some(existing) => existing.merge(value),
+                        None => {
+                            map.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+
+    /// Get the value located at @path, made of dotted keys and array
+    /// indices such as `servers[0].tls.cert`, or a JSON Pointer (RFC 6901)
+    /// such as `/servers/0/tls/cert`. The empty path is ourselves.
+    pub fn get(&self, path: &str) -> Option<&ConfigValue> {
+        parse_path(path)
+            .ok()?
+            .into_iter()
+            .try_fold(self, |value, segment| match (segment, value) {
+                (Segment::Key(key), Self::Map(map)) => map.get(key.as_ref()),
+                (Segment::Index(index), Self::Array(values)) => values.get(index),
+                (Segment::Token(token), Self::Map(map)) => map.get(token.as_ref()),
+                (Segment::Token(token), Self::Array(values)) => values.get(index(&token)?),
+                _ => None,
+            })
+    }
+
+    /// Get the value located at @path mutably, as [`ConfigValue::get`] does
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut ConfigValue> {
+        parse_path(path)
+            .ok()?
+            .into_iter()
+            .try_fold(self, |value, segment| match (segment, value) {
+                (Segment::Key(key), Self::Map(map)) => map.get_mut(key.as_ref()),
+                (Segment::Index(index), Self::Array(values)) => values.get_mut(index),
+                (Segment::Token(token), Self::Map(map)) => map.get_mut(token.as_ref()),
+                (Segment::Token(token), Self::Array(values)) => values.get_mut(index(&token)?),
+                _ => None,
+            })
+    }
+
+    /// Set the value located at @path, made of dotted keys and array indices
+    /// such as `servers[0].tls.cert` or a JSON Pointer such as
+    /// `/servers/0/tls/cert`, to @value.
+    ///
+    /// Missing maps are created on the way, replacing whatever isn't a map,
+    /// and indices may be one past the end of an array, or `-` in a JSON
+    /// Pointer, to append to it.
+    ///
+    /// ```rust
+    /// use config_file::ConfigValue;
+    ///
+    /// let mut value = ConfigValue::default();
+    /// value.set("servers[0].port", 8080.into()).unwrap();
+    /// value.set("servers[1].port", 8081.into()).unwrap();
+    /// assert_eq!(value.get("servers[1].port"), Some(&ConfigValue::Integer(8081)));
+    /// assert!(value.set("servers[3].port", 8083.into()).is_err());
+    /// value.set("/servers/-/port", 8082.into()).unwrap();
+    /// assert_eq!(value.get("/servers/2/port"), Some(&ConfigValue::Integer(8082)));
+    /// ```
+    pub fn set(&mut self, path: &str, value: ConfigValue) -> Result<(), ValueError> {
+        let mut current = self;
+        for segment in parse_path(path)? {
+            let segment = match (segment, &*current) {
+                (Segment::Token(token), Self::Array(values)) => Segment::Index(match &*token {
+                    "-" => values.len(),
+                    token => index(token)
+                        .ok_or_else(|| ValueError(format!("invalid index {token} in {path}")))?,
+                }),
+                (Segment::Token(token), _) => Segment::Key(token),
+                (segment, _) => segment,
+            };
+            current = match segment {
+                Segment::Key(key) => {
+                    if !matches!(current, Self::Map(_)) {
+                        *current = Self::Map(Map::new());
+                    }
+                    let Self::Map(map) = current else {
+                        unreachable!()
+                    };
+                    map.entry(key.into_owned()).or_default()
+                }
+                Segment::Index(index) => {
+                    if matches!(current, Self::Null) {
+                        *current = Self::Array(Vec::new());
+                    }
+                    let Self::Array(values) = current else {
+                        return Err(ValueError(format!("{path} doesn't go through an array")));
+                    };
+                    if index == values.len() {
+                        values.push(Self::Null);
+                    }
+                    values.get_mut(index).ok_or_else(|| {
+                        ValueError(format!("index {index} out of bounds in {path}"))
+                    })?
+                }
+                Segment::Elements => {
+                    return Err(ValueError(format!("{path} selects several values")))
+                }
+                Segment::Token(_) => unreachable!(),
+            };
+        }
+        *current = value;
+        Ok(())
+    }
+
+    /// Select the values matching @query, a path as given to
+    /// [`ConfigValue::get`] where `*` matches any key of a map and `[*]` any
+    /// element of an array, along with their own paths.
+    ///
+    /// ```rust
+    /// use config_file::ConfigValue;
+    ///
+    /// let mut value = ConfigValue::default();
+    /// value.set("services[0].port", 80.into()).unwrap();
+    /// value.set("services[1].port", 443.into()).unwrap();
+    /// let ports = value.select("services[*].port").unwrap();
+    /// assert_eq!(ports[1], ("services[1].port".to_string(), &ConfigValue::Integer(443)));
+    /// ```
+    pub fn select(&self, query: &str) -> Result<Vec<(String, &ConfigValue)>, ValueError> {
+        let segments = parse_path(query)?;
+        let mut matches = vec![(String::new(), self)];
+        for segment in &segments {
+            matches = matches
+                .into_iter()
+                .flat_map(|(path, value)| select(segment, path, value))
+                .collect();
+        }
+        Ok(matches)
+    }
+
+    /// Get the value located at the dotted @path, such as `server.port`
+    pub(crate) fn get_path(&self, path: &str) -> Option<&ConfigValue> {
+        path.split('.')
+            .try_fold(self, |value, key| value.as_map()?.get(key))
+    }
+
+    /// Set the value located at the dotted @path, creating the missing maps
+    /// on the way and replacing whatever isn't a map
+    pub(crate) fn set_path(&mut self, path: &str, value: ConfigValue) {
+        let mut current = self;
+        for key in path.split('.') {
+            if !matches!(current, Self::Map(_)) {
+                *current = Self::Map(Map::new());
+            }
+            let Self::Map(map) = current else {
+                unreachable!()
+            };
+            current = map.entry(key.to_string()).or_default();
+        }
+        *current = value;
+    }
+
+    /// Remove the value located at the dotted @path
+    pub(crate) fn remove_path(&mut self, path: &str) -> Option<ConfigValue> {
+        let (parents, key) = match path.rsplit_once('.') {
+            Some((parents, key)) => (Some(parents), key),
+            None => (None, path),
+        };
+        let mut current = self;
+        for parent in parents.into_iter().flat_map(|parents| parents.split('.')) {
+            current = current.as_map_mut()?.get_mut(parent)?;
+        }
+        remove(current.as_map_mut()?, key)
+    }
+
+    /// Dotted paths of the values which aren't maps, or are empty ones, in
+    /// key order
+    pub(crate) fn leaf_paths(&self) -> Vec<String> {
+        fn collect(prefix: &str, value: &ConfigValue, paths: &mut Vec<String>) {
+            match value {
+                ConfigValue::Map(map) if !map.is_empty() => {
+                    let mut entries: Vec<_> = map.iter().collect();
+                    entries.sort_unstable_by_key(|(key, _)| *key);
+                    for (key, value) in entries {
+                        match prefix {
+                            "" => collect(key, value, paths),
+                            prefix => collect(&format!("{prefix}.{key}"), value, paths),
+                        }
+                    }
+                }
+                _ => paths.push(prefix.to_string()),
+            }
+        }
+
+        let mut paths = Vec::new();
+        if !matches!(self, Self::Null) && !matches!(self, Self::Map(map) if map.is_empty()) {
+            collect("", self, &mut paths);
+        }
+        paths
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Self::Null => Unexpected::Unit,
+            Self::Bool(value) => Unexpected::Bool(*value),
+            Self::Integer(value) => match i64::try_from(*value) {
+                Ok(value) => Unexpected::Signed(value),
+                Err(_) => Unexpected::Other("large integer"),
+            },
+            Self::Float(value) => Unexpected::Float(*value),
+            Self::String(value) => Unexpected::Str(value),
+            Self::Array(_) => Unexpected::Seq,
+            Self::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl Serialize for ConfigValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_none(),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Integer(value) => {
+                if let Ok(value) = i64::try_from(*value) {
+                    serializer.serialize_i64(value)
+                } else if let Ok(value) = u64::try_from(*value) {
+                    serializer.serialize_u64(value)
+                } else {
+                    serializer.serialize_i128(*value)
+                }
+            }
+            Self::Float(value) => serializer.serialize_f64(*value),
+            Self::String(value) => serializer.serialize_str(value),
+            Self::Array(values) => values.serialize(serializer),
+            Self::Map(_) if self.as_datetime().is_some() => {
+                use ser::SerializeStruct;
+                let mut datetime = serializer.serialize_struct(DATETIME_NAME, 1)?;
+                datetime.serialize_field(DATETIME_FIELD, self.as_datetime().unwrap_or_default())?;
+                datetime.end()
+            }
+            Self::Map(map) => {
+                // Formats like TOML need tables, and arrays of tables, to
+                // come after plain values
+                let is_table = |value: &ConfigValue| match value {
+                    Self::Map(_) => value.as_datetime().is_none(),
+                    Self::Array(values) => values.first().is_some_and(|value| {
+                        value.as_map().is_some() && value.as_datetime().is_none()
+                    }),
+                    _ => false,
+                };
+                let (tables, values): (Vec<_>, Vec<_>) =
+                    map.iter().partition(|(_, value)| is_table(value));
+                serializer.collect_map(values.into_iter().chain(tables))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = ConfigValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("any configuration value")
+            }
+
+            fn visit_bool<E>(self, value: bool) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::Bool(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::Integer(value.into()))
+            }
+
+            fn visit_i128<E>(self, value: i128) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::Integer(value))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::Integer(value.into()))
+            }
+
+            fn visit_u128<E: de::Error>(self, value: u128) -> Result<ConfigValue, E> {
+                i128::try_from(value)
+                    .map(ConfigValue::Integer)
+                    .map_err(|_| E::custom("integer is too large"))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::Float(value))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::String(value.to_string()))
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::String(value))
+            }
+
+            fn visit_none<E>(self) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::Null)
+            }
+
+            fn visit_some<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<ConfigValue, D::Error> {
+                ConfigValue::deserialize(deserializer)
+            }
+
+            fn visit_unit<E>(self) -> Result<ConfigValue, E> {
+                Ok(ConfigValue::Null)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<ConfigValue, A::Error> {
+                let mut values = Vec::with_capacity(access.size_hint().unwrap_or(0));
+                while let Some(value) = access.next_element()? {
+                    values.push(value);
+                }
+                Ok(ConfigValue::Array(values))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<ConfigValue, A::Error> {
+                let mut map = Map::new();
+                while let Some((key, value)) = access.next_entry()? {
+                    map.insert(key, value);
+                }
+                // XML elements only holding text are read as a `$text` entry
+                if map.len() == 1 {
+                    if let Some(text) = remove(&mut map, "$text") {
+                        return Ok(text);
+                    }
+                }
+                #[cfg(feature = "arbitrary_precision")]
+                if let (1, Some(ConfigValue::String(number))) = (map.len(), map.get(JSON_NUMBER)) {
+                    return json_number(number);
+                }
+                Ok(ConfigValue::Map(map))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Parse the JSON @number read with arbitrary precision, failing on integers
+/// which don't fit rather than rounding them
+#[cfg(feature = "arbitrary_precision")]
+fn json_number<E: de::Error>(number: &str) -> Result<ConfigValue, E> {
+    if let Ok(integer) = number.parse() {
+        return Ok(ConfigValue::Integer(integer));
+    }
+    if !number.contains(['.', 'e', 'E']) {
+        return Err(E::custom(format!("integer {number} is too large")));
+    }
+    number
+        .parse()
+        .map(ConfigValue::Float)
+        .map_err(|_| E::custom(format!("invalid number {number}")))
+}
+
+/// A part of a path given to [`ConfigValue::get`]
+enum Segment<'a> {
+    Key(Cow<'a, str>),
+    Index(usize),
+    /// A JSON Pointer reference token, either a key or an index depending on
+    /// the value it applies to
+    Token(Cow<'a, str>),
+    /// Any element of an array, only given to [`ConfigValue::select`]
+    Elements,
+}
+
+/// Split @path into keys and array indices
+fn parse_path(path: &str) -> Result<Vec<Segment<'_>>, ValueError> {
+    let invalid = || ValueError(format!("invalid path {path}"));
+    let mut segments = Vec::new();
+    if path.is_empty() {
+        return Ok(segments);
+    }
+    if let Some(pointer) = path.strip_prefix('/') {
+        for token in pointer.split('/') {
+            let token = match token.contains('~') {
+                true => Cow::Owned(unescape(token).ok_or_else(invalid)?),
+                false => Cow::Borrowed(token),
+            };
+            segments.push(Segment::Token(token));
+        }
+        return Ok(segments);
+    }
+    for part in path.split('.') {
+        let (key, mut indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !key.is_empty() {
+            segments.push(Segment::Key(Cow::Borrowed(key)));
+        } else if indices.is_empty() {
+            return Err(invalid());
+        }
+        while !indices.is_empty() {
+            let (index, rest) = indices
+                .strip_prefix('[')
+                .and_then(|indices| indices.split_once(']'))
+                .ok_or_else(invalid)?;
+            segments.push(match index {
+                "*" => Segment::Elements,
+                index => Segment::Index(index.parse().map_err(|_| invalid())?),
+            });
+            indices = rest;
+        }
+    }
+    Ok(segments)
+}
+
+/// The values of @value, located at @path, matched by @segment, with their
+/// own paths
+fn select<'a>(
+    segment: &Segment<'_>,
+    path: String,
+    value: &'a ConfigValue,
+) -> Vec<(String, &'a ConfigValue)> {
+    let key = |key: &str| match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{path}.{key}"),
+    };
+    match (segment, value) {
+        (Segment::Key(name), ConfigValue::Map(map)) if name == "*" => {
+            map.iter().map(|(name, value)| (key(name), value)).collect()
+        }
+        (Segment::Key(name) | Segment::Token(name), ConfigValue::Map(map)) => map
+            .get(name.as_ref())
+            .map(|value| (key(name), value))
+            .into_iter()
+            .collect(),
+        (Segment::Index(index), ConfigValue::Array(values)) => values
+            .get(*index)
+            .map(|value| (format!("{path}[{index}]"), value))
+            .into_iter()
+            .collect(),
+        (Segment::Token(token), ConfigValue::Array(_)) => match index(token) {
+            Some(index) => select(&Segment::Index(index), path, value),
+            None => Vec::new(),
+        },
+        (Segment::Elements, ConfigValue::Array(values)) => values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (format!("{path}[{index}]"), value))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Decode the `~0` and `~1` escapes of a JSON Pointer @token
+fn unescape(token: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(match c {
+            '~' => match chars.next()? {
+                '0' => '~',
+                '1' => '/',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(unescaped)
+}
+
+/// Parse the JSON Pointer array index @token, which has no leading zeros
+fn index(token: &str) -> Option<usize> {
+    let canonical = token == "0" || !token.starts_with('0');
+    match canonical && token.bytes().all(|b| b.is_ascii_digit()) {
+        true => token.parse().ok(),
+        false => None,
+    }
+}
+
+impl From<bool> for ConfigValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for ConfigValue {
+    fn from(value: i64) -> Self {
+        Self::Integer(value.into())
+    }
+}
+
+impl From<f64> for ConfigValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for ConfigValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for ConfigValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<Vec<ConfigValue>> for ConfigValue {
+    fn from(values: Vec<ConfigValue>) -> Self {
+        Self::Array(values)
+    }
+}
+
+impl From<BTreeMap<String, ConfigValue>> for ConfigValue {
+    fn from(map: BTreeMap<String, ConfigValue>) -> Self {
+        Self::Map(map.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl From<Map> for ConfigValue {
+    fn from(map: Map) -> Self {
+        Self::Map(map)
+    }
+}
+
+/// Serializer producing a [`ConfigValue`]
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, value: bool) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Bool(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Integer(value.into()))
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<ConfigValue, ValueError> {
+        i128::try_from(value)
+            .map(ConfigValue::Integer)
+            .map_err(|_| ValueError("integer is too large".into()))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Float(value.into()))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Float(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::String(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Array(
+            value
+                .iter()
+                .map(|byte| ConfigValue::Integer((*byte).into()))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<ConfigValue, ValueError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<ConfigValue, ValueError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<ConfigValue, ValueError> {
+        let mut map = Map::new();
+        map.insert(variant.to_string(), to_value(value)?);
+        Ok(ConfigValue::Map(map))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, ValueError> {
+        Ok(SerializeArray(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeArray>, ValueError> {
+        Ok(SerializeVariant(variant, self.serialize_seq(Some(len))?))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<SerializeMap, ValueError> {
+        Ok(SerializeMap(Map::new(), None))
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<SerializeMap, ValueError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeMap>, ValueError> {
+        Ok(SerializeVariant(variant, self.serialize_map(Some(len))?))
+    }
+}
+
+struct SerializeArray(Vec<ConfigValue>);
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Array(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<ConfigValue, ValueError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<ConfigValue, ValueError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeMap(Map, Option<String>);
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), ValueError> {
+        self.1 = Some(match to_value(key)? {
+            ConfigValue::String(key) => key,
+            ConfigValue::Bool(key) => key.to_string(),
+            ConfigValue::Integer(key) => key.to_string(),
+            ConfigValue::Float(key) => key.to_string(),
+            _ => return Err(ValueError("map keys must be strings".into())),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        let key = self
+            .1
+            .take()
+            .ok_or_else(|| ValueError("map value without a key".into()))?;
+        self.0.insert(key, to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<ConfigValue, ValueError> {
+        Ok(ConfigValue::Map(self.0))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.0.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<ConfigValue, ValueError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Serialize an enum variant holding data as a single entry map
+struct SerializeVariant<S>(&'static str, S);
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        ser::SerializeSeq::serialize_element(&mut self.1, value)
+    }
+
+    fn end(self) -> Result<ConfigValue, ValueError> {
+        let mut map = Map::new();
+        map.insert(self.0.to_string(), ser::SerializeSeq::end(self.1)?);
+        Ok(ConfigValue::Map(map))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = ConfigValue;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        ser::SerializeStruct::serialize_field(&mut self.1, key, value)
+    }
+
+    fn end(self) -> Result<ConfigValue, ValueError> {
+        let mut map = Map::new();
+        map.insert(self.0.to_string(), ser::SerializeMap::end(self.1)?);
+        Ok(ConfigValue::Map(map))
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+                match self {
+                    Self::String(value) => match value.trim().parse::<i128>() {
+                        Ok(number) => visit_integer(number, visitor),
+                        Err(_) => match value.trim().parse::<f64>() {
+                            Ok(number) => visitor.visit_f64(number),
+                            Err(_) => Err(de::Error::invalid_type(Unexpected::Str(&value), &visitor)),
+                        },
+                    },
+                    value => value.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+fn visit_integer<'de, V: Visitor<'de>>(value: i128, visitor: V) -> Result<V::Value, ValueError> {
+    if let Ok(value) = i64::try_from(value) {
+        visitor.visit_i64(value)
+    } else if let Ok(value) = u64::try_from(value) {
+        visitor.visit_u64(value)
+    } else {
+        visitor.visit_i128(value)
+    }
+}
+
+impl<'de> Deserializer<'de> for ConfigValue {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Self::Null => visitor.visit_unit(),
+            Self::Bool(value) => visitor.visit_bool(value),
+            Self::Integer(value) => visit_integer(value, visitor),
+            Self::Float(value) => visitor.visit_f64(value),
+            Self::String(value) => visitor.visit_string(value),
+            Self::Array(values) => visitor.visit_seq(ArrayAccess(values.into_iter())),
+            Self::Map(map) => visitor.visit_map(MapAccessor(map.into_iter(), None)),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Self::String(value) => match value.trim().parse() {
+                Ok(value) => visitor.visit_bool(value),
+                Err(_) => Err(de::Error::invalid_type(Unexpected::Str(&value), &visitor)),
+            },
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64, deserialize_i128,
+        deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64, deserialize_u128,
+        deserialize_f32, deserialize_f64,
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        if let Some(datetime) = self.as_datetime() {
+            return visitor.visit_str(datetime);
+        }
+        match self {
+            Self::Bool(value) => visitor.visit_string(value.to_string()),
+            Self::Integer(value) => visitor.visit_string(value.to_string()),
+            Self::Float(value) => visitor.visit_string(value.to_string()),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Self::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            // Formats like XML can't tell a single element array from its element
+            Self::Array(_) | Self::Null => self.deserialize_any(visitor),
+            value => visitor.visit_seq(ArrayAccess(vec![value].into_iter())),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        match self {
+            Self::String(variant) => visitor.visit_enum(EnumAccessor(variant, None)),
+            Self::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.into_iter().next().unwrap_or_default();
+                visitor.visit_enum(EnumAccessor(variant, Some(value)))
+            }
+            value => Err(de::Error::invalid_type(value.unexpected(), &"an enum")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, ValueError> for ConfigValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct ArrayAccess(std::vec::IntoIter<ConfigValue>);
+
+impl<'de> SeqAccess<'de> for ArrayAccess {
+    type Error = ValueError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ValueError> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(value))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct MapAccessor(<Map as IntoIterator>::IntoIter, Option<ConfigValue>);
+
+impl<'de> MapAccess<'de> for MapAccessor {
+    type Error = ValueError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ValueError> {
+        match self.0.next() {
+            Some((key, value)) => {
+                self.1 = Some(value);
+                seed.deserialize(ConfigValue::String(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ValueError> {
+        seed.deserialize(self.1.take().unwrap_or_default())
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct EnumAccessor(String, Option<ConfigValue>);
+
+impl<'de> EnumAccess<'de> for EnumAccessor {
+    type Error = ValueError;
+    type Variant = VariantAccessor;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantAccessor), ValueError> {
+        let deserializer: StringDeserializer<ValueError> = self.0.into_deserializer();
+        Ok((seed.deserialize(deserializer)?, VariantAccessor(self.1)))
+    }
+}
+
+struct VariantAccessor(Option<ConfigValue>);
+
+impl<'de> VariantAccess<'de> for VariantAccessor {
+    type Error = ValueError;
+
+    fn unit_variant(self) -> Result<(), ValueError> {
+        match self.0 {
+            None | Some(ConfigValue::Null) => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(value.unexpected(), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, ValueError> {
+        seed.deserialize(self.0.unwrap_or_default())
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, ValueError> {
+        self.0.unwrap_or_default().deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.0.unwrap_or_default().deserialize_map(visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::TestConfig;
+
+    #[test]
+    fn test_round_trip() {
+        let value = to_value(&TestConfig::example()).unwrap();
+        assert_eq!(value.as_map().unwrap()["port"], ConfigValue::Integer(443));
+        assert_eq!(
+            from_value::<TestConfig>(value).unwrap(),
+            TestConfig::example()
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        let mut value = to_value(&TestConfig::example()).unwrap();
+        let map = value.as_map_mut().unwrap();
+        map.insert("port".into(), "443".into());
+        map.insert(
+            "tags".into(),
+            ConfigValue::Array(vec!["example".into(), "test".into()]),
+        );
+        assert_eq!(
+            from_value::<TestConfig>(value).unwrap(),
+            TestConfig::example()
+        );
+    }
+
+    #[test]
+    fn test_strip_defaults() {
+        let defaults = to_value(&TestConfig::example()).unwrap();
+        let mut value = defaults.clone();
+        let map = value.as_map_mut().unwrap();
+        map.insert("port".into(), ConfigValue::Integer(8080));
+        value.strip_defaults(&defaults);
+        let expected = BTreeMap::from([("port".to_string(), ConfigValue::Integer(8080))]);
+        assert_eq!(value, ConfigValue::from(expected));
+    }
+
+    #[test]
+    fn test_get_set() {
+        let mut value = to_value(&TestConfig::example()).unwrap();
+        assert_eq!(value.get("inner.answer"), Some(&ConfigValue::Integer(42)));
+        assert_eq!(value.get("tags[1]"), Some(&"test".into()));
+        assert_eq!(value.get(""), Some(&value));
+        assert!(value.get("tags[2]").is_none());
+        assert!(value.get("tags[").is_none());
+        assert!(value.get("host.name").is_none());
+
+        value.set("tags[2]", "new".into()).unwrap();
+        value.set("inner.answer", 0.into()).unwrap();
+        *value.get_mut("port").unwrap() = 8080.into();
+        let config: TestConfig = from_value(value.clone()).unwrap();
+        assert_eq!(config.tags, ["example", "test", "new"]);
+        assert_eq!(config.inner.answer, 0);
+        assert_eq!(config.port, 8080);
+        assert!(value.set("tags[4]", "new".into()).is_err());
+        assert!(value.set("host[0]", "new".into()).is_err());
+        assert!(value.set("inner..answer", 0.into()).is_err());
+
+        let mut matrix = ConfigValue::default();
+        matrix.set("rows[0][0]", 1.into()).unwrap();
+        assert_eq!(matrix.get("rows[0][0]"), Some(&ConfigValue::Integer(1)));
+    }
+
+    #[test]
+    fn test_pointer() {
+        let mut value = to_value(&TestConfig::example()).unwrap();
+        assert_eq!(value.get("/inner/answer"), Some(&ConfigValue::Integer(42)));
+        assert_eq!(value.get("/tags/1"), Some(&"test".into()));
+        assert!(value.get("/tags/01").is_none());
+        assert!(value.get("/tags/-").is_none());
+        assert!(value.get("/port/0").is_none());
+
+        value.set("/tags/-", "new".into()).unwrap();
+        value.set("/a~1b/c~0d", true.into()).unwrap();
+        assert_eq!(value.get("tags[2]"), Some(&"new".into()));
+        assert_eq!(value.get("a/b.c~d"), Some(&true.into()));
+        assert!(value.set("/tags/x", "new".into()).is_err());
+        assert!(value.set("/tags/5", "new".into()).is_err());
+        assert!(value.set("/bad~2", "new".into()).is_err());
+    }
+
+    #[test]
+    fn test_large_integers() {
+        assert_eq!(
+            to_value(&i128::MIN).unwrap(),
+            ConfigValue::Integer(i128::MIN)
+        );
+        assert!(to_value(&u128::MAX).is_err());
+        #[cfg(feature = "toml")]
+        assert!(crate::ConfigFormat::Toml.serialize(&u128::MAX).is_err());
+        #[cfg(feature = "arbitrary_precision")]
+        {
+            let format = crate::ConfigFormat::Json;
+            let value: ConfigValue = format
+                .deserialize(b"[-170141183460469231731687303715884105728, 1.5]")
+                .unwrap();
+            assert_eq!(
+                value,
+                ConfigValue::Array(vec![
+                    ConfigValue::Integer(i128::MIN),
+                    ConfigValue::Float(1.5)
+                ])
+            );
+            assert_eq!(
+                format
+                    .deserialize::<ConfigValue>(&format.serialize(&value).unwrap())
+                    .unwrap(),
+                value
+            );
+            assert!(format
+                .deserialize::<ConfigValue>(u128::MAX.to_string().as_bytes())
+                .is_err());
+        }
+    }
+
+    #[cfg(all(
+        feature = "preserve_order",
+        any(feature = "json", feature = "toml", feature = "yaml")
+    ))]
+    #[test]
+    fn test_preserve_order() {
+        let mut documents: Vec<(crate::ConfigFormat, &str)> = Vec::new();
+        #[cfg(feature = "json")]
+        documents.push((crate::ConfigFormat::Json, r#"{"b":1,"a":{"d":2,"c":3}}"#));
+        #[cfg(feature = "toml")]
+        documents.push((crate::ConfigFormat::Toml, "b = 1\n\n[a]\nd = 2\nc = 3\n"));
+        #[cfg(feature = "yaml")]
+        documents.push((crate::ConfigFormat::Yaml, "---\nb: 1\na:\n  d: 2\n  c: 3\n"));
+        for (format, document) in documents {
+            let mut value: ConfigValue = format.deserialize(document.as_bytes()).unwrap();
+            value.set("a.d", 4.into()).unwrap();
+            remove(value.as_map_mut().unwrap(), "b");
+            value.set("b", 1.into()).unwrap();
+            let keys =
+                |value: &ConfigValue| value.as_map().unwrap().keys().cloned().collect::<Vec<_>>();
+            assert_eq!(keys(&value), ["a", "b"]);
+            assert_eq!(keys(value.get("a").unwrap()), ["d", "c"]);
+            let stored = format.serialize(&value).unwrap();
+            let value: ConfigValue = format.deserialize(&stored).unwrap();
+            assert_eq!(keys(value.get("a").unwrap()), ["d", "c"]);
+        }
+    }
+
+    #[test]
+    fn test_coercion() {
+        assert_eq!(ConfigValue::from(" Yes ").as_bool_lenient(), Some(true));
+        assert_eq!(ConfigValue::from("off").as_bool_lenient(), Some(false));
+        assert_eq!(ConfigValue::from(1).as_bool_lenient(), Some(true));
+        assert_eq!(ConfigValue::from(2).as_bool_lenient(), None);
+        assert_eq!(ConfigValue::from("maybe").as_bool_lenient(), None);
+
+        assert_eq!(ConfigValue::from("8080").as_int_lenient(), Some(8080));
+        assert_eq!(ConfigValue::from(true).as_int_lenient(), Some(1));
+        assert_eq!(ConfigValue::from(3.0).as_int_lenient(), Some(3));
+        assert_eq!(ConfigValue::from(3.5).as_int_lenient(), None);
+        assert_eq!(ConfigValue::from("3.5").as_int_lenient(), None);
+
+        assert_eq!(ConfigValue::from("3.5").as_float_lenient(), Some(3.5));
+        assert_eq!(ConfigValue::from(2).as_float_lenient(), Some(2.0));
+        assert_eq!(ConfigValue::from(true).as_float_lenient(), None);
+
+        assert_eq!(ConfigValue::from(8080).as_str_lenient().unwrap(), "8080");
+        assert_eq!(ConfigValue::from("host").as_str_lenient().unwrap(), "host");
+        assert!(ConfigValue::Null.as_str_lenient().is_none());
+    }
+
+    #[test]
+    fn test_select() {
+        let value = to_value(&TestConfig::example()).unwrap();
+        let tags = value.select("tags[*]").unwrap();
+        assert_eq!(
+            tags,
+            [
+                ("tags[0]".to_string(), &"example".into()),
+                ("tags[1]".to_string(), &"test".into())
+            ]
+        );
+        let answers = value.select("*.answer").unwrap();
+        assert_eq!(
+            answers,
+            [("inner.answer".to_string(), &ConfigValue::Integer(42))]
+        );
+        assert_eq!(value.select("").unwrap(), [(String::new(), &value)]);
+        assert_eq!(value.select("/tags/1").unwrap(), [tags[1].clone()]);
+        assert!(value.select("port[*]").unwrap().is_empty());
+        assert!(value.select("tags[").is_err());
+    }
+}
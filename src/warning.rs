@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// Something suspicious noticed while loading a configuration, which didn't
+/// prevent it from loading
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// A key was found under a deprecated alias and renamed to its canonical
+    /// name
+    DeprecatedKey {
+        /// The deprecated name found in the file
+        alias: String,
+        /// The canonical name
+        key: String,
+    },
+    /// A layer tried to override a key locked by a previous one, and was
+    /// ignored
+    LockedKey {
+        /// The dotted path of the locked key
+        key: String,
+        /// The name of the layer which tried to override it
+        layer: String,
+        /// The name of the layer which locked it
+        locked_by: String,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeprecatedKey { alias, key } => {
+                write!(f, "`{alias}` is deprecated, use `{key}` instead")
+            }
+            Self::LockedKey {
+                key,
+                layer,
+                locked_by,
+            } => write!(
+                f,
+                "`{key}` is locked by {locked_by}, ignoring it in {layer}"
+            ),
+        }
+    }
+}
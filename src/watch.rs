@@ -0,0 +1,1424 @@
+//! Watch configuration files and reload them when they change.
+
+#[cfg(feature = "async")]
+use std::{
+    error::Error,
+    future::{poll_fn, Future},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    ffi::OsString,
+    io::ErrorKind,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "async")]
+use futures_core::Stream;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "async")]
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+#[cfg(feature = "async")]
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+use crate::{format::ConfigFormat, load_dir, read_file, write_file, ConfigFileError, ConfigValue};
+
+/// How long a watched file has to stay untouched after changing before it
+/// is reloaded, unless specified otherwise with [`WatchOptions::debounce`]
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Options controlling how a configuration file is watched
+#[derive(Debug, Clone, Default)]
+pub struct WatchOptions {
+    quarantine_after: Option<NonZeroU32>,
+    debounce: Option<Duration>,
+    #[cfg(feature = "async")]
+    cancellation: Option<CancellationToken>,
+}
+
+impl WatchOptions {
+    /// Create the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Quarantine the file once it failed to parse or validate @failures
+    /// consecutive times. Files which are removed or can't be read don't
+    /// count as failures.
+    ///
+    /// The broken file is moved aside to `<name>.broken-<timestamp>`, in
+    /// milliseconds since the Unix epoch, and the last content which loaded
+    /// successfully is written back in its place, which is then reloaded.
+    /// Nothing happens if the file never loaded successfully since there is
+    /// nothing to restore.
+    pub fn quarantine_after(mut self, failures: NonZeroU32) -> Self {
+        self.quarantine_after = Some(failures);
+        self
+    }
+
+    /// Wait for the file to stay untouched for @window after changing before
+    /// reloading it, so that the several events caused by saving it once,
+    /// such as editors writing a new file then renaming it over the old one,
+    /// trigger a single reload. A zero @window reloads on every event.
+    ///
+    /// This defaults to [`DEFAULT_DEBOUNCE`].
+    pub fn debounce(mut self, window: Duration) -> Self {
+        self.debounce = Some(window);
+        self
+    }
+
+    /// Stop watching as soon as @token is cancelled: the stream then ends
+    /// without yielding the values it had buffered
+    #[cfg(feature = "async")]
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+}
+
+/// Watch the configuration file located at @path.
+///
+/// The returned stream first yields the current content of the file, then a
+/// freshly deserialized value every time the file changes. A change leaving
+/// the file unreadable or invalid yields an error, and the stream keeps
+/// watching for the next change.
+///
+/// ```rust,no_run
+/// # async fn run() {
+/// use config_file::watch::watch_config;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config {}
+///
+/// let mut stream = watch_config::<Config>("/etc/myconfig.toml").unwrap();
+/// while let Some(config) = stream.recv().await {
+///     // apply config
+/// }
+/// # }
+/// ```
+#[cfg(feature = "async")]
+pub fn watch_config<T>(path: impl AsRef<Path>) -> Result<ConfigStream<T>, ConfigFileError>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    watch_config_with_options(path, WatchOptions::default())
+}
+
+/// Watch the configuration file located at @path using @options.
+///
+/// See [`watch_config`] for details. When the file gets quarantined, the
+/// stream yields a [`ConfigFileError::Quarantined`] error holding the path the
+/// broken file was moved to.
+#[cfg(feature = "async")]
+pub fn watch_config_with_options<T>(
+    path: impl AsRef<Path>,
+    options: WatchOptions,
+) -> Result<ConfigStream<T>, ConfigFileError>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    watch(path.as_ref(), options, |reloader, path| {
+        Some(reloader.reload(path))
+    })
+}
+
+/// Watch the configuration file located at @path, describing what happens to
+/// it rather than yielding its content.
+///
+/// The returned stream first yields [`WatchEvent::Loaded`], or
+/// [`WatchEvent::Invalid`] if the file can't be loaded, then an event every
+/// time the file changes. It only ends once closed.
+#[cfg(feature = "async")]
+pub fn watch_events(path: impl AsRef<Path>) -> Result<ConfigStream<WatchEvent>, ConfigFileError> {
+    watch_events_with_options(path, WatchOptions::default())
+}
+
+/// Watch the configuration file located at @path using @options, describing
+/// what happens to it as [`watch_events`] does
+#[cfg(feature = "async")]
+pub fn watch_events_with_options(
+    path: impl AsRef<Path>,
+    options: WatchOptions,
+) -> Result<ConfigStream<WatchEvent>, ConfigFileError> {
+    watch(path.as_ref(), options, |reloader, path| {
+        Some(Ok(reloader.event(path)))
+    })
+}
+
+/// Watch the configuration file located at @path, yielding a
+/// [`ChangeEvent`] every time it changes.
+///
+/// Unlike [`watch_events`], nothing is yielded for the current content of
+/// the file. See [`ChangeEvents`] to receive the same events without an
+/// async runtime.
+///
+/// ```rust,no_run
+/// # async fn run() {
+/// use config_file::watch::{watch_changes, ChangeEvent};
+///
+/// let mut stream = watch_changes("/etc/myconfig.toml").unwrap();
+/// while let Some(Ok(event)) = stream.recv().await {
+///     if let ChangeEvent::ParseFailed(err) = event {
+///         eprintln!("config is broken: {err}");
+///     }
+/// }
+/// # }
+/// ```
+#[cfg(feature = "async")]
+pub fn watch_changes(path: impl AsRef<Path>) -> Result<ConfigStream<ChangeEvent>, ConfigFileError> {
+    watch_changes_with_options(path, WatchOptions::default())
+}
+
+/// Watch the configuration file located at @path using @options, yielding
+/// a [`ChangeEvent`] every time it changes as [`watch_changes`] does
+#[cfg(feature = "async")]
+pub fn watch_changes_with_options(
+    path: impl AsRef<Path>,
+    options: WatchOptions,
+) -> Result<ConfigStream<ChangeEvent>, ConfigFileError> {
+    watch(path.as_ref(), options, |reloader, path| {
+        reloader.change(path).map(Ok)
+    })
+}
+
+/// Watch the file located at @path, producing the values of the returned
+/// stream with @produce, which may skip some changes
+#[cfg(feature = "async")]
+fn watch<T, F>(
+    path: &Path,
+    options: WatchOptions,
+    mut produce: F,
+) -> Result<ConfigStream<T>, ConfigFileError>
+where
+    T: Send + 'static,
+    F: FnMut(&mut Reloader, &Path) -> Option<Result<T, ConfigFileError>> + Send + 'static,
+{
+    let path = path.to_path_buf();
+    let cancelled = options
+        .cancellation
+        .clone()
+        .map(|token| Box::pin(token.cancelled_owned()));
+    let debounce = options.debounce.unwrap_or(DEFAULT_DEBOUNCE);
+    let mut reloader = Reloader::new(&path, options)?;
+    let (sender, receiver) = unbounded_channel();
+    if let Some(value) = produce(&mut reloader, &path) {
+        let _ = sender.send(value);
+    }
+    let watcher = watch_file(&path, debounce, move |path| {
+        if let Some(value) = produce(&mut reloader, path) {
+            let _ = sender.send(value);
+        }
+    })?;
+    Ok(ConfigStream {
+        receiver,
+        watcher: Some(watcher),
+        cancelled,
+    })
+}
+
+/// Something which happened to a watched configuration file.
+///
+/// Events can be serialized to forward them to logs, metrics or user
+/// interfaces.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchEvent {
+    /// The file was loaded for the first time
+    Loaded {
+        /// Path of the file
+        path: PathBuf,
+    },
+    /// The file was loaded again after changing
+    Reloaded {
+        /// Path of the file
+        path: PathBuf,
+        /// What changed since it was last loaded
+        diff: Vec<Change>,
+    },
+    /// The file couldn't be loaded
+    Invalid {
+        /// Path of the file
+        path: PathBuf,
+        /// Why it couldn't be loaded
+        error: String,
+    },
+    /// The file was removed
+    Removed {
+        /// Path of the file
+        path: PathBuf,
+    },
+}
+
+/// A change to a watched configuration file, as yielded by [`ChangeEvents`]
+/// and [`watch_changes`]
+#[derive(Debug)]
+pub enum ChangeEvent {
+    /// The file was created, and can be loaded
+    Created(PathBuf),
+    /// The file was modified, and can still be loaded
+    Modified(PathBuf),
+    /// The file was removed
+    Removed(PathBuf),
+    /// The file was created or modified, but can't be loaded anymore
+    ParseFailed(ConfigFileError),
+}
+
+/// Blocking iterator over the changes to a configuration file, waiting for
+/// the next [`ChangeEvent`] every time it is advanced.
+///
+/// The iterator never ends, and dropping it stops watching the file.
+///
+/// ```rust,no_run
+/// use config_file::watch::{ChangeEvent, ChangeEvents};
+///
+/// for event in ChangeEvents::new("/etc/myconfig.toml").unwrap() {
+///     match event {
+///         ChangeEvent::ParseFailed(err) => eprintln!("config is broken: {err}"),
+///         event => println!("config changed: {event:?}"),
+///     }
+/// }
+/// ```
+pub struct ChangeEvents {
+    receiver: Receiver<ChangeEvent>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ChangeEvents {
+    /// Watch the configuration file located at @path
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        Self::with_options(path, WatchOptions::default())
+    }
+
+    /// Watch the configuration file located at @path using @options
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        options: WatchOptions,
+    ) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+        let debounce = options.debounce.unwrap_or(DEFAULT_DEBOUNCE);
+        let mut reloader = Reloader::new(path, options)?;
+        reloader.change(path);
+        let (sender, receiver) = channel();
+        let watcher = watch_file(path, debounce, move |path| {
+            if let Some(event) = reloader.change(path) {
+                let _ = sender.send(event);
+            }
+        })?;
+        Ok(Self {
+            receiver,
+            _watcher: watcher,
+        })
+    }
+
+    /// The next change, if one happened since the last call, without
+    /// waiting for it
+    pub fn try_next(&mut self) -> Option<ChangeEvent> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// The next change, waiting at most @timeout for it
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<ChangeEvent> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for ChangeEvents {
+    type Item = ChangeEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// A value which changed between two loads of a configuration file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    /// Dotted path to the value, such as `server.port`
+    pub key: String,
+    /// The previous value, if any
+    pub old: Option<ConfigValue>,
+    /// The new value, if any
+    pub new: Option<ConfigValue>,
+}
+
+/// Compute the changes from @old to @new. Values nested in maps are compared
+/// key by key, while other values are compared as a whole.
+pub fn diff(old: &ConfigValue, new: &ConfigValue) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_into("", old, new, &mut changes);
+    changes
+}
+
+fn diff_into(prefix: &str, old: &ConfigValue, new: &ConfigValue, changes: &mut Vec<Change>) {
+    let (ConfigValue::Map(old), ConfigValue::Map(new)) = (old, new) else {
+        if old != new {
+            changes.push(Change {
+                key: prefix.to_string(),
+                old: Some(old.clone()),
+                new: Some(new.clone()),
+            });
+        }
+        return;
+    };
+    let key = |name: &str| match prefix {
+        "" => name.to_string(),
+        prefix => format!("{prefix}.{name}"),
+    };
+    for (name, old) in old {
+        match new.get(name) {
+            Some(new) => diff_into(&key(name), old, new, changes),
+            None => changes.push(Change {
+                key: key(name),
+                old: Some(old.clone()),
+                new: None,
+            }),
+        }
+    }
+    for (name, new) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+        changes.push(Change {
+            key: key(name),
+            old: None,
+            new: Some(new.clone()),
+        });
+    }
+}
+
+/// Stream of configuration values returned by [`watch_config`].
+///
+/// Dropping or [closing](ConfigStream::close) it stops watching the file.
+#[cfg(feature = "async")]
+pub struct ConfigStream<T> {
+    receiver: UnboundedReceiver<Result<T, ConfigFileError>>,
+    watcher: Option<RecommendedWatcher>,
+    cancelled: Option<Pin<Box<WaitForCancellationFutureOwned>>>,
+}
+
+#[cfg(feature = "async")]
+impl<T> ConfigStream<T> {
+    /// Receive the next value, or `None` once the stream is closed.
+    ///
+    /// This method is cancel safe: when used in `tokio::select!` and another
+    /// branch completes first, no value is lost.
+    ///
+    /// ```rust,no_run
+    /// # async fn run(token: tokio_util::sync::CancellationToken) {
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    /// let mut stream =
+    ///     config_file::watch::watch_config::<Config>("/etc/myconfig.toml").unwrap();
+    /// loop {
+    ///     tokio::select! {
+    ///         Some(config) = stream.recv() => { /* apply config */ }
+    ///         _ = token.cancelled() => break,
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn recv(&mut self) -> Option<Result<T, ConfigFileError>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Stop watching the file.
+    ///
+    /// No more values are produced once this returns, but those already
+    /// buffered are still yielded before the stream ends.
+    pub fn close(&mut self) {
+        self.watcher = None;
+        self.receiver.close();
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Stream for ConfigStream<T> {
+    type Item = Result<T, ConfigFileError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(cancelled) = &mut this.cancelled {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                this.cancelled = None;
+                this.close();
+                while this.receiver.try_recv().is_ok() {}
+                return Poll::Ready(None);
+            }
+        }
+        this.receiver.poll_recv(cx)
+    }
+}
+
+/// Watches a configuration file from a background thread, keeping its
+/// latest valid content at hand.
+///
+/// ```rust,no_run
+/// use config_file::watch::ConfigWatcher;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config {}
+///
+/// let watcher = ConfigWatcher::<Config>::new("/etc/myconfig.toml").unwrap();
+/// loop {
+///     let config = watcher.get();
+///     // use config
+///     if let Some(err) = watcher.take_error() {
+///         eprintln!("couldn't reload config: {err}");
+///     }
+/// #   break;
+/// }
+/// ```
+pub struct ConfigWatcher<T> {
+    shared: Arc<Shared<T>>,
+    _watcher: RecommendedWatcher,
+}
+
+/// State of a [`ConfigWatcher`] shared with its background thread
+struct Shared<T> {
+    latest: Mutex<Arc<T>>,
+    error: Mutex<Option<ConfigFileError>>,
+    /// Channel returned by [`ConfigWatcher::into_channel`], if any
+    #[cfg(feature = "async")]
+    channel: Mutex<Option<Arc<tokio::sync::watch::Sender<Arc<T>>>>>,
+    /// Handle returned by [`ConfigWatcher::hot`], if any
+    #[cfg(feature = "hot")]
+    hot: Mutex<Option<HotConfig<T>>>,
+    /// Callbacks registered with [`ConfigWatcher::on_change`]
+    callbacks: Mutex<Vec<Callback<T>>>,
+}
+
+type Callback<T> = Box<dyn FnMut(&T, &T) + Send>;
+
+impl<T: DeserializeOwned + Send + Sync + 'static> ConfigWatcher<T> {
+    /// Load the configuration file located at @path, and reload it every
+    /// time it changes
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        Self::with_options(path, WatchOptions::default())
+    }
+
+    /// Load the configuration file located at @path, and reload it every
+    /// time it changes according to @options
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        options: WatchOptions,
+    ) -> Result<Self, ConfigFileError> {
+        Self::with_validator(path, options, |_| Ok(()))
+    }
+
+    /// Load the configuration file located at @path, and reload it every
+    /// time it changes according to @options, as long as @validate accepts
+    /// its content.
+    ///
+    /// Content rejected by @validate is handled as content which can't be
+    /// parsed: the previous one is kept, and [`ConfigWatcher::take_error`]
+    /// returns a [`ConfigFileError::Validation`] error holding the message
+    /// @validate failed with.
+    ///
+    /// ```rust,no_run
+    /// use config_file::watch::{ConfigWatcher, WatchOptions};
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config { workers: usize }
+    ///
+    /// let watcher = ConfigWatcher::with_validator(
+    ///     "/etc/myconfig.toml",
+    ///     WatchOptions::new(),
+    ///     |config: &Config| match config.workers {
+    ///         0 => Err("workers must be positive".into()),
+    ///         _ => Ok(()),
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn with_validator(
+        path: impl AsRef<Path>,
+        options: WatchOptions,
+        validate: impl Fn(&T) -> Result<(), String> + Send + 'static,
+    ) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+        let debounce = options.debounce.unwrap_or(DEFAULT_DEBOUNCE);
+        let mut reloader = Reloader::new(path, options)?;
+        let shared = Shared::new(reloader.reload_validated(path, &validate)?);
+        let state = shared.clone();
+        let watcher = watch_file(path, debounce, move |path| {
+            state.update(reloader.reload_validated(path, &validate));
+        })?;
+        Ok(Self {
+            shared,
+            _watcher: watcher,
+        })
+    }
+
+    /// Load the configuration fragments located in the directory @dir, such
+    /// as `conf.d/`, and load them again every time one of them is added,
+    /// removed or modified.
+    ///
+    /// The files in a supported format are merged in lexical order, so later
+    /// ones override the values of earlier ones, and the directory may be
+    /// missing or empty as long as an empty configuration can be loaded.
+    ///
+    /// ```rust,no_run
+    /// use config_file::watch::ConfigWatcher;
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    ///
+    /// let watcher = ConfigWatcher::<Config>::dir("/etc/myapp/conf.d").unwrap();
+    /// ```
+    pub fn dir(dir: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        Self::dir_with_options(dir, WatchOptions::default())
+    }
+
+    /// Load the configuration fragments located in the directory @dir, and
+    /// load them again every time one of them changes according to
+    /// @options.
+    ///
+    /// Fragments are never quarantined, since the one to blame can't be told
+    /// apart from the others.
+    pub fn dir_with_options(
+        dir: impl AsRef<Path>,
+        options: WatchOptions,
+    ) -> Result<Self, ConfigFileError> {
+        let dir = absolute(dir.as_ref())?;
+        let debounce = options.debounce.unwrap_or(DEFAULT_DEBOUNCE);
+        let shared = Shared::new(load_dir(&dir)?);
+        let state = shared.clone();
+        let fragments = dir.clone();
+        let watcher = watch_dir(
+            &dir,
+            |changed| ConfigFormat::from_path(changed).is_some(),
+            debounce,
+            move || state.update(load_dir(&fragments)),
+        )?;
+        Ok(Self {
+            shared,
+            _watcher: watcher,
+        })
+    }
+}
+
+impl<T> Shared<T> {
+    fn new(config: T) -> Arc<Self> {
+        Arc::new(Self {
+            latest: Mutex::new(Arc::new(config)),
+            error: Mutex::new(None),
+            #[cfg(feature = "async")]
+            channel: Mutex::new(None),
+            #[cfg(feature = "hot")]
+            hot: Mutex::new(None),
+            callbacks: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Publish the @result of a reload
+    fn update(&self, result: Result<T, ConfigFileError>) {
+        match result {
+            Ok(config) => {
+                let config = Arc::new(config);
+                let previous = std::mem::replace(&mut *lock(&self.latest), config.clone());
+                #[cfg(feature = "hot")]
+                if let Some(hot) = &*lock(&self.hot) {
+                    hot.0.store(config.clone());
+                }
+                #[cfg(feature = "async")]
+                if let Some(sender) = &*lock(&self.channel) {
+                    sender.send_replace(config.clone());
+                }
+                for callback in lock(&self.callbacks).iter_mut() {
+                    callback(&previous, &config);
+                }
+            }
+            Err(err) => *lock(&self.error) = Some(err),
+        }
+    }
+}
+
+impl<T> ConfigWatcher<T> {
+    /// The latest content of the file which loaded successfully
+    pub fn get(&self) -> Arc<T> {
+        lock(&self.shared.latest).clone()
+    }
+
+    /// Take the error raised by the last reload, if it failed since the last
+    /// call. The previous content is kept when reloading fails.
+    pub fn take_error(&self) -> Option<ConfigFileError> {
+        lock(&self.shared.error).take()
+    }
+
+    /// Call @callback with the previous and the new content every time the
+    /// file is reloaded successfully, from the thread watching it.
+    ///
+    /// Callbacks are called in the order they were registered, and must not
+    /// register other callbacks.
+    ///
+    /// ```rust,no_run
+    /// use config_file::watch::ConfigWatcher;
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config { port: u16 }
+    ///
+    /// let watcher = ConfigWatcher::<Config>::new("/etc/myconfig.toml").unwrap();
+    /// watcher.on_change(|old, new| {
+    ///     if old.port != new.port {
+    ///         // restart the server
+    ///     }
+    /// });
+    /// ```
+    pub fn on_change(&self, callback: impl FnMut(&T, &T) + Send + 'static) {
+        lock(&self.shared.callbacks).push(Box::new(callback));
+    }
+
+    /// A handle on the latest content of the file which loaded successfully,
+    /// updated in place by every reload for as long as we live
+    #[cfg(feature = "hot")]
+    pub fn hot(&self) -> HotConfig<T> {
+        let mut hot = lock(&self.shared.hot);
+        // Reloads update the latest content before the handle, so none is
+        // missed while holding the lock
+        hot.get_or_insert_with(|| HotConfig::new(self.get()))
+            .clone()
+    }
+}
+
+/// A handle on the content of a configuration file kept up to date by a
+/// [`ConfigWatcher`], which threads can read on hot paths without locking.
+///
+/// ```rust,no_run
+/// use config_file::watch::ConfigWatcher;
+/// # #[derive(serde::Deserialize)]
+/// # struct Config { port: u16 }
+///
+/// let watcher = ConfigWatcher::<Config>::new("/etc/myconfig.toml").unwrap();
+/// let config = watcher.hot();
+/// std::thread::spawn(move || loop {
+///     let port = config.load().port;
+///     // serve on port
+/// #   break;
+/// });
+/// ```
+#[cfg(feature = "hot")]
+#[derive(Debug)]
+pub struct HotConfig<T>(Arc<arc_swap::ArcSwap<T>>);
+
+#[cfg(feature = "hot")]
+impl<T> HotConfig<T> {
+    /// Create a handle holding @config, which only changes when
+    /// [`HotConfig::store`] is called
+    pub fn new(config: impl Into<Arc<T>>) -> Self {
+        Self(Arc::new(arc_swap::ArcSwap::new(config.into())))
+    }
+
+    /// The current content, which is meant to be short lived: use
+    /// [`HotConfig::load_full`] to keep it around
+    pub fn load(&self) -> arc_swap::Guard<Arc<T>> {
+        self.0.load()
+    }
+
+    /// The current content
+    pub fn load_full(&self) -> Arc<T> {
+        self.0.load_full()
+    }
+
+    /// Replace the content with @config
+    pub fn store(&self, config: impl Into<Arc<T>>) {
+        self.0.store(config.into());
+    }
+}
+
+#[cfg(feature = "hot")]
+impl<T> Clone for HotConfig<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: Send + Sync + 'static> ConfigWatcher<T> {
+    /// Turn ourselves into a channel receiving the content of the file every
+    /// time it is reloaded successfully, so that tasks can wait for changes.
+    ///
+    /// The file is watched until every receiver is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a Tokio runtime.
+    ///
+    /// ```rust,no_run
+    /// # async fn run() {
+    /// use config_file::watch::ConfigWatcher;
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Config {}
+    ///
+    /// let watcher = ConfigWatcher::<Config>::new("/etc/myconfig.toml").unwrap();
+    /// let mut receiver = watcher.into_channel();
+    /// while receiver.changed().await.is_ok() {
+    ///     let config = receiver.borrow_and_update().clone();
+    ///     // apply config
+    /// }
+    /// # }
+    /// ```
+    pub fn into_channel(self) -> tokio::sync::watch::Receiver<Arc<T>> {
+        let mut channel = lock(&self.shared.channel);
+        // Reloads update the latest content before sending it, so none is
+        // missed while holding the lock
+        let (sender, receiver) = tokio::sync::watch::channel(self.get());
+        let sender = Arc::new(sender);
+        *channel = Some(sender.clone());
+        drop(channel);
+        tokio::spawn(async move {
+            sender.closed().await;
+            drop(self);
+        });
+        receiver
+    }
+}
+
+/// Lock @mutex, even if a thread panicked while holding it since the values
+/// we store are always consistent
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reload state shared across the changes of a watched file
+struct Reloader {
+    format: ConfigFormat,
+    options: WatchOptions,
+    /// Last content which loaded successfully
+    snapshot: Option<Vec<u8>>,
+    /// Number of consecutive failed loads
+    failures: u32,
+    /// Whether the file existed when last loaded, unless never loaded
+    exists: Option<bool>,
+    /// Last value which loaded successfully, to describe changes
+    #[cfg(feature = "async")]
+    previous: Option<ConfigValue>,
+}
+
+impl Reloader {
+    fn new(path: &Path, options: WatchOptions) -> Result<Self, ConfigFileError> {
+        Ok(Self {
+            format: ConfigFormat::from_path(path).ok_or(ConfigFileError::UnsupportedFormat)?,
+            options,
+            snapshot: None,
+            failures: 0,
+            exists: None,
+            #[cfg(feature = "async")]
+            previous: None,
+        })
+    }
+
+    #[cfg(feature = "async")]
+    fn reload<T: DeserializeOwned>(&mut self, path: &Path) -> Result<T, ConfigFileError> {
+        self.reload_validated(path, &|_| Ok(()))
+    }
+
+    /// Reload the file located at @path, considering it invalid when
+    /// @validate fails
+    fn reload_validated<T: DeserializeOwned>(
+        &mut self,
+        path: &Path,
+        validate: &dyn Fn(&T) -> Result<(), String>,
+    ) -> Result<T, ConfigFileError> {
+        // Only files which can be read but are invalid count as failures:
+        // removed files and errors reading them are reported as they are
+        let data = read_file(path)?;
+        let config = self.format.deserialize(&data).and_then(|config| {
+            validate(&config).map_err(ConfigFileError::Validation)?;
+            Ok(config)
+        });
+        match config {
+            Ok(config) => {
+                self.snapshot = Some(data);
+                self.failures = 0;
+                Ok(config)
+            }
+            Err(err) => {
+                self.failures += 1;
+                match (self.options.quarantine_after, &self.snapshot) {
+                    (Some(limit), Some(snapshot)) if self.failures >= limit.get() => {
+                        self.failures = 0;
+                        Err(quarantine(path, snapshot)?)
+                    }
+                    _ => Err(err),
+                }
+            }
+        }
+    }
+
+    /// Reload the file located at @path, describing how it changed since it
+    /// was last loaded, if it was
+    fn change(&mut self, path: &Path) -> Option<ChangeEvent> {
+        let result = self.reload_validated::<ConfigValue>(path, &|_| Ok(()));
+        let exists = !matches!(
+            &result,
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == ErrorKind::NotFound
+        );
+        let existed = self.exists.replace(exists)?;
+        let path = path.to_path_buf();
+        Some(match result {
+            Err(_) if !exists => match existed {
+                true => ChangeEvent::Removed(path),
+                false => return None,
+            },
+            Err(err) => ChangeEvent::ParseFailed(err),
+            Ok(_) if existed => ChangeEvent::Modified(path),
+            Ok(_) => ChangeEvent::Created(path),
+        })
+    }
+
+    #[cfg(feature = "async")]
+    fn event(&mut self, path: &Path) -> WatchEvent {
+        let path_buf = path.to_path_buf();
+        match self.reload::<ConfigValue>(path) {
+            Ok(value) => {
+                let event = match &self.previous {
+                    Some(previous) => WatchEvent::Reloaded {
+                        path: path_buf,
+                        diff: diff(previous, &value),
+                    },
+                    None => WatchEvent::Loaded { path: path_buf },
+                };
+                self.previous = Some(value);
+                event
+            }
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == ErrorKind::NotFound => {
+                WatchEvent::Removed { path: path_buf }
+            }
+            Err(err) => WatchEvent::Invalid {
+                path: path_buf,
+                error: error_chain(&err),
+            },
+        }
+    }
+}
+
+/// Render @err along with its sources
+#[cfg(feature = "async")]
+fn error_chain(err: &dyn Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(&format!(": {err}"));
+        source = err.source();
+    }
+    message
+}
+
+/// Move the broken file located at @path aside and restore @snapshot in its
+/// place. The timestamp is moved forward until the name is free, so that
+/// quarantining twice within a millisecond keeps both broken files.
+fn quarantine(path: &Path, snapshot: &[u8]) -> Result<ConfigFileError, ConfigFileError> {
+    let mut timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let broken = loop {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".broken-{timestamp}"));
+        let broken = path.with_file_name(name);
+        if !broken.exists() {
+            break broken;
+        }
+        timestamp += 1;
+    };
+    // Fails rather than restoring @snapshot if the file was removed meanwhile
+    std::fs::rename(path, &broken)?;
+    write_file(path, snapshot)?;
+    Ok(ConfigFileError::Quarantined(broken))
+}
+
+/// Call @on_change whenever the file located at @path is created, modified,
+/// replaced or removed, once it stayed untouched for @debounce.
+///
+/// The parent directory is watched rather than the file itself so that
+/// editors saving through a rename are noticed as well.
+fn watch_file(
+    path: &Path,
+    debounce: Duration,
+    mut on_change: impl FnMut(&Path) + Send + 'static,
+) -> Result<RecommendedWatcher, ConfigFileError> {
+    let path = absolute(path)?;
+    let name = path.file_name().map(OsString::from);
+    let parent = path.parent().unwrap_or(&path).to_path_buf();
+    watch_dir(
+        &parent,
+        move |changed| changed.file_name() == name.as_deref(),
+        debounce,
+        move || on_change(&path),
+    )
+}
+
+/// Call @on_change whenever a file located in @dir for which @matches holds
+/// is created, modified, replaced or removed, once the directory stayed
+/// untouched for @debounce
+fn watch_dir(
+    dir: &Path,
+    matches: impl Fn(&Path) -> bool + Send + 'static,
+    debounce: Duration,
+    mut on_change: impl FnMut() + Send + 'static,
+) -> Result<RecommendedWatcher, ConfigFileError> {
+    let (sender, receiver) = channel();
+    // The thread stops once the watcher, which owns the sender, is dropped
+    std::thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            if !debounce.is_zero() {
+                loop {
+                    match receiver.recv_timeout(debounce) {
+                        Ok(()) => {}
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+            }
+            on_change();
+        }
+    });
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_))
+            || !event.paths.iter().any(|changed| matches(changed))
+        {
+            return;
+        }
+        let _ = sender.send(());
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, ConfigFileError> {
+    Ok(std::env::current_dir()?.join(path))
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "toml")]
+    use std::{env::temp_dir, time::Duration};
+
+    use super::*;
+    use crate::test::TestConfig;
+    #[cfg(all(feature = "async", feature = "toml"))]
+    use crate::FromConfigFile;
+    #[cfg(feature = "toml")]
+    use crate::ToConfigFile;
+
+    #[cfg(feature = "async")]
+    async fn next<T>(stream: &mut ConfigStream<T>) -> Option<Result<T, ConfigFileError>> {
+        tokio::time::timeout(Duration::from_secs(5), stream.recv())
+            .await
+            .unwrap()
+    }
+
+    /// Poll @done until it holds, failing after 5 seconds
+    #[cfg(feature = "toml")]
+    fn wait(done: &dyn Fn() -> bool) {
+        for _ in 0..500 {
+            if done() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out waiting for reload");
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", feature = "toml"))]
+    async fn test_watch_config() {
+        let dir = temp_dir().join("config-file-watch-async");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let mut stream = watch_config::<TestConfig>(&path).unwrap();
+        assert_eq!(
+            next(&mut stream).await.unwrap().unwrap(),
+            TestConfig::example()
+        );
+
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        loop {
+            if let Ok(config) = next(&mut stream).await.unwrap() {
+                if config.port == 8080 {
+                    break;
+                }
+            }
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_quarantine_twice() {
+        let dir = temp_dir().join("config-file-watch-quarantine-twice");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let mut broken = Vec::new();
+        for content in ["first", "second"] {
+            std::fs::write(&path, content).unwrap();
+            match quarantine(&path, b"port = 443").unwrap() {
+                ConfigFileError::Quarantined(path) => broken.push(path),
+                err => panic!("unexpected error: {err}"),
+            }
+        }
+        assert_ne!(broken[0], broken[1]);
+        assert_eq!(std::fs::read_to_string(&broken[0]).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(&broken[1]).unwrap(), "second");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", feature = "toml"))]
+    async fn test_quarantine_removed() {
+        let dir = temp_dir().join("config-file-watch-quarantine-removed");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let options = WatchOptions::new().quarantine_after(NonZeroU32::MIN);
+        let mut stream = watch_config_with_options::<TestConfig>(&path, options).unwrap();
+        assert!(next(&mut stream).await.unwrap().is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            next(&mut stream).await.unwrap(),
+            Err(ConfigFileError::FileAccess(err)) if err.kind() == ErrorKind::NotFound
+        ));
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        drop(stream);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", feature = "toml"))]
+    async fn test_quarantine() {
+        let dir = temp_dir().join("config-file-watch-quarantine");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let options = WatchOptions::new().quarantine_after(NonZeroU32::MIN);
+        let mut stream = watch_config_with_options::<TestConfig>(&path, options).unwrap();
+        assert!(next(&mut stream).await.unwrap().is_ok());
+
+        let edited = dir.join("edited");
+        std::fs::write(&edited, "port = \"broken\"").unwrap();
+        std::fs::rename(edited, &path).unwrap();
+        let broken = loop {
+            if let Err(ConfigFileError::Quarantined(broken)) = next(&mut stream).await.unwrap() {
+                break broken;
+            }
+        };
+        assert_eq!(
+            std::fs::read_to_string(broken).unwrap(),
+            "port = \"broken\""
+        );
+        assert_eq!(
+            TestConfig::from_config_file(&path).unwrap(),
+            TestConfig::example()
+        );
+        drop(stream);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", feature = "toml"))]
+    async fn test_watch_events() {
+        let dir = temp_dir().join("config-file-watch-events");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let mut stream = watch_events(&path).unwrap();
+        let path = absolute(&path).unwrap();
+        assert_eq!(
+            next(&mut stream).await.unwrap().unwrap(),
+            WatchEvent::Loaded { path: path.clone() }
+        );
+
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        let reloaded = WatchEvent::Reloaded {
+            path: path.clone(),
+            diff: vec![Change {
+                key: "port".into(),
+                old: Some(ConfigValue::Integer(443)),
+                new: Some(ConfigValue::Integer(8080)),
+            }],
+        };
+        loop {
+            if next(&mut stream).await.unwrap().unwrap() == reloaded {
+                break;
+            }
+        }
+        let serialized = crate::value::to_value(&reloaded).unwrap();
+        assert_eq!(serialized.as_map().unwrap()["event"], "reloaded".into());
+
+        std::fs::remove_file(&path).unwrap();
+        loop {
+            if let WatchEvent::Removed { .. } = next(&mut stream).await.unwrap().unwrap() {
+                break;
+            }
+        }
+        drop(stream);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = crate::value::to_value(&TestConfig::example()).unwrap();
+        let mut new = old.clone();
+        let map = new.as_map_mut().unwrap();
+        crate::value::remove(map, "host");
+        map.insert("inner".into(), ConfigValue::Map(Default::default()));
+        assert_eq!(
+            diff(&old, &new),
+            [
+                Change {
+                    key: "host".into(),
+                    old: Some("example.com".into()),
+                    new: None,
+                },
+                Change {
+                    key: "inner.answer".into(),
+                    old: Some(ConfigValue::Integer(42)),
+                    new: None,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", feature = "toml"))]
+    async fn test_cancellation() {
+        let dir = temp_dir().join("config-file-watch-cancel");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let token = CancellationToken::new();
+        let options = WatchOptions::new().cancellation_token(token.clone());
+        let mut stream = watch_config_with_options::<TestConfig>(&path, options).unwrap();
+        token.cancel();
+        assert!(next(&mut stream).await.is_none());
+        assert!(stream.watcher.is_none());
+
+        let mut stream = watch_config::<TestConfig>(&path).unwrap();
+        stream.close();
+        assert!(next(&mut stream).await.unwrap().is_ok());
+        assert!(next(&mut stream).await.is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_config_watcher() {
+        let dir = temp_dir().join("config-file-watcher");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let watcher = ConfigWatcher::<TestConfig>::new(&path).unwrap();
+        assert_eq!(*watcher.get(), TestConfig::example());
+
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        wait(&|| watcher.get().port == 8080);
+
+        std::fs::write(&path, "port = \"broken\"").unwrap();
+        wait(&|| watcher.take_error().is_some());
+        assert_eq!(watcher.get().port, 8080);
+        drop(watcher);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", feature = "toml"))]
+    async fn test_debounce() {
+        let dir = temp_dir().join("config-file-watch-debounce");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let options = WatchOptions::new().debounce(Duration::from_millis(200));
+        let mut stream = watch_events_with_options(&path, options).unwrap();
+        assert!(matches!(
+            next(&mut stream).await.unwrap().unwrap(),
+            WatchEvent::Loaded { .. }
+        ));
+        // Save the way vim does: move the file away, then write a new one
+        let backup = dir.join("config.toml~");
+        std::fs::rename(&path, &backup).unwrap();
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        std::fs::remove_file(backup).unwrap();
+        let WatchEvent::Reloaded { diff, .. } = next(&mut stream).await.unwrap().unwrap() else {
+            panic!("expected a single reload");
+        };
+        assert_eq!(diff[0].new, Some(ConfigValue::Integer(8080)));
+        let more = tokio::time::timeout(Duration::from_millis(500), stream.recv()).await;
+        assert!(more.is_err());
+        drop(stream);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", feature = "toml"))]
+    async fn test_into_channel() {
+        let dir = temp_dir().join("config-file-watch-channel");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let watcher = ConfigWatcher::<TestConfig>::new(&path).unwrap();
+        let mut receiver = watcher.into_channel();
+        assert_eq!(**receiver.borrow(), TestConfig::example());
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), receiver.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receiver.borrow_and_update().port, 8080);
+        drop(receiver);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "hot", feature = "toml"))]
+    fn test_hot() {
+        let dir = temp_dir().join("config-file-watch-hot");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+
+        let watcher = ConfigWatcher::<TestConfig>::new(&path).unwrap();
+        let hot = watcher.hot();
+        assert_eq!(*hot.load_full(), TestConfig::example());
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        let reader = std::thread::spawn(move || {
+            for _ in 0..500 {
+                if hot.load().port == 8080 {
+                    return true;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            false
+        });
+        assert!(reader.join().unwrap());
+        drop(watcher);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_validator() {
+        let dir = temp_dir().join("config-file-watch-validator");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let validate = |config: &TestConfig| match config.port {
+            0 => Err("port must be positive".to_string()),
+            _ => Ok(()),
+        };
+        let watcher = ConfigWatcher::with_validator(&path, WatchOptions::new(), validate).unwrap();
+
+        let mut changed = TestConfig::example();
+        changed.port = 0;
+        changed.to_config_file(&path).unwrap();
+        let err = (0..500)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                watcher.take_error()
+            })
+            .unwrap();
+        assert!(
+            matches!(err, ConfigFileError::Validation(message) if message.contains("positive"))
+        );
+        assert_eq!(watcher.get().port, 443);
+        drop(watcher);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_watch_dir() {
+        let dir = temp_dir().join("config-file-watch-dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TestConfig::example()
+            .to_config_file(dir.join("10-base.toml"))
+            .unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let watcher = ConfigWatcher::<TestConfig>::dir(&dir).unwrap();
+        assert_eq!(*watcher.get(), TestConfig::example());
+
+        let fragment = dir.join("20-port.toml");
+        std::fs::write(&fragment, "port = 8080").unwrap();
+        wait(&|| watcher.get().port == 8080);
+        assert_eq!(watcher.get().host, "example.com");
+
+        std::fs::write(&fragment, "port = \"broken\"").unwrap();
+        wait(&|| watcher.take_error().is_some());
+        assert_eq!(watcher.get().port, 8080);
+
+        std::fs::remove_file(&fragment).unwrap();
+        wait(&|| watcher.get().port == 443);
+        drop(watcher);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_change_events() {
+        let dir = temp_dir().join("config-file-watch-changes");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let mut events = ChangeEvents::new(&path).unwrap();
+        let timeout = Duration::from_secs(5);
+
+        TestConfig::example().to_config_file(&path).unwrap();
+        let event = events.next_timeout(timeout);
+        assert!(
+            matches!(event, Some(ChangeEvent::Created(changed)) if changed.ends_with("config.toml"))
+        );
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        let event = events.next_timeout(timeout);
+        assert!(matches!(event, Some(ChangeEvent::Modified(_))));
+        std::fs::write(&path, "port = ").unwrap();
+        let event = events.next_timeout(timeout);
+        assert!(matches!(event, Some(ChangeEvent::ParseFailed(_))));
+        std::fs::remove_file(&path).unwrap();
+        let event = events.next_timeout(timeout);
+        assert!(matches!(event, Some(ChangeEvent::Removed(_))));
+        assert!(events.try_next().is_none());
+        drop(events);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_on_change() {
+        let dir = temp_dir().join("config-file-watch-on-change");
+        let path = dir.join("config.toml");
+        TestConfig::example().to_config_file(&path).unwrap();
+        let watcher = ConfigWatcher::<TestConfig>::new(&path).unwrap();
+        let (sender, receiver) = channel();
+        watcher.on_change(move |old, new| {
+            let _ = sender.send((old.port, new.port));
+        });
+
+        let mut changed = TestConfig::example();
+        changed.port = 8080;
+        changed.to_config_file(&path).unwrap();
+        let ports = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(ports, (443, 8080));
+        std::fs::write(&path, "port = \"broken\"").unwrap();
+        assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+        drop(watcher);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
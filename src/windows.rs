@@ -0,0 +1,50 @@
+//! Windows file attributes of the stored files.
+//!
+//! Files are opened with the default share mode of the standard library,
+//! which lets other readers and writers open them, and lets them be renamed
+//! or deleted while open. Other programs opening them without sharing make
+//! replacing them fail with a sharing violation, which is retried when a
+//! [`crate::RetryPolicy`] is given.
+
+use std::{io::ErrorKind, os::windows::fs::MetadataExt, path::Path};
+
+use crate::{ConfigFileError, StoreOptions};
+
+const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+/// The attributes to create the file replacing the one located at @path
+/// with: those requested by @options, or else the ones of the file being
+/// replaced
+pub(crate) fn attributes(path: &Path, options: &StoreOptions) -> u32 {
+    let existing = std::fs::metadata(path).map_or(0, |metadata| metadata.file_attributes());
+    let mut attributes = existing & (FILE_ATTRIBUTE_READONLY | FILE_ATTRIBUTE_HIDDEN);
+    for (attribute, enabled) in [
+        (FILE_ATTRIBUTE_READONLY, options.read_only),
+        (FILE_ATTRIBUTE_HIDDEN, options.hidden),
+    ] {
+        match enabled {
+            Some(true) => attributes |= attribute,
+            Some(false) => attributes &= !attribute,
+            None => {}
+        }
+    }
+    attributes
+}
+
+/// Clear the read-only attribute of the file located at @path, if any, since
+/// read-only files can be neither replaced nor removed
+pub(crate) fn make_writable(path: &Path) -> Result<(), ConfigFileError> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => {
+            let mut permissions = metadata.permissions();
+            // This module is Windows only, where this merely clears the
+            // read-only attribute
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            Ok(std::fs::set_permissions(path, permissions)?)
+        }
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}